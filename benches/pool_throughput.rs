@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use load_balancer::{ConnString, ConnectionPool};
+
+const TASKS: usize = 256;
+const ROUNDS: usize = 200;
+const POOL_SIZE: usize = 32;
+
+// The pool as it was before the redesign: one mutex per backend, held across the connect
+// on a miss.
+struct LockedPool {
+    address: String,
+    idle: Mutex<VecDeque<TcpStream>>,
+}
+
+impl LockedPool {
+    async fn connect(&self) -> TcpStream {
+        let mut idle = self.idle.lock().await;
+        match idle.pop_back() {
+            Some(stream) => stream,
+            None => TcpStream::connect(&self.address).await.unwrap(),
+        }
+    }
+
+    async fn release(&self, stream: TcpStream) {
+        let mut idle = self.idle.lock().await;
+        if idle.len() < POOL_SIZE {
+            idle.push_back(stream);
+        }
+    }
+}
+
+async fn sink_backend() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 64];
+                while let Ok(1..) = stream.read(&mut buf).await {}
+            });
+        }
+    });
+    addr
+}
+
+async fn locked_pool(addr: SocketAddr) -> Duration {
+    let locked = Arc::new(LockedPool {
+        address: addr.to_string(),
+        idle: Mutex::new(VecDeque::new()),
+    });
+    let started = Instant::now();
+    let handles: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let locked = Arc::clone(&locked);
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let stream = locked.connect().await;
+                    tokio::task::yield_now().await;
+                    locked.release(stream).await;
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    started.elapsed()
+}
+
+async fn connection_pool(addr: SocketAddr) -> Duration {
+    let pool = ConnectionPool::new(
+        vec![ConnString::new(addr.ip().to_string(), addr.port())],
+        POOL_SIZE,
+    );
+    let started = Instant::now();
+    let handles: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let pooled = pool.connect(0, None).await.unwrap();
+                    tokio::task::yield_now().await;
+                    pool.release(0, pooled);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    started.elapsed()
+}
+
+// cargo bench --bench pool_throughput
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(8)
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let addr = sink_backend().await;
+        let checkouts = (TASKS * ROUNDS) as f64;
+        for (name, elapsed) in [
+            ("mutex pool", locked_pool(addr).await),
+            ("connection pool", connection_pool(addr).await),
+        ] {
+            println!(
+                "{}: {:.0} checkouts/s ({} tasks x {} rounds in {:?})",
+                name,
+                checkouts / elapsed.as_secs_f64(),
+                TASKS,
+                ROUNDS,
+                elapsed
+            );
+        }
+    });
+}
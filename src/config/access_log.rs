@@ -0,0 +1,187 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+// 1 TiB; anything larger would overflow once converted to bytes.
+const MAX_FILE_SIZE_MB: u64 = 1024 * 1024;
+
+// Every field of an access log record; templates may only reference these.
+pub const ACCESS_LOG_FIELDS: [&str; 13] = [
+    "timestamp",
+    "id",
+    "uuid",
+    "target",
+    "status",
+    "user_id",
+    "client",
+    "backend",
+    "bytes_received",
+    "bytes",
+    "time_taken",
+    "http_status",
+    "reason",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogFormat {
+    #[default]
+    Json,
+    Template(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogSink {
+    #[default]
+    Stdout,
+    File(FileSinkConfig),
+    Syslog(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FileSinkConfig {
+    pub path: String,
+    #[serde(default = "default_max_size_mb")]
+    pub max_size_mb: u64,
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccessLogConfig {
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    #[serde(default)]
+    pub sink: AccessLogSink,
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+}
+
+fn default_max_size_mb() -> u64 {
+    100
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+impl AccessLogConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(ConfigError::invalid(
+                format!("{}.sample_rate", prefix),
+                "must be between 0 and 1",
+            ));
+        }
+        if let AccessLogFormat::Template(template) = &self.format {
+            for field in template_fields(template) {
+                if !ACCESS_LOG_FIELDS.contains(&field) {
+                    return Err(ConfigError::invalid(
+                        format!("{}.format", prefix),
+                        format!("unknown field {{{}}} in template", field),
+                    ));
+                }
+            }
+        }
+        match &self.sink {
+            AccessLogSink::File(file) if file.max_size_mb > MAX_FILE_SIZE_MB => {
+                Err(ConfigError::invalid(
+                    format!("{}.sink.file.max_size_mb", prefix),
+                    format!("must be at most {}", MAX_FILE_SIZE_MB),
+                ))
+            }
+            AccessLogSink::File(file) if file.path.is_empty() || file.max_size_mb == 0 => {
+                Err(ConfigError::invalid(
+                    format!("{}.sink.file", prefix),
+                    "path must not be empty and max_size_mb must be greater than 0",
+                ))
+            }
+            AccessLogSink::Syslog(address) if address.is_empty() => Err(ConfigError::invalid(
+                format!("{}.sink.syslog", prefix),
+                "must be a socket path or host:port",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Names between `{` and `}` in a template, in order of appearance.
+pub fn template_fields(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(field, _)| field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> AccessLogConfig {
+        toml::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn defaults_test() {
+        let config = parse("");
+        assert_eq!(config.format, AccessLogFormat::Json);
+        assert_eq!(config.sink, AccessLogSink::Stdout);
+        assert_eq!(config.sample_rate, 1.0);
+        assert!(config.validate("access_log").is_ok());
+    }
+
+    #[test]
+    fn parse_sinks_test() {
+        let config = parse(r#"sink = { file = { path = "/var/log/lb/access.log" } }"#);
+        assert_eq!(
+            config.sink,
+            AccessLogSink::File(FileSinkConfig {
+                path: "/var/log/lb/access.log".to_string(),
+                max_size_mb: 100,
+                max_files: 5,
+            })
+        );
+        let config = parse(r#"sink = { syslog = "/dev/log" }"#);
+        assert_eq!(config.sink, AccessLogSink::Syslog("/dev/log".to_string()));
+    }
+
+    #[test]
+    fn validate_template_test() {
+        let config = parse(r#"format = { template = "{client} -> {backend} {reason}" }"#);
+        assert!(config.validate("access_log").is_ok());
+
+        let config = parse(r#"format = { template = "{client} {latency}" }"#);
+        assert_eq!(
+            config.validate("access_log").unwrap_err(),
+            ConfigError::invalid("access_log.format", "unknown field {latency} in template")
+        );
+    }
+
+    #[test]
+    fn validate_max_size_test() {
+        let config = parse(r#"sink = { file = { path = "access.log", max_size_mb = 1048576 } }"#);
+        assert!(config.validate("access_log").is_ok());
+
+        let config = parse(
+            r#"sink = { file = { path = "access.log", max_size_mb = 18446744073709551615 } }"#,
+        );
+        assert_eq!(
+            config.validate("access_log").unwrap_err(),
+            ConfigError::invalid(
+                "access_log.sink.file.max_size_mb",
+                "must be at most 1048576"
+            )
+        );
+    }
+
+    #[test]
+    fn validate_sample_rate_test() {
+        let config = parse("sample_rate = 1.5");
+        assert!(config.validate("access_log").is_err());
+    }
+}
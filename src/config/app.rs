@@ -1,46 +1,409 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::config::access_log::AccessLogConfig;
+use crate::config::error::ConfigError;
+use crate::config::listener::{ListenerConfig, ListenerMode};
+use crate::config::pool::PoolConfig;
+use crate::config::retry::RetryConfig;
 use crate::config::router_map::RouterMap;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &str) -> Option<ConfigFormat> {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Some(ConfigFormat::Toml),
+            Some("yaml") | Some("yml") => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
     pub listen_addr: Option<String>,
+    #[serde(default)]
+    pub mode: ListenerMode,
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+    pub admin_addr: Option<String>,
+    #[serde(rename = "request_timeout_sec", default = "default_request_timeout")]
     pub request_timout_sec: u64,
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout_sec: u64,
+    #[serde(default = "default_request_log_size")]
+    pub request_log_size: usize,
+    pub access_log: Option<AccessLogConfig>,
     pub router_map: Option<RouterMap>,
+    #[serde(default)]
+    pub groups: BTreeMap<String, PoolConfig>,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(skip)]
     is_built: bool,
 }
 
+fn default_request_timeout() -> u64 {
+    30
+}
+
+fn default_drain_timeout() -> u64 {
+    30
+}
+
+fn default_request_log_size() -> usize {
+    1000
+}
+
+impl Default for AppConfig {
+    fn default() -> AppConfig {
+        AppConfig::new()
+    }
+}
+
 impl AppConfig {
     pub fn new() -> AppConfig {
         AppConfig {
             listen_addr: None,
-            request_timout_sec: 30,
+            mode: ListenerMode::default(),
+            listeners: Vec::new(),
+            admin_addr: None,
+            request_timout_sec: default_request_timeout(),
+            drain_timeout_sec: default_drain_timeout(),
+            request_log_size: default_request_log_size(),
+            access_log: None,
             router_map: None,
+            groups: BTreeMap::new(),
+            retry: RetryConfig::default(),
             is_built: false,
         }
     }
 
-    pub fn router(&mut self, router_map: RouterMap) {
-        self.router_map = Some(router_map);
+    pub fn from_file(path: &str) -> Result<AppConfig, ConfigError> {
+        let format =
+            ConfigFormat::from_path(path).ok_or_else(|| ConfigError::UnsupportedFormat {
+                path: path.to_string(),
+            })?;
+        let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+            path: path.to_string(),
+            message: e.to_string(),
+        })?;
+        AppConfig::parse(&contents, format).map_err(|e| match e {
+            ConfigError::Parse { message, .. } => ConfigError::Parse {
+                path: path.to_string(),
+                message,
+            },
+            other => other,
+        })
+    }
+
+    pub fn parse(contents: &str, format: ConfigFormat) -> Result<AppConfig, ConfigError> {
+        let parsed = match format {
+            ConfigFormat::Toml => toml::from_str::<AppConfig>(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => {
+                serde_yaml::from_str::<AppConfig>(contents).map_err(|e| e.to_string())
+            }
+        };
+        let mut config = parsed.map_err(|message| ConfigError::Parse {
+            path: "<inline>".to_string(),
+            message,
+        })?;
+        config.build()?;
+        Ok(config)
     }
 
-    pub fn listener(&mut self, listen_addr: String) {
-        self.listen_addr = Some(listen_addr);
+    pub fn router(&mut self, router_map: RouterMap) {
+        self.router_map = Some(router_map);
     }
 
     pub fn request_timeout(&mut self, timeout_ms: u64) {
         self.request_timout_sec = timeout_ms;
     }
 
-    pub fn build(&mut self) {
-        if self.listen_addr.is_none() {
-            panic!("Listener address is not provided");
+    pub fn build(&mut self) -> Result<(), ConfigError> {
+        if self.listen_addr.is_none() && self.listeners.is_empty() {
+            return Err(ConfigError::MissingField { field: "listeners" });
+        }
+        if let Some(listen_addr) = &self.listen_addr
+            && listen_addr.parse::<SocketAddr>().is_err()
+        {
+            return Err(ConfigError::invalid(
+                "listen_addr",
+                format!("'{}' is not a valid socket address", listen_addr),
+            ));
+        }
+        if let Some(admin_addr) = &self.admin_addr
+            && admin_addr.parse::<SocketAddr>().is_err()
+        {
+            return Err(ConfigError::invalid(
+                "admin_addr",
+                format!("'{}' is not a valid socket address", admin_addr),
+            ));
+        }
+        if self.request_timout_sec == 0 {
+            return Err(ConfigError::invalid(
+                "request_timeout_sec",
+                "must be greater than 0",
+            ));
+        }
+        if self.groups.is_empty() {
+            return Err(ConfigError::MissingField { field: "groups" });
         }
-        if self.router_map.is_none() {
-            panic!("Router map is not provided");
+        for (name, group) in &self.groups {
+            group.validate(&format!("groups.{}", name))?;
+        }
+
+        let known_group = |group: &str| self.groups.contains_key(group);
+        for (idx, listener) in self.listeners.iter().enumerate() {
+            listener.validate(&format!("listeners[{}]", idx), known_group)?;
+        }
+        let listeners = self.all_listeners();
+        for (idx, listener) in listeners.iter().enumerate() {
+            if listeners[..idx]
+                .iter()
+                .any(|other| other.address == listener.address)
+            {
+                return Err(ConfigError::invalid(
+                    "listeners",
+                    format!(
+                        "address '{}' is used by more than one listener",
+                        listener.address
+                    ),
+                ));
+            }
+        }
+        match &self.router_map {
+            Some(router_map) => router_map.validate("router_map", known_group)?,
+            None if listeners
+                .iter()
+                .any(|listener| listener.router_map.is_none()) =>
+            {
+                return Err(ConfigError::MissingField {
+                    field: "router_map",
+                });
+            }
+            None => {}
+        }
+        self.retry.validate("retry")?;
+        if let Some(access_log) = &self.access_log {
+            access_log.validate("access_log")?;
         }
         self.is_built = true;
+        Ok(())
+    }
+
+    // The top-level `listen_addr`/`mode` pair is shorthand for a single listener.
+    pub fn all_listeners(&self) -> Vec<ListenerConfig> {
+        self.listen_addr
+            .iter()
+            .map(|address| ListenerConfig::new(address, self.mode))
+            .chain(self.listeners.iter().cloned())
+            .collect()
     }
 
     pub fn is_built(&self) -> bool {
         self.is_built
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::access_log::AccessLogSink;
+
+    const TOML_CONFIG: &str = r#"
+listen_addr = "127.0.0.1:8080"
+mode = "http"
+request_timeout_sec = 15
+
+[router_map]
+default_group = "web"
+
+[[router_map.routes]]
+path_prefix = "/api"
+group = "api"
+
+[groups.web]
+max_pool_size = 4
+backends = ["127.0.0.1:3000", "127.0.0.1:3001"]
+
+[groups.api]
+backends = ["127.0.0.1:4000"]
+"#;
+
+    const YAML_CONFIG: &str = r#"
+listen_addr: "127.0.0.1:8080"
+router_map:
+  default_group: web
+groups:
+  web:
+    backends:
+      - "127.0.0.1:3000"
+"#;
+
+    #[test]
+    fn parse_toml_test() {
+        let config = AppConfig::parse(TOML_CONFIG, ConfigFormat::Toml).unwrap();
+        assert!(config.is_built());
+        assert_eq!(config.listen_addr.as_deref(), Some("127.0.0.1:8080"));
+        assert_eq!(config.mode, ListenerMode::Http);
+        assert_eq!(config.request_timout_sec, 15);
+
+        let pool = &config.groups["web"];
+        assert_eq!(pool.max_pool_size, 4);
+        assert_eq!(pool.backends.len(), 2);
+        assert_eq!(pool.backends[1].address(), "127.0.0.1:3001");
+        assert_eq!(config.groups["api"].backends.len(), 1);
+    }
+
+    #[test]
+    fn parse_yaml_test() {
+        let config = AppConfig::parse(YAML_CONFIG, ConfigFormat::Yaml).unwrap();
+        assert_eq!(config.request_timout_sec, 30);
+        assert_eq!(config.drain_timeout_sec, 30);
+        assert_eq!(config.request_log_size, 1000);
+        assert_eq!(config.mode, ListenerMode::Tcp);
+        assert_eq!(config.retry.attempts, 3);
+        assert_eq!(config.groups["web"].max_pool_size, 10);
+    }
+
+    #[test]
+    fn format_from_path_test() {
+        assert_eq!(ConfigFormat::from_path("lb.toml"), Some(ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::from_path("lb.yml"), Some(ConfigFormat::Yaml));
+        assert_eq!(ConfigFormat::from_path("lb.json"), None);
+    }
+
+    #[test]
+    fn build_missing_listener_test() {
+        let mut config = AppConfig::new();
+        config.router(RouterMap::new());
+        assert_eq!(
+            config.build(),
+            Err(ConfigError::MissingField { field: "listeners" })
+        );
+        assert!(!config.is_built());
+    }
+
+    #[test]
+    fn build_rejects_invalid_backend_test() {
+        let contents = TOML_CONFIG.replace("127.0.0.1:3001", "127.0.0.1:0");
+        let error = AppConfig::parse(&contents, ConfigFormat::Toml).unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::InvalidValue { ref field, .. } if field == "groups.web.backends[1]"
+        ));
+    }
+
+    #[test]
+    fn build_rejects_unknown_route_group_test() {
+        let contents = TOML_CONFIG.replace("group = \"api\"", "group = \"missing\"");
+        let error = AppConfig::parse(&contents, ConfigFormat::Toml).unwrap_err();
+        assert_eq!(
+            error,
+            ConfigError::invalid(
+                "router_map.routes[0].group",
+                "unknown backend group 'missing'"
+            )
+        );
+    }
+
+    #[test]
+    fn parse_listeners_test() {
+        let contents = format!(
+            "{}{}",
+            TOML_CONFIG,
+            r#"
+[[listeners]]
+address = "127.0.0.1:9000"
+
+[[listeners]]
+address = "127.0.0.1:9001"
+mode = "http"
+router_map = { default_group = "api" }
+"#
+        );
+        let config = AppConfig::parse(&contents, ConfigFormat::Toml).unwrap();
+        let listeners = config.all_listeners();
+        assert_eq!(listeners.len(), 3);
+        assert_eq!(listeners[0].address, "127.0.0.1:8080");
+        assert_eq!(listeners[0].mode, ListenerMode::Http);
+        assert_eq!(listeners[1].mode, ListenerMode::Tcp);
+        assert!(listeners[1].router_map.is_none());
+        assert!(listeners[2].router_map.is_some());
+    }
+
+    #[test]
+    fn build_rejects_duplicate_listener_test() {
+        let contents = format!(
+            "{}\n[[listeners]]\naddress = \"127.0.0.1:8080\"\n",
+            TOML_CONFIG
+        );
+        let error = AppConfig::parse(&contents, ConfigFormat::Toml).unwrap_err();
+        assert_eq!(
+            error,
+            ConfigError::invalid(
+                "listeners",
+                "address '127.0.0.1:8080' is used by more than one listener"
+            )
+        );
+    }
+
+    #[test]
+    fn build_requires_router_map_per_listener_test() {
+        let contents = r#"
+[[listeners]]
+address = "127.0.0.1:9000"
+router_map = { default_group = "web" }
+
+[[listeners]]
+address = "127.0.0.1:9001"
+
+[groups.web]
+backends = ["127.0.0.1:3000"]
+"#;
+        let error = AppConfig::parse(contents, ConfigFormat::Toml).unwrap_err();
+        assert_eq!(
+            error,
+            ConfigError::MissingField {
+                field: "router_map"
+            }
+        );
+    }
+
+    #[test]
+    fn parse_access_log_test() {
+        let contents = format!(
+            "{}\n[access_log]\nsample_rate = 0.1\nsink = {{ syslog = \"127.0.0.1:514\" }}\n",
+            TOML_CONFIG
+        );
+        let config = AppConfig::parse(&contents, ConfigFormat::Toml).unwrap();
+        let access_log = config.access_log.unwrap();
+        assert_eq!(access_log.sample_rate, 0.1);
+        assert_eq!(
+            access_log.sink,
+            AccessLogSink::Syslog("127.0.0.1:514".to_string())
+        );
+
+        let contents = contents.replace("sample_rate = 0.1", "sample_rate = 2");
+        let error = AppConfig::parse(&contents, ConfigFormat::Toml).unwrap_err();
+        assert_eq!(
+            error,
+            ConfigError::invalid("access_log.sample_rate", "must be between 0 and 1")
+        );
+    }
+
+    #[test]
+    fn parse_reports_bad_address_test() {
+        let contents = TOML_CONFIG.replace("127.0.0.1:3001", "localhost");
+        let error = AppConfig::parse(&contents, ConfigFormat::Toml).unwrap_err();
+        assert!(matches!(error, ConfigError::Parse { .. }));
+    }
+}
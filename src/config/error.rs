@@ -0,0 +1,57 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Read { path: String, message: String },
+    Parse { path: String, message: String },
+    UnsupportedFormat { path: String },
+    MissingField { field: &'static str },
+    InvalidValue { field: String, message: String },
+}
+
+impl ConfigError {
+    pub fn invalid(field: impl Into<String>, message: impl Into<String>) -> ConfigError {
+        ConfigError::InvalidValue {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, message } => {
+                write!(f, "failed to read config file {}: {}", path, message)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "failed to parse config file {}: {}", path, message)
+            }
+            ConfigError::UnsupportedFormat { path } => write!(
+                f,
+                "unsupported config format for {}: expected .toml, .yaml or .yml",
+                path
+            ),
+            ConfigError::MissingField { field } => write!(f, "missing required field `{}`", field),
+            ConfigError::InvalidValue { field, message } => {
+                write!(f, "invalid value for `{}`: {}", field, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_test() {
+        let error = ConfigError::invalid("pool.max_pool_size", "must be greater than 0");
+        assert_eq!(
+            error.to_string(),
+            "invalid value for `pool.max_pool_size`: must be greater than 0"
+        );
+    }
+}
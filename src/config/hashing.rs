@@ -0,0 +1,103 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashKey {
+    #[default]
+    SessionId,
+    ClientIp,
+    Header(String),
+    Cookie(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HashingConfig {
+    #[serde(default)]
+    pub key: HashKey,
+    #[serde(default = "default_virtual_nodes")]
+    pub virtual_nodes: usize,
+    #[serde(default = "default_table_size")]
+    pub table_size: usize,
+}
+
+fn default_virtual_nodes() -> usize {
+    160
+}
+
+fn default_table_size() -> usize {
+    65537
+}
+
+impl Default for HashingConfig {
+    fn default() -> HashingConfig {
+        HashingConfig {
+            key: HashKey::default(),
+            virtual_nodes: default_virtual_nodes(),
+            table_size: default_table_size(),
+        }
+    }
+}
+
+impl HashingConfig {
+    pub fn validate(&self, prefix: &str, backends: usize) -> Result<(), ConfigError> {
+        if let HashKey::Header(name) | HashKey::Cookie(name) = &self.key
+            && name.is_empty()
+        {
+            return Err(ConfigError::invalid(
+                format!("{}.key", prefix),
+                "header and cookie names must not be empty",
+            ));
+        }
+        if self.virtual_nodes == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.virtual_nodes", prefix),
+                "must be greater than 0",
+            ));
+        }
+        if !is_prime(self.table_size) || self.table_size < backends * 10 {
+            return Err(ConfigError::invalid(
+                format!("{}.table_size", prefix),
+                "must be a prime at least 10 times the number of backends",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn is_prime(n: usize) -> bool {
+    n >= 2
+        && (2..)
+            .take_while(|i| i * i <= n)
+            .all(|i| !n.is_multiple_of(i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_key_deserialize_test() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            key: HashKey,
+        }
+
+        let parsed: Wrapper = toml::from_str(r#"key = "client_ip""#).unwrap();
+        assert_eq!(parsed.key, HashKey::ClientIp);
+        let parsed: Wrapper = toml::from_str(r#"key = { cookie = "sid" }"#).unwrap();
+        assert_eq!(parsed.key, HashKey::Cookie("sid".to_string()));
+    }
+
+    #[test]
+    fn validate_table_size_test() {
+        let mut config = HashingConfig::default();
+        assert!(config.validate("hashing", 20).is_ok());
+
+        config.table_size = 65536;
+        assert!(config.validate("hashing", 20).is_err());
+        config.table_size = 13;
+        assert!(config.validate("hashing", 20).is_err());
+    }
+}
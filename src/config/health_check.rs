@@ -0,0 +1,153 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeKind {
+    Tcp,
+    Http,
+    // grpc.health.v1.Health/Check over HTTP/2.
+    Grpc,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(rename = "type", default = "default_kind")]
+    pub kind: ProbeKind,
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_rise")]
+    pub rise: u32,
+    #[serde(default = "default_fall")]
+    pub fall: u32,
+    #[serde(default = "default_http_path")]
+    pub http_path: String,
+    #[serde(default = "default_expected_status")]
+    pub expected_status: u16,
+    // Empty asks about the server as a whole.
+    #[serde(default)]
+    pub grpc_service: String,
+}
+
+fn default_kind() -> ProbeKind {
+    ProbeKind::Tcp
+}
+
+fn default_interval_ms() -> u64 {
+    5000
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_rise() -> u32 {
+    2
+}
+
+fn default_fall() -> u32 {
+    3
+}
+
+fn default_http_path() -> String {
+    "/".to_string()
+}
+
+fn default_expected_status() -> u16 {
+    200
+}
+
+impl HealthCheckConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.interval_ms == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.interval_ms", prefix),
+                "must be greater than 0",
+            ));
+        }
+        if self.timeout_ms == 0 || self.timeout_ms > self.interval_ms {
+            return Err(ConfigError::invalid(
+                format!("{}.timeout_ms", prefix),
+                "must be greater than 0 and not exceed interval_ms",
+            ));
+        }
+        if self.rise == 0 || self.fall == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.rise", prefix),
+                "rise and fall must be greater than 0",
+            ));
+        }
+        if !self.http_path.starts_with('/') {
+            return Err(ConfigError::invalid(
+                format!("{}.http_path", prefix),
+                "must start with '/'",
+            ));
+        }
+        if !(100..=599).contains(&self.expected_status) {
+            return Err(ConfigError::invalid(
+                format!("{}.expected_status", prefix),
+                "must be a valid HTTP status code",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> HealthCheckConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn http_probe_test() {
+        let config = parse("type = \"http\"\nhttp_path = \"/health\"\nexpected_status = 204");
+        assert_eq!(config.kind, ProbeKind::Http);
+        assert_eq!((config.interval_ms, config.timeout_ms), (5000, 1000));
+        assert_eq!((config.rise, config.fall), (2, 3));
+        assert!(config.validate("health_check").is_ok());
+
+        assert_eq!(
+            parse("http_path = \"health\"").validate("health_check"),
+            Err(ConfigError::invalid(
+                "health_check.http_path",
+                "must start with '/'"
+            ))
+        );
+        for (status, valid) in [(99, false), (100, true), (599, true), (600, false)] {
+            let config = parse(&format!("expected_status = {}", status));
+            assert_eq!(config.validate("health_check").is_ok(), valid, "{}", status);
+        }
+    }
+
+    #[test]
+    fn timeout_within_interval_test() {
+        for (interval_ms, timeout_ms, valid) in
+            [(1000, 1000, true), (1000, 1001, false), (1000, 0, false)]
+        {
+            let config = parse(&format!(
+                "interval_ms = {}\ntimeout_ms = {}",
+                interval_ms, timeout_ms
+            ));
+            assert_eq!(
+                config.validate("health_check").is_ok(),
+                valid,
+                "{} {}",
+                interval_ms,
+                timeout_ms
+            );
+        }
+        assert_eq!(
+            parse("interval_ms = 0").validate("health_check"),
+            Err(ConfigError::invalid(
+                "health_check.interval_ms",
+                "must be greater than 0"
+            ))
+        );
+    }
+}
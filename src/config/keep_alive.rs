@@ -0,0 +1,82 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KeepAliveConfig {
+    #[serde(default = "default_idle_timeout_sec")]
+    pub idle_timeout_sec: u64,
+    #[serde(default = "default_max_lifetime_sec")]
+    pub max_lifetime_sec: u64,
+    #[serde(default = "default_max_requests")]
+    pub max_requests: u32,
+}
+
+fn default_idle_timeout_sec() -> u64 {
+    60
+}
+
+fn default_max_lifetime_sec() -> u64 {
+    600
+}
+
+fn default_max_requests() -> u32 {
+    1000
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> KeepAliveConfig {
+        KeepAliveConfig {
+            idle_timeout_sec: default_idle_timeout_sec(),
+            max_lifetime_sec: default_max_lifetime_sec(),
+            max_requests: default_max_requests(),
+        }
+    }
+}
+
+impl KeepAliveConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        for (field, value) in [
+            ("idle_timeout_sec", self.idle_timeout_sec),
+            ("max_lifetime_sec", self.max_lifetime_sec),
+            ("max_requests", self.max_requests as u64),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::invalid(
+                    format!("{}.{}", prefix, field),
+                    "must be greater than 0",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_test() {
+        let config: KeepAliveConfig = toml::from_str("max_requests = 50").unwrap();
+        assert_eq!(config.idle_timeout_sec, 60);
+        assert_eq!(config.max_lifetime_sec, 600);
+        assert_eq!(config.max_requests, 50);
+        assert!(config.validate("keep_alive").is_ok());
+    }
+
+    #[test]
+    fn validate_test() {
+        let config = KeepAliveConfig {
+            idle_timeout_sec: 0,
+            ..KeepAliveConfig::default()
+        };
+        assert_eq!(
+            config.validate("groups.web.keep_alive").unwrap_err(),
+            ConfigError::invalid(
+                "groups.web.keep_alive.idle_timeout_sec",
+                "must be greater than 0"
+            )
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+// What a client gets once the wait queue is full or its wait timed out: `reject` answers
+// HTTP clients with 503 and closes anything else, `reset` aborts the connection with a RST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowAction {
+    #[default]
+    Reject,
+    Reset,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ConnectionLimitConfig {
+    pub max_connections: usize,
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(default = "default_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+    #[serde(default)]
+    pub overflow: OverflowAction,
+}
+
+fn default_queue_size() -> usize {
+    100
+}
+
+fn default_queue_timeout_ms() -> u64 {
+    1000
+}
+
+impl ConnectionLimitConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.max_connections == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.max_connections", prefix),
+                "must be greater than 0",
+            ));
+        }
+        if self.queue_size > 0 && self.queue_timeout_ms == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.queue_timeout_ms", prefix),
+                "must be greater than 0 when queueing is enabled",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> ConnectionLimitConfig {
+        toml::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn defaults_test() {
+        let config = parse("max_connections = 50");
+        assert_eq!(config.max_connections, 50);
+        assert_eq!(config.queue_size, 100);
+        assert_eq!(config.queue_timeout_ms, 1000);
+        assert_eq!(config.overflow, OverflowAction::Reject);
+        assert!(config.validate("limits").is_ok());
+
+        let config = parse("max_connections = 5\nqueue_size = 0\noverflow = \"reset\"");
+        assert_eq!(config.overflow, OverflowAction::Reset);
+    }
+
+    #[test]
+    fn validate_test() {
+        assert_eq!(
+            parse("max_connections = 0").validate("groups.web.limits"),
+            Err(ConfigError::invalid(
+                "groups.web.limits.max_connections",
+                "must be greater than 0"
+            ))
+        );
+        assert!(
+            parse("max_connections = 10\nqueue_timeout_ms = 0")
+                .validate("limits")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+use crate::config::error::ConfigError;
+use crate::config::limits::ConnectionLimitConfig;
+use crate::config::rate_limit::{RateLimitConfig, RateLimitKey};
+use crate::config::router_map::RouterMap;
+use crate::config::tls::TlsConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenerMode {
+    #[default]
+    Tcp,
+    Http,
+    // Routes by the SNI in the ClientHello and forwards the TLS session untouched.
+    TlsPassthrough,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListenerConfig {
+    pub address: String,
+    #[serde(default)]
+    pub mode: ListenerMode,
+    pub router_map: Option<RouterMap>,
+    pub limits: Option<ConnectionLimitConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub tls: Option<TlsConfig>,
+}
+
+impl ListenerConfig {
+    pub fn new(address: &str, mode: ListenerMode) -> ListenerConfig {
+        ListenerConfig {
+            address: address.to_string(),
+            mode,
+            router_map: None,
+            limits: None,
+            rate_limit: None,
+            tls: None,
+        }
+    }
+
+    pub fn validate(
+        &self,
+        prefix: &str,
+        known_group: impl Fn(&str) -> bool,
+    ) -> Result<(), ConfigError> {
+        if self.address.parse::<SocketAddr>().is_err() {
+            return Err(ConfigError::invalid(
+                format!("{}.address", prefix),
+                format!("'{}' is not a valid socket address", self.address),
+            ));
+        }
+        if let Some(router_map) = &self.router_map {
+            router_map.validate(&format!("{}.router_map", prefix), known_group)?;
+        }
+        if let Some(limits) = &self.limits {
+            limits.validate(&format!("{}.limits", prefix))?;
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.validate(&format!("{}.rate_limit", prefix))?;
+            if matches!(rate_limit.key, RateLimitKey::Header(_)) && self.mode != ListenerMode::Http
+            {
+                return Err(ConfigError::invalid(
+                    format!("{}.rate_limit.key", prefix),
+                    "header keys need an HTTP listener",
+                ));
+            }
+        }
+        if let Some(tls) = &self.tls {
+            if self.mode == ListenerMode::TlsPassthrough {
+                return Err(ConfigError::invalid(
+                    format!("{}.tls", prefix),
+                    "passthrough listeners forward TLS without terminating it",
+                ));
+            }
+            tls.validate(&format!("{}.tls", prefix))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_address_test() {
+        let listener = ListenerConfig::new("localhost:80", ListenerMode::Http);
+        assert_eq!(
+            listener.validate("listeners[0]", |_| true),
+            Err(ConfigError::invalid(
+                "listeners[0].address",
+                "'localhost:80' is not a valid socket address"
+            ))
+        );
+        assert!(
+            ListenerConfig::new("127.0.0.1:80", ListenerMode::Tcp)
+                .validate("listeners[0]", |_| true)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn validate_own_router_map_test() {
+        let mut router_map = RouterMap::new();
+        router_map.default_route("missing");
+        let mut listener = ListenerConfig::new("127.0.0.1:80", ListenerMode::Http);
+        listener.router_map = Some(router_map);
+        assert_eq!(
+            listener.validate("listeners[1]", |group| group == "web"),
+            Err(ConfigError::invalid(
+                "listeners[1].router_map.default_group",
+                "unknown backend group 'missing'"
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_header_rate_limit_needs_http_test() {
+        let mut listener = ListenerConfig::new("127.0.0.1:80", ListenerMode::Tcp);
+        listener.rate_limit =
+            Some(toml::from_str("requests_per_sec = 5\nkey = { header = \"X-Api-Key\" }").unwrap());
+        assert_eq!(
+            listener.validate("listeners[0]", |_| true),
+            Err(ConfigError::invalid(
+                "listeners[0].rate_limit.key",
+                "header keys need an HTTP listener"
+            ))
+        );
+        listener.mode = ListenerMode::Http;
+        assert!(listener.validate("listeners[0]", |_| true).is_ok());
+    }
+
+    #[test]
+    fn validate_passthrough_without_tls_test() {
+        let mut listener: ListenerConfig =
+            toml::from_str("address = \"127.0.0.1:443\"\nmode = \"tls_passthrough\"").unwrap();
+        assert_eq!(listener.mode, ListenerMode::TlsPassthrough);
+        assert!(listener.validate("listeners[0]", |_| true).is_ok());
+
+        listener.tls = Some(toml::from_str("certificates = []").unwrap());
+        assert_eq!(
+            listener.validate("listeners[0]", |_| true),
+            Err(ConfigError::invalid(
+                "listeners[0].tls",
+                "passthrough listeners forward TLS without terminating it"
+            ))
+        );
+    }
+}
@@ -1,2 +1,15 @@
+pub mod access_log;
 pub mod app;
+pub mod error;
+pub mod hashing;
+pub mod health_check;
+pub mod keep_alive;
+pub mod limits;
+pub mod listener;
+pub mod outlier_detection;
+pub mod pool;
+pub mod rate_limit;
+pub mod retry;
 pub mod router_map;
+pub mod sticky;
+pub mod tls;
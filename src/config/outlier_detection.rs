@@ -0,0 +1,99 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutlierDetectionConfig {
+    #[serde(default = "default_consecutive_failures")]
+    pub consecutive_failures: u32,
+    #[serde(default = "default_base_ejection_ms")]
+    pub base_ejection_ms: u64,
+    #[serde(default = "default_max_ejection_ms")]
+    pub max_ejection_ms: u64,
+    #[serde(default = "default_max_ejection_percent")]
+    pub max_ejection_percent: u8,
+}
+
+fn default_consecutive_failures() -> u32 {
+    5
+}
+
+fn default_base_ejection_ms() -> u64 {
+    30_000
+}
+
+fn default_max_ejection_ms() -> u64 {
+    300_000
+}
+
+fn default_max_ejection_percent() -> u8 {
+    50
+}
+
+impl OutlierDetectionConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.consecutive_failures == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.consecutive_failures", prefix),
+                "must be greater than 0",
+            ));
+        }
+        if self.base_ejection_ms == 0 || self.base_ejection_ms > self.max_ejection_ms {
+            return Err(ConfigError::invalid(
+                format!("{}.base_ejection_ms", prefix),
+                "must be greater than 0 and not exceed max_ejection_ms",
+            ));
+        }
+        if self.max_ejection_percent > 100 {
+            return Err(ConfigError::invalid(
+                format!("{}.max_ejection_percent", prefix),
+                "must be between 0 and 100",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> OutlierDetectionConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn ejection_time_bounds_test() {
+        assert!(parse("").validate("outlier_detection").is_ok());
+        for (base_ms, max_ms, valid) in [(1000, 1000, true), (2000, 1000, false), (0, 1000, false)]
+        {
+            let config = parse(&format!(
+                "base_ejection_ms = {}\nmax_ejection_ms = {}",
+                base_ms, max_ms
+            ));
+            assert_eq!(
+                config.validate("outlier_detection").is_ok(),
+                valid,
+                "{} {}",
+                base_ms,
+                max_ms
+            );
+        }
+    }
+
+    #[test]
+    fn max_ejection_percent_test() {
+        for (percent, valid) in [(0, true), (100, true), (101, false)] {
+            let config = parse(&format!("max_ejection_percent = {}", percent));
+            assert_eq!(config.validate("outlier_detection").is_ok(), valid);
+        }
+        assert_eq!(
+            parse("max_ejection_percent = 101").validate("outlier_detection"),
+            Err(ConfigError::invalid(
+                "outlier_detection.max_ejection_percent",
+                "must be between 0 and 100"
+            ))
+        );
+        assert!(toml::from_str::<OutlierDetectionConfig>("max_ejection_percent = 300").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+use crate::config::hashing::{HashKey, HashingConfig};
+use crate::config::health_check::HealthCheckConfig;
+use crate::config::keep_alive::KeepAliveConfig;
+use crate::config::limits::ConnectionLimitConfig;
+use crate::config::outlier_detection::OutlierDetectionConfig;
+use crate::config::sticky::StickyConfig;
+use crate::config::tls::BackendTlsConfig;
+use crate::domain::backend_conn::ConnString;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyKind {
+    #[default]
+    RoundRobin,
+    WeightedRoundRobin,
+    LeastConnections,
+    PowerOfTwoChoices,
+    Random,
+    ConsistentHash,
+    Maglev,
+}
+
+// What HTTP listeners speak to the group's backends; TCP listeners ignore it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendProtocol {
+    #[default]
+    Http1,
+    Http2,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolConfig {
+    pub backends: Vec<ConnString>,
+    #[serde(default = "default_max_pool_size")]
+    pub max_pool_size: usize,
+    #[serde(default)]
+    pub protocol: BackendProtocol,
+    #[serde(default)]
+    pub keep_alive: KeepAliveConfig,
+    pub limits: Option<ConnectionLimitConfig>,
+    #[serde(default)]
+    pub strategy: StrategyKind,
+    #[serde(default)]
+    pub hashing: HashingConfig,
+    pub health_check: Option<HealthCheckConfig>,
+    pub outlier_detection: Option<OutlierDetectionConfig>,
+    pub sticky: Option<StickyConfig>,
+    pub tls: Option<BackendTlsConfig>,
+}
+
+fn default_max_pool_size() -> usize {
+    10
+}
+
+impl PoolConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.backends.is_empty() {
+            return Err(ConfigError::invalid(
+                format!("{}.backends", prefix),
+                "at least one backend is required",
+            ));
+        }
+        if self.max_pool_size == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.max_pool_size", prefix),
+                "must be greater than 0",
+            ));
+        }
+        for (idx, backend) in self.backends.iter().enumerate() {
+            if backend.get_host().is_empty() || backend.get_port() == 0 {
+                return Err(ConfigError::invalid(
+                    format!("{}.backends[{}]", prefix, idx),
+                    format!("'{}' is not a valid backend address", backend.address()),
+                ));
+            }
+            if backend.get_weight() == 0 {
+                return Err(ConfigError::invalid(
+                    format!("{}.backends[{}].weight", prefix, idx),
+                    "must be greater than 0",
+                ));
+            }
+        }
+        self.keep_alive
+            .validate(&format!("{}.keep_alive", prefix))?;
+        if let Some(limits) = &self.limits {
+            limits.validate(&format!("{}.limits", prefix))?;
+        }
+        self.hashing
+            .validate(&format!("{}.hashing", prefix), self.backends.len())?;
+        if let Some(health_check) = &self.health_check {
+            health_check.validate(&format!("{}.health_check", prefix))?;
+        }
+        if let Some(outlier_detection) = &self.outlier_detection {
+            outlier_detection.validate(&format!("{}.outlier_detection", prefix))?;
+        }
+        if let Some(sticky) = &self.sticky {
+            if self.hashing.key == HashKey::SessionId {
+                return Err(ConfigError::invalid(
+                    format!("{}.hashing.key", prefix),
+                    "sticky sessions need a client_ip, header or cookie key",
+                ));
+            }
+            sticky.validate(&format!("{}.sticky", prefix))?;
+        }
+        if let Some(tls) = &self.tls {
+            tls.validate(&format!("{}.tls", prefix))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    #[default]
+    ClientIp,
+    // HTTP only; requests without the header fall back to the client IP.
+    Header(String),
+}
+
+// `reject` answers 429 (and closes raw TCP clients), `delay` holds the client until a token
+// frees up (rejecting once that would take longer than `max_delay_ms`), `drop` resets the
+// connection without an answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAction {
+    #[default]
+    Reject,
+    Delay,
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RateLimitConfig {
+    pub requests_per_sec: Option<f64>,
+    pub burst: Option<u32>,
+    pub max_connections_per_ip: Option<usize>,
+    #[serde(default)]
+    pub key: RateLimitKey,
+    #[serde(default)]
+    pub action: RateLimitAction,
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+    #[serde(default = "default_idle_timeout_sec")]
+    pub idle_timeout_sec: u64,
+}
+
+fn default_max_delay_ms() -> u64 {
+    1000
+}
+
+fn default_idle_timeout_sec() -> u64 {
+    60
+}
+
+impl RateLimitConfig {
+    // Defaults to one second worth of requests.
+    pub fn burst(&self) -> f64 {
+        match (self.burst, self.requests_per_sec) {
+            (Some(burst), _) => burst as f64,
+            (None, Some(rate)) => rate.ceil().max(1.0),
+            (None, None) => 1.0,
+        }
+    }
+
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.requests_per_sec.is_none() && self.max_connections_per_ip.is_none() {
+            return Err(ConfigError::invalid(
+                prefix,
+                "requests_per_sec or max_connections_per_ip is required",
+            ));
+        }
+        if self
+            .requests_per_sec
+            .is_some_and(|rate| !rate.is_finite() || rate <= 0.0)
+        {
+            return Err(ConfigError::invalid(
+                format!("{}.requests_per_sec", prefix),
+                "must be greater than 0",
+            ));
+        }
+        for (field, value) in [
+            ("burst", self.burst.map(|burst| burst as usize)),
+            ("max_connections_per_ip", self.max_connections_per_ip),
+        ] {
+            if value == Some(0) {
+                return Err(ConfigError::invalid(
+                    format!("{}.{}", prefix, field),
+                    "must be greater than 0",
+                ));
+            }
+        }
+        if self.idle_timeout_sec == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.idle_timeout_sec", prefix),
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> RateLimitConfig {
+        toml::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn defaults_test() {
+        let config = parse("requests_per_sec = 2.5");
+        assert_eq!(config.burst(), 3.0);
+        assert_eq!(config.key, RateLimitKey::ClientIp);
+        assert_eq!(config.action, RateLimitAction::Reject);
+        assert_eq!(config.max_delay_ms, 1000);
+        assert!(config.validate("rate_limit").is_ok());
+
+        let config = parse(
+            "requests_per_sec = 10\nburst = 50\nkey = { header = \"X-Api-Key\" }\naction = \"delay\"",
+        );
+        assert_eq!(config.burst(), 50.0);
+        assert_eq!(config.key, RateLimitKey::Header("X-Api-Key".to_string()));
+        assert_eq!(config.action, RateLimitAction::Delay);
+    }
+
+    #[test]
+    fn validate_test() {
+        assert_eq!(
+            parse("action = \"drop\"").validate("listeners[0].rate_limit"),
+            Err(ConfigError::invalid(
+                "listeners[0].rate_limit",
+                "requests_per_sec or max_connections_per_ip is required"
+            ))
+        );
+        assert!(
+            parse("requests_per_sec = 0")
+                .validate("rate_limit")
+                .is_err()
+        );
+        assert_eq!(
+            parse("max_connections_per_ip = 0").validate("rate_limit"),
+            Err(ConfigError::invalid(
+                "rate_limit.max_connections_per_ip",
+                "must be greater than 0"
+            ))
+        );
+        assert!(
+            parse("max_connections_per_ip = 4")
+                .validate("rate_limit")
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetryConfig {
+    #[serde(default = "default_attempts")]
+    pub attempts: usize,
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+    #[serde(default = "default_budget_ms")]
+    pub budget_ms: u64,
+}
+
+fn default_attempts() -> usize {
+    3
+}
+
+fn default_connect_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_budget_ms() -> u64 {
+    5000
+}
+
+impl Default for RetryConfig {
+    fn default() -> RetryConfig {
+        RetryConfig {
+            attempts: default_attempts(),
+            connect_timeout_ms: default_connect_timeout_ms(),
+            budget_ms: default_budget_ms(),
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.attempts == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.attempts", prefix),
+                "must be greater than 0",
+            ));
+        }
+        if self.connect_timeout_ms == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.connect_timeout_ms", prefix),
+                "must be greater than 0",
+            ));
+        }
+        if self.budget_ms < self.connect_timeout_ms {
+            return Err(ConfigError::invalid(
+                format!("{}.budget_ms", prefix),
+                "must not be lower than connect_timeout_ms",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> RetryConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn budget_covers_connect_timeout_test() {
+        assert!(RetryConfig::default().validate("retry").is_ok());
+        assert!(parse("connect_timeout_ms = 5000").validate("retry").is_ok());
+        assert_eq!(
+            parse("connect_timeout_ms = 2000\nbudget_ms = 1999").validate("retry"),
+            Err(ConfigError::invalid(
+                "retry.budget_ms",
+                "must not be lower than connect_timeout_ms"
+            ))
+        );
+    }
+
+    #[test]
+    fn attempts_test() {
+        assert_eq!(parse("attempts = 1").attempts, 1);
+        assert_eq!(
+            parse("attempts = 0").validate("retry"),
+            Err(ConfigError::invalid(
+                "retry.attempts",
+                "must be greater than 0"
+            ))
+        );
+        assert_eq!(
+            parse("connect_timeout_ms = 0").validate("retry"),
+            Err(ConfigError::invalid(
+                "retry.connect_timeout_ms",
+                "must be greater than 0"
+            ))
+        );
+    }
+}
@@ -1,31 +1,351 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
 
+use crate::config::error::ConfigError;
 
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Route {
+    pub listener: Option<String>,
+    pub host: Option<String>,
+    pub path_prefix: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub path_regex: Option<Regex>,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    // gRPC calls arrive as POST /<package.Service>/<Method>.
+    pub grpc_service: Option<String>,
+    pub grpc_method: Option<String>,
+    pub group: String,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RouteRequest<'a> {
+    pub listener: &'a str,
+    pub host: Option<&'a str>,
+    pub path: Option<&'a str>,
+    pub method: Option<&'a str>,
+}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct RouterMap {
-    map: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    routes: Vec<Route>,
+    default_group: Option<String>,
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| Regex::new(&pattern).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl Route {
+    // A constraint the request cannot answer (e.g. Host on a raw TCP listener) never matches.
+    pub fn matches(&self, request: &RouteRequest<'_>) -> bool {
+        if let Some(listener) = &self.listener
+            && listener != request.listener
+        {
+            return false;
+        }
+        if let Some(host) = &self.host
+            && !request
+                .host
+                .is_some_and(|request_host| host_matches(host, request_host))
+        {
+            return false;
+        }
+        if let Some(prefix) = &self.path_prefix
+            && !request
+                .path
+                .is_some_and(|path| path.starts_with(prefix.as_str()))
+        {
+            return false;
+        }
+        if let Some(regex) = &self.path_regex
+            && !request.path.is_some_and(|path| regex.is_match(path))
+        {
+            return false;
+        }
+        if (self.grpc_service.is_some() || self.grpc_method.is_some())
+            && !request
+                .path
+                .and_then(grpc_call)
+                .is_some_and(|(service, method)| {
+                    self.grpc_service
+                        .as_ref()
+                        .is_none_or(|name| name == service)
+                        && self.grpc_method.as_ref().is_none_or(|name| name == method)
+                })
+        {
+            return false;
+        }
+        if !self.methods.is_empty()
+            && !request.method.is_some_and(|method| {
+                self.methods
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(method))
+            })
+        {
+            return false;
+        }
+        true
+    }
+}
+
+fn grpc_call(path: &str) -> Option<(&str, &str)> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    if service.is_empty() || method.is_empty() || method.contains('/') {
+        return None;
+    }
+    Some((service, method))
+}
+
+// "*.example.com" matches any subdomain; ports on the request host are ignored.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => {
+            host.len() > suffix.len() + 1
+                && host[host.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+                && host.as_bytes()[host.len() - suffix.len() - 1] == b'.'
+        }
+        None => pattern.eq_ignore_ascii_case(host),
+    }
 }
 
 impl RouterMap {
     pub fn new() -> Self {
         RouterMap {
-            map: std::collections::HashMap::new(),
+            routes: Vec::new(),
+            default_group: None,
         }
     }
 
-    pub fn map_route(&mut self, incoming: &str, backend: &str) {
-        self.map.insert(incoming.to_string(), backend.to_string());
+    pub fn map_route(&mut self, route: Route) {
+        self.routes.push(route);
+    }
+
+    pub fn default_route(&mut self, group: &str) {
+        self.default_group = Some(group.to_string());
+    }
+
+    pub fn resolve(&self, request: &RouteRequest<'_>) -> Option<&str> {
+        self.routes
+            .iter()
+            .find(|route| route.matches(request))
+            .map(|route| route.group.as_str())
+            .or(self.default_group.as_deref())
+    }
+
+    pub fn validate(
+        &self,
+        prefix: &str,
+        known_group: impl Fn(&str) -> bool,
+    ) -> Result<(), ConfigError> {
+        for (idx, route) in self.routes.iter().enumerate() {
+            if route.grpc_method.is_some() && route.grpc_service.is_none() {
+                return Err(ConfigError::invalid(
+                    format!("{}.routes[{}].grpc_method", prefix, idx),
+                    "grpc_method needs a grpc_service",
+                ));
+            }
+            if let Some(service) = &route.grpc_service
+                && (service.is_empty() || service.contains('/'))
+            {
+                return Err(ConfigError::invalid(
+                    format!("{}.routes[{}].grpc_service", prefix, idx),
+                    format!("'{}' is not a valid service name", service),
+                ));
+            }
+            if !known_group(&route.group) {
+                return Err(ConfigError::invalid(
+                    format!("{}.routes[{}].group", prefix, idx),
+                    format!("unknown backend group '{}'", route.group),
+                ));
+            }
+        }
+        if let Some(group) = &self.default_group
+            && !known_group(group)
+        {
+            return Err(ConfigError::invalid(
+                format!("{}.default_group", prefix),
+                format!("unknown backend group '{}'", group),
+            ));
+        }
+        if self.routes.is_empty() && self.default_group.is_none() {
+            return Err(ConfigError::invalid(
+                prefix,
+                "at least one route or a default_group is required",
+            ));
+        }
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn map_route_range(
-        &mut self,
-        incoming: &str,
-        backend_host_only: &str,
-        port_start: usize,
-        port_end: usize,
-    ) {
-        for port in port_start..=port_end {
-            self.map_route(format!("{}:{}", incoming, port).as_str(), backend_host_only);
+    fn http_request<'a>(host: &'a str, path: &'a str, method: &'a str) -> RouteRequest<'a> {
+        RouteRequest {
+            listener: "127.0.0.1:8080",
+            host: Some(host),
+            path: Some(path),
+            method: Some(method),
         }
     }
+
+    fn router() -> RouterMap {
+        let mut router = RouterMap::new();
+        router.map_route(Route {
+            host: Some("api.example.com".to_string()),
+            path_prefix: Some("/v1/".to_string()),
+            methods: vec!["GET".to_string(), "POST".to_string()],
+            group: "api".to_string(),
+            ..Route::default()
+        });
+        router.map_route(Route {
+            path_regex: Some(Regex::new(r"^/static/.*\.css$").unwrap()),
+            group: "static".to_string(),
+            ..Route::default()
+        });
+        router.map_route(Route {
+            listener: Some("127.0.0.1:9000".to_string()),
+            group: "tcp".to_string(),
+            ..Route::default()
+        });
+        router.default_route("web");
+        router
+    }
+
+    #[test]
+    fn host_matches_test() {
+        assert!(host_matches("api.example.com", "API.example.com:8080"));
+        assert!(host_matches("*.example.com", "a.b.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+    }
+
+    #[test]
+    fn resolve_by_host_path_and_method_test() {
+        let router = router();
+        let request = http_request("api.example.com", "/v1/users", "post");
+        assert_eq!(router.resolve(&request), Some("api"));
+
+        let request = http_request("api.example.com", "/v1/users", "DELETE");
+        assert_eq!(router.resolve(&request), Some("web"));
+
+        let request = http_request("api.example.com", "/v2/users", "GET");
+        assert_eq!(router.resolve(&request), Some("web"));
+    }
+
+    #[test]
+    fn resolve_by_regex_test() {
+        let router = router();
+        let request = http_request("example.com", "/static/site.css", "GET");
+        assert_eq!(router.resolve(&request), Some("static"));
+        let request = http_request("example.com", "/static/site.js", "GET");
+        assert_eq!(router.resolve(&request), Some("web"));
+    }
+
+    #[test]
+    fn resolve_tcp_listener_test() {
+        let router = router();
+        let request = RouteRequest {
+            listener: "127.0.0.1:9000",
+            ..RouteRequest::default()
+        };
+        assert_eq!(router.resolve(&request), Some("tcp"));
+    }
+
+    #[test]
+    fn resolve_without_default_test() {
+        let mut router = RouterMap::new();
+        router.map_route(Route {
+            path_prefix: Some("/api".to_string()),
+            group: "api".to_string(),
+            ..Route::default()
+        });
+        let request = http_request("example.com", "/", "GET");
+        assert_eq!(router.resolve(&request), None);
+    }
+
+    #[test]
+    fn resolve_grpc_call_test() {
+        let mut router = RouterMap::new();
+        router.map_route(Route {
+            grpc_service: Some("shop.v1.Orders".to_string()),
+            grpc_method: Some("Create".to_string()),
+            group: "writes".to_string(),
+            ..Route::default()
+        });
+        router.map_route(Route {
+            grpc_service: Some("shop.v1.Orders".to_string()),
+            group: "orders".to_string(),
+            ..Route::default()
+        });
+        router.default_route("web");
+
+        let request = http_request("example.com", "/shop.v1.Orders/Create", "POST");
+        assert_eq!(router.resolve(&request), Some("writes"));
+        let request = http_request("example.com", "/shop.v1.Orders/Get", "POST");
+        assert_eq!(router.resolve(&request), Some("orders"));
+        let request = http_request("example.com", "/shop.v1.Orders/Get/extra", "POST");
+        assert_eq!(router.resolve(&request), Some("web"));
+
+        router.map_route(Route {
+            grpc_method: Some("Get".to_string()),
+            group: "web".to_string(),
+            ..Route::default()
+        });
+        assert_eq!(
+            router.validate("router_map", |_| true),
+            Err(ConfigError::invalid(
+                "router_map.routes[2].grpc_method",
+                "grpc_method needs a grpc_service"
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_unknown_group_test() {
+        let router = router();
+        assert!(router.validate("router_map", |_| true).is_ok());
+        let error = router
+            .validate("router_map", |group| group != "static")
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ConfigError::invalid(
+                "router_map.routes[1].group",
+                "unknown backend group 'static'"
+            )
+        );
+    }
+
+    #[test]
+    fn deserialize_test() {
+        let router: RouterMap = toml::from_str(
+            r#"
+default_group = "web"
+
+[[routes]]
+path_regex = "^/img/"
+methods = ["GET"]
+group = "images"
+"#,
+        )
+        .unwrap();
+        let request = http_request("example.com", "/img/a.png", "GET");
+        assert_eq!(router.resolve(&request), Some("images"));
+
+        let invalid = toml::from_str::<RouterMap>("[[routes]]\npath_regex = \"(\"\ngroup = \"x\"");
+        assert!(invalid.is_err());
+    }
 }
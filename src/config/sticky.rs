@@ -0,0 +1,76 @@
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StickyConfig {
+    #[serde(default = "default_ttl_sec")]
+    pub ttl_sec: u64,
+    #[serde(default = "default_idle_timeout_sec")]
+    pub idle_timeout_sec: u64,
+    #[serde(default = "default_sweep_interval_sec")]
+    pub sweep_interval_sec: u64,
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_ttl_sec() -> u64 {
+    3600
+}
+
+fn default_idle_timeout_sec() -> u64 {
+    300
+}
+
+fn default_sweep_interval_sec() -> u64 {
+    30
+}
+
+fn default_max_entries() -> usize {
+    100_000
+}
+
+impl StickyConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        for (field, value) in [
+            ("ttl_sec", self.ttl_sec),
+            ("idle_timeout_sec", self.idle_timeout_sec),
+            ("sweep_interval_sec", self.sweep_interval_sec),
+            ("max_entries", self.max_entries as u64),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::invalid(
+                    format!("{}.{}", prefix, field),
+                    "must be greater than 0",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_reports_each_field_test() {
+        let config: StickyConfig = toml::from_str("").unwrap();
+        assert!(config.validate("groups.web.sticky").is_ok());
+        for field in [
+            "ttl_sec",
+            "idle_timeout_sec",
+            "sweep_interval_sec",
+            "max_entries",
+        ] {
+            let config: StickyConfig = toml::from_str(&format!("{} = 0", field)).unwrap();
+            assert_eq!(
+                config.validate("groups.web.sticky"),
+                Err(ConfigError::invalid(
+                    format!("groups.web.sticky.{}", field),
+                    "must be greater than 0"
+                ))
+            );
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use rustls::crypto::ring::ALL_CIPHER_SUITES;
+use rustls::pki_types::ServerName;
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+// A certificate chain and its private key, both PEM files. Without `server_names` it is
+// the fallback for clients that send no SNI or one that matches no other certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CertificateConfig {
+    pub cert: String,
+    pub key: String,
+    #[serde(default)]
+    pub server_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TlsConfig {
+    pub certificates: Vec<CertificateConfig>,
+    #[serde(default = "default_versions")]
+    pub versions: Vec<TlsVersion>,
+    // rustls names such as `TLS13_AES_256_GCM_SHA384`; all suites are allowed when unset.
+    pub cipher_suites: Option<Vec<String>>,
+    // Defaults to `h2` and `http/1.1` on HTTP listeners and to no ALPN on TCP listeners.
+    pub alpn: Option<Vec<String>>,
+    #[serde(default = "default_handshake_timeout_ms")]
+    pub handshake_timeout_ms: u64,
+}
+
+// TLS towards the backends of a group. Without a `ca_bundle` the system trust store is used;
+// `client_cert` and `client_key` together enable mutual TLS.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BackendTlsConfig {
+    pub ca_bundle: Option<String>,
+    // Sent as SNI and verified against the certificate instead of the backend's host.
+    pub server_name: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    #[serde(default = "default_versions")]
+    pub versions: Vec<TlsVersion>,
+}
+
+fn default_versions() -> Vec<TlsVersion> {
+    vec![TlsVersion::Tls12, TlsVersion::Tls13]
+}
+
+fn default_handshake_timeout_ms() -> u64 {
+    10000
+}
+
+impl TlsConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.certificates.is_empty() {
+            return Err(ConfigError::invalid(
+                format!("{}.certificates", prefix),
+                "at least one certificate is required",
+            ));
+        }
+        for (idx, certificate) in self.certificates.iter().enumerate() {
+            if let Some(name) = certificate.server_names.iter().find(|name| {
+                let name = name.strip_prefix("*.").unwrap_or(name);
+                name.is_empty() || name.contains('*')
+            }) {
+                return Err(ConfigError::invalid(
+                    format!("{}.certificates[{}].server_names", prefix, idx),
+                    format!("'{}' is not a valid server name", name),
+                ));
+            }
+        }
+        if self.versions.is_empty() {
+            return Err(ConfigError::invalid(
+                format!("{}.versions", prefix),
+                "at least one version is required",
+            ));
+        }
+        if let Some(cipher_suites) = &self.cipher_suites {
+            if let Some(unknown) = cipher_suites.iter().find(|name| !is_known_suite(name)) {
+                return Err(ConfigError::invalid(
+                    format!("{}.cipher_suites", prefix),
+                    format!("unknown cipher suite '{}'", unknown),
+                ));
+            }
+            let usable = ALL_CIPHER_SUITES.iter().any(|suite| {
+                cipher_suites
+                    .iter()
+                    .any(|name| suite_name(suite) == Some(name))
+                    && self.versions.iter().any(|version| match version {
+                        TlsVersion::Tls12 => suite.tls13().is_none(),
+                        TlsVersion::Tls13 => suite.tls13().is_some(),
+                    })
+            });
+            if !usable {
+                return Err(ConfigError::invalid(
+                    format!("{}.cipher_suites", prefix),
+                    "no cipher suite matches the enabled versions",
+                ));
+            }
+        }
+        if self
+            .alpn
+            .as_ref()
+            .is_some_and(|alpn| alpn.iter().any(String::is_empty))
+        {
+            return Err(ConfigError::invalid(
+                format!("{}.alpn", prefix),
+                "protocol names must not be empty",
+            ));
+        }
+        if self.handshake_timeout_ms == 0 {
+            return Err(ConfigError::invalid(
+                format!("{}.handshake_timeout_ms", prefix),
+                "must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl BackendTlsConfig {
+    pub fn validate(&self, prefix: &str) -> Result<(), ConfigError> {
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            return Err(ConfigError::invalid(
+                format!("{}.client_cert", prefix),
+                "client_cert and client_key must be set together",
+            ));
+        }
+        if let Some(server_name) = &self.server_name
+            && ServerName::try_from(server_name.as_str()).is_err()
+        {
+            return Err(ConfigError::invalid(
+                format!("{}.server_name", prefix),
+                format!("'{}' is not a valid server name", server_name),
+            ));
+        }
+        if self.versions.is_empty() {
+            return Err(ConfigError::invalid(
+                format!("{}.versions", prefix),
+                "at least one version is required",
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub fn suite_name(suite: &rustls::SupportedCipherSuite) -> Option<&'static str> {
+    suite.suite().as_str()
+}
+
+fn is_known_suite(name: &str) -> bool {
+    ALL_CIPHER_SUITES
+        .iter()
+        .any(|suite| suite_name(suite) == Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> TlsConfig {
+        toml::from_str(contents).unwrap()
+    }
+
+    const CERTIFICATES: &str = "certificates = [{ cert = \"a.pem\", key = \"a.key\" }]\n";
+
+    #[test]
+    fn defaults_test() {
+        let config = parse(CERTIFICATES);
+        assert_eq!(config.versions, vec![TlsVersion::Tls12, TlsVersion::Tls13]);
+        assert_eq!(config.cipher_suites, None);
+        assert_eq!(config.alpn, None);
+        assert_eq!(config.handshake_timeout_ms, 10000);
+        assert!(config.certificates[0].server_names.is_empty());
+        assert!(config.validate("tls").is_ok());
+
+        let config = parse(&format!(
+            "{}versions = [\"1.3\"]\ncipher_suites = [\"TLS13_AES_256_GCM_SHA384\"]",
+            CERTIFICATES
+        ));
+        assert_eq!(config.versions, vec![TlsVersion::Tls13]);
+        assert!(config.validate("tls").is_ok());
+    }
+
+    #[test]
+    fn validate_test() {
+        assert_eq!(
+            parse("certificates = []").validate("listeners[0].tls"),
+            Err(ConfigError::invalid(
+                "listeners[0].tls.certificates",
+                "at least one certificate is required"
+            ))
+        );
+        assert_eq!(
+            parse(&format!("{}cipher_suites = [\"RC4\"]", CERTIFICATES)).validate("tls"),
+            Err(ConfigError::invalid(
+                "tls.cipher_suites",
+                "unknown cipher suite 'RC4'"
+            ))
+        );
+        assert_eq!(
+            parse(&format!(
+                "{}versions = [\"1.2\"]\ncipher_suites = [\"TLS13_AES_128_GCM_SHA256\"]",
+                CERTIFICATES
+            ))
+            .validate("tls"),
+            Err(ConfigError::invalid(
+                "tls.cipher_suites",
+                "no cipher suite matches the enabled versions"
+            ))
+        );
+        assert_eq!(
+            parse(
+                "certificates = [{ cert = \"a.pem\", key = \"a.key\", server_names = [\"a.*.com\"] }]"
+            )
+            .validate("tls"),
+            Err(ConfigError::invalid(
+                "tls.certificates[0].server_names",
+                "'a.*.com' is not a valid server name"
+            ))
+        );
+        assert!(
+            parse(&format!("{}versions = []", CERTIFICATES))
+                .validate("tls")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn backend_tls_test() {
+        let parse = |contents: &str| toml::from_str::<BackendTlsConfig>(contents).unwrap();
+        let config = parse("");
+        assert_eq!(config.ca_bundle, None);
+        assert_eq!(config.versions, vec![TlsVersion::Tls12, TlsVersion::Tls13]);
+        assert!(config.validate("groups.web.tls").is_ok());
+
+        assert_eq!(
+            parse("client_cert = \"lb.pem\"").validate("groups.web.tls"),
+            Err(ConfigError::invalid(
+                "groups.web.tls.client_cert",
+                "client_cert and client_key must be set together"
+            ))
+        );
+        assert_eq!(
+            parse("server_name = \"not a name\"").validate("tls"),
+            Err(ConfigError::invalid(
+                "tls.server_name",
+                "'not a name' is not a valid server name"
+            ))
+        );
+        assert!(
+            parse("ca_bundle = \"ca.pem\"\nserver_name = \"api.internal\"\nclient_cert = \"lb.pem\"\nclient_key = \"lb.key\"")
+                .validate("tls")
+                .is_ok()
+        );
+    }
+}
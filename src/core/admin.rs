@@ -0,0 +1,705 @@
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::Incoming;
+use hyper::header::{self, HeaderValue};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::Arc;
+use tokio::io;
+use tokio::net::TcpListener;
+
+use crate::config::app::AppConfig;
+use crate::config::error::ConfigError;
+use crate::config::pool::PoolConfig;
+use crate::core::http_proxy::{ProxyBody, error_response};
+use crate::core::reload::ConfigReloader;
+use crate::core::router::BackendGroup;
+use crate::core::shutdown::Shutdown;
+use crate::domain::backend_conn::ConnString;
+use crate::domain::backend_status::AdminState;
+use crate::domain::request::Status;
+use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+use crate::infrastructure::metrics::MetricsRegistry;
+
+pub struct AdminApi {
+    pub reloader: Arc<ConfigReloader>,
+    pub metrics: Arc<MetricsRegistry>,
+}
+
+const DEFAULT_REQUEST_LIMIT: usize = 100;
+// Request bodies are small JSON documents; anything bigger is not read.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AdminError {
+    NotFound(String),
+    BadRequest(String),
+    PayloadTooLarge(String),
+}
+
+#[derive(Serialize)]
+struct GroupView {
+    name: String,
+    strategy: &'static str,
+    backends: Vec<BackendView>,
+}
+
+#[derive(Serialize)]
+struct BackendView {
+    address: String,
+    uuid: String,
+    weight: u32,
+    state: AdminState,
+    healthy: bool,
+    ejected: bool,
+    active: usize,
+}
+
+#[derive(Deserialize)]
+struct WeightUpdate {
+    weight: u32,
+}
+
+#[derive(Deserialize)]
+struct StateUpdate {
+    state: AdminState,
+}
+
+impl AdminError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AdminError::NotFound(_) => StatusCode::NOT_FOUND,
+            AdminError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AdminError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::NotFound(message)
+            | AdminError::BadRequest(message)
+            | AdminError::PayloadTooLarge(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl From<ConfigError> for AdminError {
+    fn from(error: ConfigError) -> AdminError {
+        AdminError::BadRequest(error.to_string())
+    }
+}
+
+pub async fn serve_admin(
+    listener: TcpListener,
+    admin: Arc<AdminApi>,
+    shutdown: Shutdown,
+) -> io::Result<()> {
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.triggered() => return Ok(()),
+        };
+        let admin = Arc::clone(&admin);
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let admin = Arc::clone(&admin);
+                async move { admin.handle(request).await }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                error!("Error serving admin connection from {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+impl AdminApi {
+    async fn handle(&self, request: Request<Incoming>) -> Result<Response<ProxyBody>, Infallible> {
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        let result = match (&method, segments.as_slice()) {
+            (&Method::POST, ["reload"]) => {
+                info!("Reload requested through the admin API");
+                self.reloader
+                    .reload()
+                    .await
+                    .map(|summary| json_response(StatusCode::OK, &summary))
+                    .map_err(AdminError::from)
+            }
+            (&Method::GET, ["metrics"]) => Ok(self.render_metrics().await),
+            (&Method::GET, ["requests"]) => self.recent_requests(request.uri().query()),
+            (&Method::GET, ["backends"]) => Ok(self.list_backends().await),
+            (&Method::POST, ["groups", group, "backends"]) => {
+                self.add_backend(group, request).await
+            }
+            (&Method::DELETE, ["groups", group, "backends", address]) => {
+                self.remove_backend(group, address).await
+            }
+            (&Method::PUT, ["groups", group, "backends", address, "weight"]) => {
+                self.set_weight(group, address, request).await
+            }
+            (&Method::PUT, ["groups", group, "backends", address, "state"]) => {
+                self.set_state(group, address, request).await
+            }
+            (&Method::GET, ["groups", group, "sticky"]) => self.sticky_assignments(group).await,
+            _ => Err(AdminError::NotFound("not found".to_string())),
+        };
+        Ok(result.unwrap_or_else(|e| {
+            warn!("Admin request {} {} failed: {}", method, path, e);
+            error_response(e.status(), &e.to_string())
+        }))
+    }
+
+    async fn render_metrics(&self) -> Response<ProxyBody> {
+        let router = self.reloader.router().await;
+        let groups = router.groups();
+        let pools: Vec<(&str, &ConnectionPool)> = groups
+            .iter()
+            .map(|group| (group.name.as_str(), &group.pool))
+            .collect();
+        let mut response = Response::new(
+            Full::new(Bytes::from(self.metrics.render(&pools)))
+                .map_err(|never| match never {})
+                .boxed(),
+        );
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4"),
+        );
+        response
+    }
+
+    async fn list_backends(&self) -> Response<ProxyBody> {
+        let router = self.reloader.router().await;
+        let groups: Vec<GroupView> = router
+            .groups()
+            .into_iter()
+            .map(|group| GroupView {
+                name: group.name.clone(),
+                strategy: group.pool.strategy.name(),
+                backends: (0..group.pool.backends.len())
+                    .map(|backend_idx| backend_view(group, backend_idx))
+                    .collect(),
+            })
+            .collect();
+        json_response(StatusCode::OK, &groups)
+    }
+
+    async fn add_backend(
+        &self,
+        group: &str,
+        request: Request<Incoming>,
+    ) -> Result<Response<ProxyBody>, AdminError> {
+        self.find_group(group).await?;
+        let backend: ConnString = read_json(request).await?;
+        let address = backend.address();
+        info!("Adding backend {} to group {}", address, group);
+        let summary = self
+            .reloader
+            .update(|app_config| {
+                let backends = &mut group_config(app_config, group)?.backends;
+                if backends
+                    .iter()
+                    .any(|existing| existing.address() == address)
+                {
+                    return Err(ConfigError::invalid(
+                        format!("groups.{}.backends", group),
+                        format!("backend {} already exists", address),
+                    ));
+                }
+                backends.push(backend);
+                Ok(())
+            })
+            .await?;
+        Ok(json_response(StatusCode::CREATED, &summary))
+    }
+
+    async fn remove_backend(
+        &self,
+        group: &str,
+        address: &str,
+    ) -> Result<Response<ProxyBody>, AdminError> {
+        self.find_backend(group, address).await?;
+        info!("Removing backend {} from group {}", address, group);
+        let summary = self
+            .reloader
+            .update(|app_config| {
+                group_config(app_config, group)?
+                    .backends
+                    .retain(|backend| backend.address() != address);
+                Ok(())
+            })
+            .await?;
+        Ok(json_response(StatusCode::OK, &summary))
+    }
+
+    async fn set_weight(
+        &self,
+        group: &str,
+        address: &str,
+        request: Request<Incoming>,
+    ) -> Result<Response<ProxyBody>, AdminError> {
+        self.find_backend(group, address).await?;
+        let update: WeightUpdate = read_json(request).await?;
+        info!(
+            "Setting weight of backend {} in group {} to {}",
+            address, group, update.weight
+        );
+        let summary = self
+            .reloader
+            .update(|app_config| {
+                for backend in group_config(app_config, group)?.backends.iter_mut() {
+                    if backend.address() == address {
+                        *backend = backend.clone().with_weight(update.weight);
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(json_response(StatusCode::OK, &summary))
+    }
+
+    async fn set_state(
+        &self,
+        group: &str,
+        address: &str,
+        request: Request<Incoming>,
+    ) -> Result<Response<ProxyBody>, AdminError> {
+        let (group, backend_idx) = self.find_backend(group, address).await?;
+        let update: StateUpdate = read_json(request).await?;
+        info!(
+            "Setting backend {} in group {} to {:?}",
+            address, group.name, update.state
+        );
+        group.pool.status[backend_idx].set_admin_state(update.state);
+        if update.state == AdminState::Maintenance {
+            group.pool.clear_idle(backend_idx);
+        }
+        Ok(json_response(
+            StatusCode::OK,
+            &backend_view(&group, backend_idx),
+        ))
+    }
+
+    async fn sticky_assignments(&self, group: &str) -> Result<Response<ProxyBody>, AdminError> {
+        let group = self.find_group(group).await?;
+        let sticky = group.sticky.as_ref().ok_or_else(|| {
+            AdminError::NotFound(format!(
+                "sticky sessions are not enabled for group {}",
+                group.name
+            ))
+        })?;
+        Ok(json_response(StatusCode::OK, &sticky.assignments()))
+    }
+
+    fn recent_requests(&self, query: Option<&str>) -> Result<Response<ProxyBody>, AdminError> {
+        let mut limit = DEFAULT_REQUEST_LIMIT;
+        let mut status = None;
+        for (key, value) in query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+        {
+            match key {
+                "limit" => {
+                    limit = value
+                        .parse()
+                        .map_err(|_| AdminError::BadRequest(format!("invalid limit {}", value)))?
+                }
+                "status" => status = Some(parse_status(value)?),
+                _ => {}
+            }
+        }
+        let requests = self.metrics.requests().recent(limit, status.as_ref());
+        Ok(json_response(StatusCode::OK, &requests))
+    }
+
+    async fn find_group(&self, name: &str) -> Result<Arc<BackendGroup>, AdminError> {
+        self.reloader
+            .router()
+            .await
+            .group(name)
+            .cloned()
+            .ok_or_else(|| AdminError::NotFound(format!("unknown group {}", name)))
+    }
+
+    async fn find_backend(
+        &self,
+        group: &str,
+        address: &str,
+    ) -> Result<(Arc<BackendGroup>, usize), AdminError> {
+        let group = self.find_group(group).await?;
+        let backend_idx = group.pool.position(address).ok_or_else(|| {
+            AdminError::NotFound(format!(
+                "unknown backend {} in group {}",
+                address, group.name
+            ))
+        })?;
+        Ok((group, backend_idx))
+    }
+}
+
+fn backend_view(group: &BackendGroup, backend_idx: usize) -> BackendView {
+    let backend = &group.pool.backends[backend_idx];
+    let status = &group.pool.status[backend_idx];
+    BackendView {
+        address: backend.address(),
+        uuid: backend.get_uuid().to_string(),
+        weight: backend.get_weight(),
+        state: status.admin_state(),
+        healthy: status.is_healthy(),
+        ejected: status.is_ejected(),
+        active: status.active(),
+    }
+}
+
+fn parse_status(value: &str) -> Result<Status, AdminError> {
+    match value {
+        "created" => Ok(Status::Created),
+        "processing" => Ok(Status::Processing),
+        "completed" => Ok(Status::Completed),
+        "failed" => Ok(Status::Failed),
+        _ => Err(AdminError::BadRequest(format!("unknown status {}", value))),
+    }
+}
+
+fn group_config<'a>(
+    app_config: &'a mut AppConfig,
+    group: &str,
+) -> Result<&'a mut PoolConfig, ConfigError> {
+    app_config
+        .groups
+        .get_mut(group)
+        .ok_or_else(|| ConfigError::invalid("groups", format!("unknown group {}", group)))
+}
+
+async fn read_json<T: DeserializeOwned>(request: Request<Incoming>) -> Result<T, AdminError> {
+    let body = Limited::new(request.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map_err(|e| match e.downcast_ref::<LengthLimitError>() {
+            Some(_) => {
+                AdminError::PayloadTooLarge(format!("request body exceeds {} bytes", MAX_BODY_SIZE))
+            }
+            None => AdminError::BadRequest(e.to_string()),
+        })?
+        .to_bytes();
+    serde_json::from_slice(&body)
+        .map_err(|e| AdminError::BadRequest(format!("invalid request body: {}", e)))
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<ProxyBody> {
+    let body = match serde_json::to_vec(value) {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+    let mut response = Response::new(
+        Full::new(Bytes::from(body))
+            .map_err(|never| match never {})
+            .boxed(),
+    );
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::app::ConfigFormat;
+    use crate::core::router::Router;
+    use crate::domain::request::Request as RequestRecord;
+    use crate::infrastructure::request_log::RequestLog;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    const CONFIG: &str = r#"
+listen_addr = "127.0.0.1:8080"
+
+[router_map]
+default_group = "web"
+
+[groups.web]
+backends = ["127.0.0.1:3000", "127.0.0.1:3001"]
+
+[groups.web.hashing]
+key = "client_ip"
+
+[groups.web.sticky]
+
+[groups.api]
+backends = ["127.0.0.1:4000"]
+"#;
+
+    async fn start_admin(config_path: &str) -> (std::net::SocketAddr, Arc<ConfigReloader>) {
+        let metrics = Arc::new(MetricsRegistry::new(Arc::new(RequestLog::new(10))));
+        start_admin_with(config_path, metrics).await
+    }
+
+    async fn start_admin_with(
+        config_path: &str,
+        metrics: Arc<MetricsRegistry>,
+    ) -> (std::net::SocketAddr, Arc<ConfigReloader>) {
+        let app_config = AppConfig::parse(CONFIG, ConfigFormat::Toml).unwrap();
+        let router = Arc::new(Router::from_config(&app_config).unwrap());
+        let reloader = Arc::new(ConfigReloader::new(
+            config_path,
+            app_config,
+            router,
+            Vec::new(),
+        ));
+        let admin = Arc::new(AdminApi {
+            reloader: Arc::clone(&reloader),
+            metrics,
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_admin(listener, admin, Shutdown::new()));
+        (addr, reloader)
+    }
+
+    async fn request(addr: std::net::SocketAddr, method: &str, path: &str, body: &str) -> String {
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let head = format!(
+            "{} {} HTTP/1.1\r\nHost: admin\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        client.write_all(head.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    async fn web_backends(reloader: &ConfigReloader) -> Vec<ConnString> {
+        let router = reloader.router().await;
+        router.group("web").unwrap().pool.backends.to_vec()
+    }
+
+    #[tokio::test]
+    async fn list_backends_test() {
+        let (addr, _) = start_admin("unused.toml").await;
+        let response = request(addr, "GET", "/backends", "").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        let groups: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(groups[0]["name"], "api");
+        assert_eq!(groups[1]["name"], "web");
+        assert_eq!(groups[1]["backends"][1]["address"], "127.0.0.1:3001");
+        assert_eq!(groups[1]["backends"][1]["state"], "active");
+        assert_eq!(groups[1]["backends"][1]["healthy"], true);
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint_test() {
+        let (addr, reloader) = start_admin("unused.toml").await;
+        let router = reloader.router().await;
+        let web = router.group("web").unwrap();
+        web.pool.metrics[0]
+            .connect_failures
+            .fetch_add(2, std::sync::atomic::Ordering::Relaxed);
+
+        let response = request(addr, "GET", "/metrics", "").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("content-type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains(
+            "lb_backend_connect_failures_total{group=\"web\",backend=\"127.0.0.1:3000\"} 2\n"
+        ));
+        assert!(
+            response.contains("lb_backend_healthy{group=\"api\",backend=\"127.0.0.1:4000\"} 1\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn recent_requests_test() {
+        let metrics = Arc::new(MetricsRegistry::new(Arc::new(RequestLog::new(10))));
+        let listener = metrics.listener("127.0.0.1:8080");
+        for (id, status) in [
+            (0, Status::Completed),
+            (1, Status::Failed),
+            (2, Status::Completed),
+        ] {
+            let mut record = RequestRecord::new(id, "web".to_string(), uuid::Uuid::new_v4());
+            record.set_backend("127.0.0.1:3000".to_string());
+            record.set_status(status);
+            listener.record(record);
+        }
+        let (addr, _) = start_admin_with("unused.toml", metrics).await;
+
+        let response = request(addr, "GET", "/requests?limit=2", "").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        let requests: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(requests.as_array().unwrap().len(), 2);
+        assert_eq!(requests[0]["id"], 2);
+        assert_eq!(requests[1]["status"], "failed");
+        assert_eq!(requests[1]["backend"], "127.0.0.1:3000");
+
+        let response = request(addr, "GET", "/requests?status=failed", "").await;
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        let requests: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(requests.as_array().unwrap().len(), 1);
+        assert_eq!(requests[0]["id"], 1);
+
+        let response = request(addr, "GET", "/requests?status=lost", "").await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[tokio::test]
+    async fn add_and_remove_backend_test() {
+        let (addr, reloader) = start_admin("unused.toml").await;
+        let response = request(
+            addr,
+            "POST",
+            "/groups/web/backends",
+            r#"{"address": "127.0.0.1:3002", "weight": 2}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert!(response.contains("\"backends_added\":[\"127.0.0.1:3002\"]"));
+        let backends = web_backends(&reloader).await;
+        assert_eq!(backends.len(), 3);
+        assert_eq!(backends[2].get_weight(), 2);
+
+        let response = request(addr, "POST", "/groups/web/backends", r#""127.0.0.1:3002""#).await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        let response = request(addr, "DELETE", "/groups/web/backends/127.0.0.1:3000", "").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let addresses: Vec<String> = web_backends(&reloader)
+            .await
+            .iter()
+            .map(ConnString::address)
+            .collect();
+        assert_eq!(addresses, vec!["127.0.0.1:3001", "127.0.0.1:3002"]);
+    }
+
+    #[tokio::test]
+    async fn set_weight_test() {
+        let (addr, reloader) = start_admin("unused.toml").await;
+        let uuid = web_backends(&reloader).await[0].get_uuid();
+
+        let response = request(
+            addr,
+            "PUT",
+            "/groups/web/backends/127.0.0.1:3000/weight",
+            r#"{"weight": 5}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let backends = web_backends(&reloader).await;
+        assert_eq!(backends[0].get_weight(), 5);
+        assert_eq!(backends[0].get_uuid(), uuid);
+
+        let response = request(
+            addr,
+            "PUT",
+            "/groups/web/backends/127.0.0.1:3000/weight",
+            r#"{"weight": 0}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[tokio::test]
+    async fn set_state_test() {
+        let (addr, reloader) = start_admin("unused.toml").await;
+        let response = request(
+            addr,
+            "PUT",
+            "/groups/web/backends/127.0.0.1:3001/state",
+            r#"{"state": "draining"}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\"state\":\"draining\""));
+
+        let router = reloader.router().await;
+        let pool = &router.group("web").unwrap().pool;
+        assert!(!pool.is_available(1));
+        assert!(pool.accepts_pinned(1));
+
+        let response = request(
+            addr,
+            "PUT",
+            "/groups/web/backends/127.0.0.1:3001/state",
+            r#"{"state": "asleep"}"#,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[tokio::test]
+    async fn sticky_assignments_test() {
+        let (addr, reloader) = start_admin("unused.toml").await;
+        let router = reloader.router().await;
+        let sticky = router.group("web").unwrap().sticky.clone().unwrap();
+        sticky.pin(
+            crate::infrastructure::smart_tcp_pool::SmartTcpConnPool::user_id(1),
+            1,
+        );
+
+        let response = request(addr, "GET", "/groups/web/sticky", "").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\"backend\":\"127.0.0.1:3001\""));
+
+        let response = request(addr, "GET", "/groups/api/sticky", "").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[tokio::test]
+    async fn unknown_group_and_backend_test() {
+        let (addr, _) = start_admin("unused.toml").await;
+        let response = request(addr, "DELETE", "/groups/db/backends/127.0.0.1:3000", "").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = request(addr, "DELETE", "/groups/web/backends/127.0.0.1:9999", "").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = request(addr, "GET", "/nothing", "").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_body_test() {
+        let (addr, reloader) = start_admin("unused.toml").await;
+        let body = format!("\"{}\"", "a".repeat(MAX_BODY_SIZE));
+        let response = request(addr, "POST", "/groups/web/backends", &body).await;
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+        assert_eq!(web_backends(&reloader).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reload_endpoint_test() {
+        let path = std::env::temp_dir().join(format!("admin-reload-{}.toml", std::process::id()));
+        std::fs::write(&path, CONFIG.replace("127.0.0.1:3001", "127.0.0.1:3005")).unwrap();
+        let (addr, _) = start_admin(&path.to_string_lossy()).await;
+
+        let response = request(addr, "POST", "/reload", "").await;
+        std::fs::remove_file(&path).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\"backends_added\":[\"127.0.0.1:3005\"]"));
+
+        let (addr, _) = start_admin("/nonexistent/lb.toml").await;
+        let response = request(addr, "POST", "/reload", "").await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
@@ -0,0 +1,412 @@
+use socket2::SockRef;
+use std::fmt;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::{Duration, timeout};
+
+use crate::config::limits::OverflowAction;
+use crate::config::retry::RetryConfig;
+use crate::infrastructure::connection_limit::QueueError;
+use crate::infrastructure::fast_tcp_pool::{
+    BackendSlot, ConnectionPool, Http2Sender, PooledStream,
+};
+use crate::infrastructure::tls::ClientStream;
+
+const PEEK_TIMEOUT: Duration = Duration::from_millis(200);
+const HTTP_METHODS: [&str; 9] = [
+    "GET ", "HEAD ", "POST ", "PUT ", "DELETE ", "CONNECT ", "OPTIONS ", "TRACE ", "PATCH ",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailoverError {
+    NoBackendAvailable,
+    AllAttemptsFailed { attempts: usize },
+    BudgetExhausted { attempts: usize },
+    Overloaded(QueueError),
+    RateLimited,
+}
+
+impl FailoverError {
+    pub fn http_status(&self) -> (u16, &'static str) {
+        match self {
+            FailoverError::NoBackendAvailable | FailoverError::Overloaded(_) => {
+                (503, "Service Unavailable")
+            }
+            FailoverError::AllAttemptsFailed { .. } | FailoverError::BudgetExhausted { .. } => {
+                (502, "Bad Gateway")
+            }
+            FailoverError::RateLimited => (429, "Too Many Requests"),
+        }
+    }
+}
+
+impl fmt::Display for FailoverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailoverError::NoBackendAvailable => write!(f, "no healthy backend available"),
+            FailoverError::AllAttemptsFailed { attempts } => {
+                write!(f, "all {} backend connect attempts failed", attempts)
+            }
+            FailoverError::BudgetExhausted { attempts } => write!(
+                f,
+                "connect budget exhausted after {} backend attempts",
+                attempts
+            ),
+            FailoverError::Overloaded(e) => write!(f, "{}", e),
+            FailoverError::RateLimited => write!(f, "client rate limit exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for FailoverError {}
+
+pub async fn connect_with_failover(
+    pool: &ConnectionPool,
+    session_id: u64,
+    preferred: Option<usize>,
+    retry: &RetryConfig,
+) -> Result<(usize, PooledStream, BackendSlot), FailoverError> {
+    failover(
+        pool,
+        session_id,
+        preferred,
+        retry,
+        |backend_idx, connect_timeout| pool.connect(backend_idx, Some(connect_timeout)),
+    )
+    .await
+}
+
+// HTTP/2 groups hand out a sender on the backend's shared connection instead of a stream.
+pub async fn multiplex_with_failover(
+    pool: &ConnectionPool,
+    session_id: u64,
+    preferred: Option<usize>,
+    retry: &RetryConfig,
+) -> Result<(usize, Http2Sender, BackendSlot), FailoverError> {
+    failover(
+        pool,
+        session_id,
+        preferred,
+        retry,
+        |backend_idx, connect_timeout| pool.connect_multiplexed(backend_idx, connect_timeout),
+    )
+    .await
+}
+
+async fn failover<T, F, Fut>(
+    pool: &ConnectionPool,
+    session_id: u64,
+    preferred: Option<usize>,
+    retry: &RetryConfig,
+    connect: F,
+) -> Result<(usize, T, BackendSlot), FailoverError>
+where
+    F: Fn(usize, Duration) -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    let started = Instant::now();
+    let budget = Duration::from_millis(retry.budget_ms);
+    let connect_timeout = Duration::from_millis(retry.connect_timeout_ms);
+    let mut tried: Vec<usize> = Vec::with_capacity(retry.attempts);
+
+    while tried.len() < retry.attempts {
+        let remaining = budget.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return Err(FailoverError::BudgetExhausted {
+                attempts: tried.len(),
+            });
+        }
+        let preferred = preferred.filter(|_| tried.is_empty());
+        let (backend_idx, slot) = match pool.reserve_any(session_id, &tried, preferred).await {
+            Ok(Some(reserved)) => reserved,
+            Ok(None) => break,
+            Err(e) => return Err(FailoverError::Overloaded(e)),
+        };
+        tried.push(backend_idx);
+
+        if let Some(connection) = connect(backend_idx, connect_timeout.min(remaining)).await {
+            return Ok((backend_idx, connection, slot));
+        }
+    }
+
+    if tried.is_empty() {
+        Err(FailoverError::NoBackendAvailable)
+    } else {
+        Err(FailoverError::AllAttemptsFailed {
+            attempts: tried.len(),
+        })
+    }
+}
+
+// Answers HTTP clients with a proper error response; anything else is just closed.
+// Overload and rate limit rejections may instead reset the connection.
+pub async fn reject(
+    mut incoming_stream: TcpStream,
+    error: &FailoverError,
+    overflow: OverflowAction,
+) {
+    if resets(error, overflow) {
+        reset(incoming_stream);
+        return;
+    }
+    let mut buf = [0u8; 8];
+    let peeked = timeout(PEEK_TIMEOUT, incoming_stream.peek(&mut buf))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or(0);
+
+    if looks_like_http(&buf[..peeked]) {
+        let (code, reason) = error.http_status();
+        let body = format!("{}\n", error);
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            code,
+            reason,
+            body.len(),
+            body
+        );
+        let _ = incoming_stream.write_all(response.as_bytes()).await;
+    }
+    let _ = incoming_stream.shutdown().await;
+}
+
+// Once TLS is terminated the client's bytes can't be peeked at, so it only gets a close_notify.
+pub async fn reject_client(
+    incoming_stream: ClientStream,
+    error: &FailoverError,
+    overflow: OverflowAction,
+) {
+    match incoming_stream {
+        ClientStream::Plain(stream) => reject(stream, error, overflow).await,
+        ClientStream::Tls(stream) if resets(error, overflow) => reset(stream.into_inner().0),
+        ClientStream::Tls(mut stream) => {
+            let _ = stream.shutdown().await;
+        }
+    }
+}
+
+fn resets(error: &FailoverError, overflow: OverflowAction) -> bool {
+    matches!(
+        error,
+        FailoverError::Overloaded(_) | FailoverError::RateLimited
+    ) && overflow == OverflowAction::Reset
+}
+
+// A zero linger never blocks on close: the kernel drops the socket and sends a RST.
+fn reset(incoming_stream: TcpStream) {
+    let _ = SockRef::from(&incoming_stream).set_linger(Some(Duration::ZERO));
+}
+
+fn looks_like_http(prefix: &[u8]) -> bool {
+    !prefix.is_empty()
+        && HTTP_METHODS.iter().any(|method| {
+            let method = method.as_bytes();
+            let len = prefix.len().min(method.len());
+            prefix[..len] == method[..len]
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::limits::ConnectionLimitConfig;
+    use crate::domain::backend_conn::ConnString;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn retry(attempts: usize) -> RetryConfig {
+        RetryConfig {
+            attempts,
+            connect_timeout_ms: 200,
+            budget_ms: 1000,
+        }
+    }
+
+    async fn dead_addr() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[test]
+    fn looks_like_http_test() {
+        assert!(looks_like_http(b"GET / HT"));
+        assert!(looks_like_http(b"DEL"));
+        assert!(!looks_like_http(b"\x16\x03\x01\x02"));
+        assert!(!looks_like_http(b""));
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_backend_test() {
+        let dead = dead_addr().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let alive = listener.local_addr().unwrap();
+
+        let pool = ConnectionPool::new(
+            vec![
+                ConnString::new(dead.ip().to_string(), dead.port()),
+                ConnString::new(alive.ip().to_string(), alive.port()),
+            ],
+            10,
+        );
+
+        let (backend_idx, stream, _) = connect_with_failover(&pool, 1, None, &retry(3))
+            .await
+            .unwrap();
+        assert_eq!(backend_idx, 1);
+        assert_eq!(stream.stream.tcp().peer_addr().unwrap(), alive);
+    }
+
+    #[tokio::test]
+    async fn preferred_backend_tried_first_test() {
+        let listener1 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr1 = listener1.local_addr().unwrap();
+        let listener2 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr2 = listener2.local_addr().unwrap();
+
+        let pool = ConnectionPool::new(
+            vec![
+                ConnString::new(addr1.ip().to_string(), addr1.port()),
+                ConnString::new(addr2.ip().to_string(), addr2.port()),
+            ],
+            10,
+        );
+
+        for session_id in 0..3 {
+            let (backend_idx, ..) = connect_with_failover(&pool, session_id, Some(1), &retry(3))
+                .await
+                .unwrap();
+            assert_eq!(backend_idx, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn all_attempts_failed_test() {
+        let dead1 = dead_addr().await;
+        let dead2 = dead_addr().await;
+        let pool = ConnectionPool::new(
+            vec![
+                ConnString::new(dead1.ip().to_string(), dead1.port()),
+                ConnString::new(dead2.ip().to_string(), dead2.port()),
+            ],
+            10,
+        );
+
+        let result = connect_with_failover(&pool, 1, None, &retry(5)).await;
+        assert_eq!(
+            result.unwrap_err(),
+            FailoverError::AllAttemptsFailed { attempts: 2 }
+        );
+    }
+
+    #[tokio::test]
+    async fn no_backend_available_test() {
+        let pool = ConnectionPool::new(vec![ConnString::new("127.0.0.1".to_string(), 9999)], 10);
+        pool.status[0].record_probe(false, 1, 1);
+
+        let result = connect_with_failover(&pool, 1, None, &retry(3)).await;
+        assert_eq!(result.unwrap_err(), FailoverError::NoBackendAvailable);
+    }
+
+    #[tokio::test]
+    async fn reject_http_client_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let (incoming, _) = listener.accept().await.unwrap();
+
+        reject(
+            incoming,
+            &FailoverError::NoBackendAvailable,
+            OverflowAction::Reset,
+        )
+        .await;
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    }
+
+    #[tokio::test]
+    async fn reject_raw_tcp_client_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"\x00\x01binary").await.unwrap();
+        let (incoming, _) = listener.accept().await.unwrap();
+
+        reject(
+            incoming,
+            &FailoverError::AllAttemptsFailed { attempts: 1 },
+            OverflowAction::Reject,
+        )
+        .await;
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert!(response.is_empty());
+    }
+
+    #[tokio::test]
+    async fn saturated_backend_queues_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut pool = ConnectionPool::new(
+            vec![ConnString::new(addr.ip().to_string(), addr.port())],
+            10,
+        );
+        pool.limits(ConnectionLimitConfig {
+            max_connections: 1,
+            queue_size: 1,
+            queue_timeout_ms: 1000,
+            overflow: OverflowAction::Reject,
+        });
+
+        let (_, _, slot) = connect_with_failover(&pool, 1, None, &retry(3))
+            .await
+            .unwrap();
+        let queued = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                connect_with_failover(&pool, 2, None, &retry(3))
+                    .await
+                    .map(|_| ())
+            }
+        });
+        while pool.queue.waiting() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            connect_with_failover(&pool, 3, None, &retry(3))
+                .await
+                .unwrap_err(),
+            FailoverError::Overloaded(QueueError::Full)
+        );
+        drop(slot);
+        assert_eq!(queued.await.unwrap(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn reject_overloaded_with_reset_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let (incoming, _) = listener.accept().await.unwrap();
+
+        reject(
+            incoming,
+            &FailoverError::Overloaded(QueueError::Timeout),
+            OverflowAction::Reset,
+        )
+        .await;
+
+        let mut response = Vec::new();
+        let error = client.read_to_end(&mut response).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::ConnectionReset);
+    }
+}
@@ -0,0 +1,255 @@
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Response, StatusCode};
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::time::{Duration, Sleep, sleep};
+
+use crate::core::http_proxy::ProxyBody;
+
+pub const GRPC_TIMEOUT: HeaderName = HeaderName::from_static("grpc-timeout");
+const GRPC_STATUS: HeaderName = HeaderName::from_static("grpc-status");
+const GRPC_MESSAGE: HeaderName = HeaderName::from_static("grpc-message");
+
+// Status codes from https://grpc.github.io/grpc/core/md_doc_statuscodes.html.
+const INTERNAL: u16 = 13;
+const UNAUTHENTICATED: u16 = 16;
+const PERMISSION_DENIED: u16 = 7;
+const UNIMPLEMENTED: u16 = 12;
+const RESOURCE_EXHAUSTED: u16 = 8;
+const DEADLINE_EXCEEDED: u16 = 4;
+const UNAVAILABLE: u16 = 14;
+const UNKNOWN: u16 = 2;
+
+// Ends a response stream with DEADLINE_EXCEEDED in its trailers once the call's deadline
+// passes, however much of it the backend has sent by then.
+pub struct DeadlineBody {
+    inner: Option<ProxyBody>,
+    expiry: Pin<Box<Sleep>>,
+}
+
+// Largest value the 8 digits of a `grpc-timeout` can hold.
+const MAX_TIMEOUT_VALUE: u128 = 99_999_999;
+
+pub fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| {
+            content_type == "application/grpc" || content_type.starts_with("application/grpc+")
+        })
+}
+
+// "100m" is 100 milliseconds; malformed values are ignored like an absent header.
+pub fn timeout(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(GRPC_TIMEOUT)?.to_str().ok()?;
+    let digits = value.get(..value.len().checked_sub(1)?)?;
+    if digits.is_empty() || digits.len() > 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = digits.parse().ok()?;
+    match &value[digits.len()..] {
+        "H" => Some(Duration::from_secs(amount * 3600)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+pub fn timeout_value(remaining: Duration) -> HeaderValue {
+    let value = match remaining.as_millis() {
+        millis if millis <= MAX_TIMEOUT_VALUE => format!("{}m", millis),
+        _ => format!("{}S", remaining.as_secs().min(MAX_TIMEOUT_VALUE as u64)),
+    };
+    HeaderValue::from_str(&value).expect("digits and a unit are a valid header value")
+}
+
+// The usual HTTP to gRPC mapping, except that the proxy's own timeouts and rate limits
+// are reported as what they are.
+pub fn status(status: StatusCode) -> u16 {
+    match status {
+        StatusCode::BAD_REQUEST => INTERNAL,
+        StatusCode::UNAUTHORIZED => UNAUTHENTICATED,
+        StatusCode::FORBIDDEN => PERMISSION_DENIED,
+        StatusCode::NOT_FOUND => UNIMPLEMENTED,
+        StatusCode::TOO_MANY_REQUESTS => RESOURCE_EXHAUSTED,
+        StatusCode::GATEWAY_TIMEOUT => DEADLINE_EXCEEDED,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => UNAVAILABLE,
+        _ => UNKNOWN,
+    }
+}
+
+// A trailers-only response: gRPC clients read the outcome from grpc-status, not the HTTP status.
+pub fn error_response(http_status: StatusCode, message: &str) -> Response<ProxyBody> {
+    let body = Empty::new().map_err(|never| match never {}).boxed();
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/grpc"),
+    );
+    headers.extend(status_headers(status(http_status), message));
+    response
+}
+
+fn status_headers(code: u16, message: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(GRPC_STATUS, HeaderValue::from(code));
+    if let Ok(message) = HeaderValue::from_str(&percent_encode(message)) {
+        headers.insert(GRPC_MESSAGE, message);
+    }
+    headers
+}
+
+impl DeadlineBody {
+    pub fn new(inner: ProxyBody, remaining: Duration) -> DeadlineBody {
+        DeadlineBody {
+            inner: Some(inner),
+            expiry: Box::pin(sleep(remaining)),
+        }
+    }
+}
+
+impl Body for DeadlineBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+        let Some(inner) = self.inner.as_mut() else {
+            return Poll::Ready(None);
+        };
+        if let Poll::Ready(frame) = Pin::new(inner).poll_frame(cx) {
+            return Poll::Ready(frame);
+        }
+        if self.expiry.as_mut().poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        // Dropping the backend's body cancels the call there as well.
+        self.inner = None;
+        let trailers = status_headers(DEADLINE_EXCEEDED, "deadline exceeded");
+        Poll::Ready(Some(Ok(Frame::trailers(trailers))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.as_ref().is_none_or(Body::is_end_stream)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+fn percent_encode(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        if (0x20..=0x7e).contains(&byte) && byte != b'%' {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::Full;
+
+    fn headers(name: HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn is_grpc_test() {
+        assert!(is_grpc(&headers(header::CONTENT_TYPE, "application/grpc")));
+        assert!(is_grpc(&headers(
+            header::CONTENT_TYPE,
+            "application/grpc+proto"
+        )));
+        assert!(!is_grpc(&headers(
+            header::CONTENT_TYPE,
+            "application/grpc-web"
+        )));
+        assert!(!is_grpc(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn timeout_test() {
+        assert_eq!(
+            timeout(&headers(GRPC_TIMEOUT, "250m")),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(
+            timeout(&headers(GRPC_TIMEOUT, "2H")),
+            Some(Duration::from_secs(7200))
+        );
+        assert_eq!(timeout(&headers(GRPC_TIMEOUT, "123456789m")), None);
+        assert_eq!(timeout(&headers(GRPC_TIMEOUT, "m")), None);
+        assert_eq!(timeout(&headers(GRPC_TIMEOUT, "10x")), None);
+
+        assert_eq!(timeout_value(Duration::from_micros(1500)), "1m");
+        assert_eq!(timeout_value(Duration::from_secs(200_000)), "200000S");
+    }
+
+    #[test]
+    fn error_response_test() {
+        let response = error_response(StatusCode::GATEWAY_TIMEOUT, "backend timed out: 100%");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[GRPC_STATUS], "4");
+        assert_eq!(
+            response.headers()[GRPC_MESSAGE],
+            "backend timed out: 100%25"
+        );
+        assert_eq!(status(StatusCode::NOT_FOUND), UNIMPLEMENTED);
+        assert_eq!(status(StatusCode::SERVICE_UNAVAILABLE), UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn deadline_body_test() {
+        let body = Full::new(Bytes::from_static(b"done"))
+            .map_err(|never| match never {})
+            .boxed();
+        let body = DeadlineBody::new(body, Duration::from_secs(5));
+        let collected = body.collect().await.unwrap();
+        assert!(collected.trailers().is_none());
+        assert_eq!(collected.to_bytes(), "done");
+
+        // A stream that never ends is cut off with DEADLINE_EXCEEDED.
+        let body = DeadlineBody::new(Stalled(false).boxed(), Duration::from_millis(50));
+        let collected = body.collect().await.unwrap();
+        assert_eq!(collected.trailers().unwrap()[GRPC_STATUS], "4");
+        assert_eq!(collected.to_bytes(), "part");
+    }
+
+    // Sends one frame, then nothing ever again.
+    struct Stalled(bool);
+
+    impl Body for Stalled {
+        type Data = Bytes;
+        type Error = hyper::Error;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+            if self.0 {
+                return Poll::Pending;
+            }
+            self.0 = true;
+            Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(b"part")))))
+        }
+    }
+}
@@ -0,0 +1,1317 @@
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode, Uri, Version};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use log::{debug, error, info, warn};
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::sync::oneshot;
+use tokio::time::{Duration, timeout};
+
+use crate::config::pool::BackendProtocol;
+use crate::config::rate_limit::{RateLimitAction, RateLimitKey};
+use crate::config::retry::RetryConfig;
+use crate::config::router_map::RouteRequest;
+use crate::core::failover::{FailoverError, connect_with_failover, multiplex_with_failover};
+use crate::core::grpc;
+use crate::core::router::SharedRouter;
+use crate::core::session_key::request_key;
+use crate::core::shutdown::Shutdown;
+use crate::domain::request::{Request as RequestRecord, TerminationReason};
+use crate::infrastructure::fast_tcp_pool::{
+    BackendSlot, ConnectionPool, Http2Sender, PooledStream,
+};
+use crate::infrastructure::metrics::ListenerMetrics;
+use crate::infrastructure::rate_limit::{Admission, RateLimiter};
+use crate::infrastructure::smart_tcp_pool::SmartTcpConnPool;
+use crate::infrastructure::tls::{ALPN_H2, ClientStream};
+
+pub type ProxyBody = BoxBody<Bytes, hyper::Error>;
+type ServeError = Box<dyn std::error::Error + Send + Sync>;
+
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+pub struct HttpProxy {
+    pub router: SharedRouter,
+    pub listener: String,
+    pub retry: RetryConfig,
+    pub request_timeout: Duration,
+    pub request_counter: Arc<AtomicU64>,
+    pub metrics: Arc<ListenerMetrics>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub shutdown: Shutdown,
+}
+
+// A backend connection of its own for HTTP/1.1, or a share of one for HTTP/2.
+enum Upstream {
+    Http1(PooledStream),
+    Http2(Http2Sender),
+}
+
+// Tags the responses the proxy answers by itself, so gRPC callers get them as a grpc-status.
+#[derive(Debug, Clone)]
+struct ProxyError(String);
+
+// Handed to hyper to close the connection without an answer.
+#[derive(Debug)]
+struct Dropped;
+
+// Signals `done` once the wrapped body has been read to the end.
+struct EndOfStream<B> {
+    inner: B,
+    done: Option<oneshot::Sender<()>>,
+}
+
+// Records the request once its response body has been streamed out or dropped.
+struct InFlight {
+    request: RequestRecord,
+    start: Instant,
+    bytes: usize,
+    bytes_received: Arc<AtomicUsize>,
+    metrics: Arc<ListenerMetrics>,
+}
+
+pub async fn serve_http(
+    proxy: Arc<HttpProxy>,
+    incoming_stream: ClientStream,
+    client_addr: SocketAddr,
+) -> Result<(), ServeError> {
+    let shutdown = proxy.shutdown.clone();
+    let scheme = if incoming_stream.is_tls() {
+        "https"
+    } else {
+        "http"
+    };
+    let service = service_fn(move |request| {
+        let proxy = Arc::clone(&proxy);
+        async move {
+            let is_grpc = grpc::is_grpc(request.headers());
+            let response = match proxy.throttle(request.headers(), client_addr).await? {
+                Some(response) => response,
+                None => {
+                    let Ok(response) = proxy.forward(request, client_addr, scheme).await;
+                    response
+                }
+            };
+            match response.extensions().get::<ProxyError>() {
+                Some(ProxyError(message)) if is_grpc => {
+                    Ok(grpc::error_response(response.status(), message))
+                }
+                _ => Ok::<_, Dropped>(response),
+            }
+        }
+    });
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder.http1().keep_alive(true);
+    // TLS clients get HTTP/2 only if they negotiated it through ALPN; plaintext clients may
+    // open with the h2c prior-knowledge preface instead of an HTTP/1.1 request.
+    let builder = match incoming_stream.alpn_protocol() {
+        Some(ALPN_H2) => builder.http2_only(),
+        _ if incoming_stream.is_tls() => builder.http1_only(),
+        _ => builder,
+    };
+    let connection = builder.serve_connection(TokioIo::new(incoming_stream), service);
+    tokio::pin!(connection);
+    // On shutdown the in-flight request is finished, then the keep-alive connection is closed.
+    tokio::select! {
+        result = connection.as_mut() => return result.or_else(ignore_dropped),
+        _ = shutdown.triggered() => connection.as_mut().graceful_shutdown(),
+    }
+    connection.await.or_else(ignore_dropped)
+}
+
+fn ignore_dropped(e: ServeError) -> Result<(), ServeError> {
+    let dropped = e
+        .downcast_ref::<hyper::Error>()
+        .and_then(std::error::Error::source)
+        .is_some_and(|source| source.is::<Dropped>());
+    if dropped { Ok(()) } else { Err(e) }
+}
+
+impl fmt::Display for Dropped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "connection dropped by the rate limiter")
+    }
+}
+
+impl std::error::Error for Dropped {}
+
+impl HttpProxy {
+    // Ok(None) lets the request through, after waiting out its delay if it got one.
+    async fn throttle(
+        &self,
+        headers: &HeaderMap,
+        client_addr: SocketAddr,
+    ) -> Result<Option<Response<ProxyBody>>, Dropped> {
+        let Some(rate_limiter) = &self.rate_limiter else {
+            return Ok(None);
+        };
+        let key = match &rate_limiter.config().key {
+            RateLimitKey::Header(name) => headers
+                .get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                .map(|value| format!("{}:{}", name, value)),
+            RateLimitKey::ClientIp => None,
+        }
+        .unwrap_or_else(|| client_addr.ip().to_string());
+        match rate_limiter.check(&key) {
+            Admission::Allow => Ok(None),
+            Admission::Delay(wait) => {
+                tokio::time::sleep(wait).await;
+                Ok(None)
+            }
+            Admission::Deny(_) if rate_limiter.config().action == RateLimitAction::Drop => {
+                debug!("Dropping {}: rate limit exceeded for {}", client_addr, key);
+                Err(Dropped)
+            }
+            Admission::Deny(retry_after) => {
+                debug!("Rejecting {}: rate limit exceeded for {}", client_addr, key);
+                let mut response =
+                    error_response(StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded");
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+                );
+                Ok(Some(response))
+            }
+        }
+    }
+
+    async fn forward(
+        &self,
+        mut request: Request<Incoming>,
+        client_addr: SocketAddr,
+        scheme: &'static str,
+    ) -> Result<Response<ProxyBody>, Infallible> {
+        let start = Instant::now();
+        let request_id = self.request_counter.fetch_add(1, Ordering::Relaxed);
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let deadline = grpc::timeout(request.headers());
+
+        let host = request
+            .headers()
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| {
+                request
+                    .uri()
+                    .authority()
+                    .map(|authority| authority.as_str())
+            });
+        let route = RouteRequest {
+            listener: &self.listener,
+            host,
+            path: Some(&path),
+            method: Some(method.as_str()),
+        };
+        let Some(group) = self.router.load().route(&route) else {
+            warn!(
+                "Request {}: no route for {} {} (host {:?})",
+                request_id, method, path, host
+            );
+            return Ok(error_response(StatusCode::NOT_FOUND, "no route"));
+        };
+        let pool = &group.pool;
+
+        let key = request_key(&pool.hash_key, request.headers(), client_addr, request_id);
+        let user_id = SmartTcpConnPool::user_id(key);
+        let mut in_flight = InFlight {
+            request: RequestRecord::new(request_id, format!("{} {}", method, path), user_id),
+            start,
+            bytes: 0,
+            bytes_received: Arc::new(AtomicUsize::new(0)),
+            metrics: Arc::clone(&self.metrics),
+        };
+        in_flight.request.set_client(client_addr);
+        let preferred = group
+            .sticky
+            .as_ref()
+            .and_then(|sticky| sticky.get_or_assign_backend(user_id));
+
+        // A gRPC deadline also covers the time spent connecting.
+        let connecting = connect_upstream(pool, key, preferred, &self.retry);
+        let connected = match deadline {
+            Some(deadline) => timeout(deadline.saturating_sub(start.elapsed()), connecting).await,
+            None => Ok(connecting.await),
+        };
+        let (backend_idx, upstream, active) = match connected {
+            Ok(Ok(connection)) => connection,
+            Ok(Err(e)) => {
+                error!("Request {}: {} {} failed: {}", request_id, method, path, e);
+                let (code, _) = e.http_status();
+                let status = StatusCode::from_u16(code).unwrap_or(StatusCode::BAD_GATEWAY);
+                in_flight.fail(TerminationReason::NoBackend, status);
+                return Ok(error_response(status, &e.to_string()));
+            }
+            Err(_) => {
+                error!(
+                    "Request {}: deadline exceeded while connecting for {} {}",
+                    request_id, method, path
+                );
+                in_flight.fail(TerminationReason::Timeout, StatusCode::GATEWAY_TIMEOUT);
+                return Ok(error_response(
+                    StatusCode::GATEWAY_TIMEOUT,
+                    "deadline exceeded",
+                ));
+            }
+        };
+        if let Some(sticky) = &group.sticky
+            && preferred != Some(backend_idx)
+        {
+            sticky.pin(user_id, backend_idx);
+        }
+        let backend = pool.backends[backend_idx].address();
+        in_flight.request.set_backend(backend.clone());
+        let backend_metrics = Arc::clone(&pool.metrics[backend_idx]);
+
+        prepare_upstream_request(&mut request, client_addr, scheme);
+        // The backend learns how much of the deadline is left.
+        let request_timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_sub(start.elapsed());
+                request
+                    .headers_mut()
+                    .insert(grpc::GRPC_TIMEOUT, grpc::timeout_value(remaining));
+                remaining.min(self.request_timeout)
+            }
+            None => self.request_timeout,
+        };
+        let request = {
+            let listener_metrics = Arc::clone(&self.metrics);
+            let backend_metrics = Arc::clone(&backend_metrics);
+            let bytes_received = Arc::clone(&in_flight.bytes_received);
+            request.map(|body| {
+                body.map_frame(move |frame| {
+                    if let Some(data) = frame.data_ref() {
+                        bytes_received.fetch_add(data.len(), Ordering::Relaxed);
+                        let len = data.len() as u64;
+                        listener_metrics
+                            .bytes_received
+                            .fetch_add(len, Ordering::Relaxed);
+                        backend_metrics.bytes_sent.fetch_add(len, Ordering::Relaxed);
+                    }
+                    frame
+                })
+                .boxed()
+            })
+        };
+        let sent_at = Instant::now();
+        let response = timeout(
+            request_timeout,
+            send(pool.clone(), backend_idx, upstream, request),
+        )
+        .await;
+        self.metrics.session_duration.observe(start.elapsed());
+        backend_metrics.session_duration.observe(sent_at.elapsed());
+
+        let (response, reuse) = match response {
+            Ok(Ok(sent)) => sent,
+            Ok(Err(e)) => {
+                error!("Request {}: backend {} failed: {}", request_id, backend, e);
+                pool.report_failure(backend_idx);
+                in_flight.fail(TerminationReason::IoError, StatusCode::BAD_GATEWAY);
+                return Ok(error_response(StatusCode::BAD_GATEWAY, "backend error"));
+            }
+            Err(_) => {
+                error!("Request {}: backend {} timed out", request_id, backend);
+                backend_metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+                pool.report_failure(backend_idx);
+                in_flight.fail(TerminationReason::Timeout, StatusCode::GATEWAY_TIMEOUT);
+                return Ok(error_response(
+                    StatusCode::GATEWAY_TIMEOUT,
+                    "backend timed out",
+                ));
+            }
+        };
+
+        let status = response.status();
+        if status.is_server_error() {
+            warn!(
+                "Request {}: backend {} answered {}",
+                request_id, backend, status
+            );
+            pool.report_failure(backend_idx);
+        } else {
+            pool.report_success(backend_idx);
+        }
+        info!(
+            "Request {}: {} {} -> {} {} in {:?}",
+            request_id,
+            method,
+            path,
+            backend,
+            status.as_u16(),
+            start.elapsed()
+        );
+
+        in_flight.request.set_http_status(status.as_u16());
+        in_flight.request.finish(TerminationReason::Done);
+
+        let keep_alive = is_keep_alive(&response);
+        let (mut parts, body) = response.into_parts();
+        strip_hop_by_hop(&mut parts.headers);
+        // The backend stays "active" until the response body has been streamed out.
+        let listener_metrics = Arc::clone(&self.metrics);
+        let body = EndOfStream::new(body, reuse.filter(|_| keep_alive))
+            .map_frame(move |frame| {
+                let _ = &active;
+                if let Some(data) = frame.data_ref() {
+                    in_flight.add_bytes(data.len());
+                    let len = data.len() as u64;
+                    listener_metrics
+                        .bytes_sent
+                        .fetch_add(len, Ordering::Relaxed);
+                    backend_metrics
+                        .bytes_received
+                        .fetch_add(len, Ordering::Relaxed);
+                }
+                frame
+            })
+            .boxed();
+        let body = match deadline {
+            Some(deadline) => {
+                grpc::DeadlineBody::new(body, deadline.saturating_sub(start.elapsed())).boxed()
+            }
+            None => body,
+        };
+        Ok(Response::from_parts(parts, body))
+    }
+}
+
+impl InFlight {
+    fn add_bytes(&mut self, bytes: usize) {
+        self.bytes += bytes;
+    }
+
+    fn fail(&mut self, reason: TerminationReason, status: StatusCode) {
+        self.request.set_http_status(status.as_u16());
+        self.request.finish(reason);
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.request.set_bytes(self.bytes);
+        self.request
+            .set_bytes_received(self.bytes_received.load(Ordering::Relaxed));
+        self.request
+            .set_time_taken(self.start.elapsed().as_secs_f64());
+        self.metrics.record(self.request.clone());
+    }
+}
+
+impl<B: Body + Unpin> EndOfStream<B> {
+    fn new(inner: B, done: Option<oneshot::Sender<()>>) -> EndOfStream<B> {
+        let mut body = EndOfStream { inner, done };
+        // Bodies that are empty from the start (HEAD, 204, 304) are never polled.
+        if body.inner.is_end_stream() {
+            body.finish();
+        }
+        body
+    }
+
+    fn finish(&mut self) {
+        if let Some(done) = self.done.take() {
+            let _ = done.send(());
+        }
+    }
+}
+
+impl<B: Body + Unpin> Body for EndOfStream<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(None) => self.finish(),
+            Poll::Ready(Some(Ok(_))) if self.inner.is_end_stream() => self.finish(),
+            _ => {}
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+async fn connect_upstream(
+    pool: &ConnectionPool,
+    session_id: u64,
+    preferred: Option<usize>,
+    retry: &RetryConfig,
+) -> Result<(usize, Upstream, BackendSlot), FailoverError> {
+    match pool.protocol {
+        BackendProtocol::Http1 => connect_with_failover(pool, session_id, preferred, retry)
+            .await
+            .map(|(backend_idx, pooled, slot)| (backend_idx, Upstream::Http1(pooled), slot)),
+        BackendProtocol::Http2 => multiplex_with_failover(pool, session_id, preferred, retry)
+            .await
+            .map(|(backend_idx, sender, slot)| (backend_idx, Upstream::Http2(sender), slot)),
+    }
+}
+
+// Sends one request over a backend connection. For HTTP/1.1, once the response body has
+// been read to the end and the returned sender fires, the connection goes back to the pool.
+// HTTP/2 connections stay shared and need no hand-back.
+async fn send(
+    pool: ConnectionPool,
+    backend_idx: usize,
+    upstream: Upstream,
+    mut request: Request<ProxyBody>,
+) -> Result<(Response<Incoming>, Option<oneshot::Sender<()>>), hyper::Error> {
+    let pooled = match upstream {
+        Upstream::Http1(pooled) => pooled,
+        Upstream::Http2(mut sender) => {
+            let scheme = if pool.tls.is_some() { "https" } else { "http" };
+            into_http2(&mut request, scheme, &pool.backends[backend_idx].address());
+            sender.ready().await?;
+            return Ok((sender.send_request(request).await?, None));
+        }
+    };
+    let PooledStream {
+        stream,
+        created,
+        idle_since,
+        requests,
+    } = pooled;
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    let (reuse, reusable) = oneshot::channel();
+    tokio::spawn(async move {
+        match connection.without_shutdown().await {
+            Ok(parts) if parts.read_buf.is_empty() && reusable.await.is_ok() => {
+                let pooled = PooledStream {
+                    stream: parts.io.into_inner(),
+                    created,
+                    idle_since,
+                    requests,
+                };
+                pool.release(backend_idx, pooled);
+            }
+            Ok(_) => {}
+            Err(e) => error!("Backend connection error: {}", e),
+        }
+    });
+    let response = sender.send_request(request).await?;
+    Ok((response, Some(reuse)))
+}
+
+// HTTP/2 carries the host in the :authority pseudo-header instead of a Host header.
+// Requests without a usable Host are addressed to the backend itself.
+fn into_http2<B>(request: &mut Request<B>, scheme: &str, backend: &str) {
+    let host = request.headers_mut().remove(header::HOST);
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str())
+        .to_string();
+    let uri = |authority: &[u8]| {
+        Uri::builder()
+            .scheme(scheme)
+            .authority(authority)
+            .path_and_query(path_and_query.as_str())
+            .build()
+            .ok()
+    };
+    if let Some(uri) = host
+        .and_then(|host| uri(host.as_bytes()))
+        .or_else(|| uri(backend.as_bytes()))
+    {
+        *request.uri_mut() = uri;
+    }
+    *request.version_mut() = Version::HTTP_2;
+}
+
+fn is_keep_alive(response: &Response<Incoming>) -> bool {
+    let connection = response
+        .headers()
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|token| token.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    match response.version() {
+        Version::HTTP_11 => !connection.iter().any(|token| token == "close"),
+        Version::HTTP_10 => connection.iter().any(|token| token == "keep-alive"),
+        _ => false,
+    }
+}
+
+fn prepare_upstream_request(
+    request: &mut Request<Incoming>,
+    client_addr: SocketAddr,
+    scheme: &'static str,
+) {
+    if let Some(path_and_query) = request.uri().path_and_query().cloned() {
+        let host = request.uri().authority().cloned();
+        *request.uri_mut() = Uri::from(path_and_query);
+        if let Some(host) = host
+            && !request.headers().contains_key(header::HOST)
+            && let Ok(value) = HeaderValue::from_str(host.as_str())
+        {
+            request.headers_mut().insert(header::HOST, value);
+        }
+    }
+
+    let headers = request.headers_mut();
+    // gRPC needs `te: trailers` end to end, and trailers are passed through either way.
+    let trailers = headers
+        .get_all(header::TE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case("trailers"));
+    strip_hop_by_hop(headers);
+    if trailers {
+        headers.insert(header::TE, HeaderValue::from_static("trailers"));
+    }
+    let forwarded_for = match headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(previous) => format!("{}, {}", previous, client_addr.ip()),
+        None => client_addr.ip().to_string(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+        headers.insert(HeaderName::from_static("x-forwarded-for"), value);
+    }
+    headers.insert(
+        HeaderName::from_static("x-forwarded-proto"),
+        HeaderValue::from_static(scheme),
+    );
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<String> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .collect();
+    for name in HOP_BY_HOP_HEADERS
+        .iter()
+        .copied()
+        .chain(listed.iter().map(String::as_str))
+    {
+        headers.remove(name);
+    }
+}
+
+pub fn error_response(status: StatusCode, message: &str) -> Response<ProxyBody> {
+    let body = Full::new(Bytes::from(format!("{}\n", message)))
+        .map_err(|never| match never {})
+        .boxed();
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+    response
+        .extensions_mut()
+        .insert(ProxyError(message.to_string()));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::listener::ListenerMode;
+    use crate::config::router_map::{Route, RouterMap};
+    use crate::core::router::{BackendGroup, Router};
+    use crate::domain::backend_conn::ConnString;
+    use crate::domain::request::Status;
+    use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+    use crate::infrastructure::request_log::RequestLog;
+    use crate::infrastructure::tls::tests::{
+        backend_tls, certificate, connect, connector, tls_config,
+    };
+    use crate::infrastructure::tls::{ALPN_HTTP11, TlsOriginator, TlsTerminator};
+    use arc_swap::ArcSwap;
+    use hyper::server::conn::http1 as server_http1;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn backend(name: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let service = service_fn(move |request: Request<Incoming>| async move {
+                        let forwarded = request
+                            .headers()
+                            .get("x-forwarded-for")
+                            .map(|value| value.to_str().unwrap().to_string())
+                            .unwrap_or_default();
+                        let body = format!("{} {} {}", name, request.uri(), forwarded);
+                        let mut response = Response::new(Full::new(Bytes::from(body)));
+                        if let Some(proto) = request.headers().get("x-forwarded-proto") {
+                            response
+                                .headers_mut()
+                                .insert("x-forwarded-proto", proto.clone());
+                        }
+                        Ok::<_, Infallible>(response)
+                    });
+                    let _ = server_http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn start_proxy(
+        backends: Vec<SocketAddr>,
+        shutdown: Shutdown,
+        requests: Arc<RequestLog>,
+    ) -> SocketAddr {
+        start_proxy_with(pool(backends), shutdown, requests, None, None).await
+    }
+
+    fn pool(backends: Vec<SocketAddr>) -> ConnectionPool {
+        let backends = backends
+            .iter()
+            .map(|addr| ConnString::new(addr.ip().to_string(), addr.port()))
+            .collect();
+        ConnectionPool::new(backends, 10)
+    }
+
+    async fn start_proxy_with(
+        pool: ConnectionPool,
+        shutdown: Shutdown,
+        requests: Arc<RequestLog>,
+        rate_limiter: Option<Arc<RateLimiter>>,
+        tls: Option<TlsTerminator>,
+    ) -> SocketAddr {
+        let mut map = RouterMap::new();
+        map.map_route(Route {
+            path_prefix: Some("/missing".to_string()),
+            group: "missing".to_string(),
+            ..Route::default()
+        });
+        map.default_route("web");
+        let mut router = Router::new(map);
+        router.add_group(BackendGroup::new("web", pool));
+        let proxy = Arc::new(HttpProxy {
+            router: Arc::new(ArcSwap::from_pointee(router)),
+            listener: "127.0.0.1:0".to_string(),
+            retry: RetryConfig::default(),
+            request_timeout: Duration::from_secs(5),
+            request_counter: Arc::new(AtomicU64::new(0)),
+            metrics: Arc::new(ListenerMetrics::new(requests)),
+            rate_limiter,
+            shutdown,
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, client_addr)) = listener.accept().await {
+                let proxy = Arc::clone(&proxy);
+                let tls = tls.clone();
+                tokio::spawn(async move {
+                    let stream = match tls {
+                        Some(tls) => tls.accept(stream).await.unwrap(),
+                        None => ClientStream::Plain(stream),
+                    };
+                    serve_http(proxy, stream, client_addr).await
+                });
+            }
+        });
+        addr
+    }
+
+    async fn read_response(client: &mut TcpStream) -> String {
+        let mut buf = vec![0u8; 4096];
+        let mut response = String::new();
+        while !response.contains("\r\n\r\n") || !response_complete(&response) {
+            let n = client.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            response.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+        }
+        response
+    }
+
+    fn response_complete(response: &str) -> bool {
+        let Some((head, body)) = response.split_once("\r\n\r\n") else {
+            return false;
+        };
+        head.lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .and_then(|len| len.trim().parse::<usize>().ok())
+            .is_some_and(|len| body.len() >= len)
+    }
+
+    #[test]
+    fn strip_hop_by_hop_test() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONNECTION, "keep-alive, x-custom".parse().unwrap());
+        headers.insert("x-custom", "1".parse().unwrap());
+        headers.insert("keep-alive", "timeout=5".parse().unwrap());
+        headers.insert("x-kept", "1".parse().unwrap());
+
+        strip_hop_by_hop(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert!(headers.contains_key("x-kept"));
+    }
+
+    #[test]
+    fn into_http2_test() {
+        let mut request = Request::get("/users?page=2")
+            .header(header::HOST, "api.example.com")
+            .body(())
+            .unwrap();
+        into_http2(&mut request, "https", "10.0.0.1:8443");
+        assert_eq!(request.version(), Version::HTTP_2);
+        assert_eq!(request.uri(), "https://api.example.com/users?page=2");
+        assert!(!request.headers().contains_key(header::HOST));
+
+        for host in [None, Some("bad host")] {
+            let mut request = Request::get("/health").body(()).unwrap();
+            if let Some(host) = host {
+                request
+                    .headers_mut()
+                    .insert(header::HOST, HeaderValue::from_static(host));
+            }
+            into_http2(&mut request, "http", "10.0.0.1:8080");
+            assert_eq!(request.uri(), "http://10.0.0.1:8080/health");
+        }
+    }
+
+    #[tokio::test]
+    async fn balances_each_request_on_keep_alive_connection_test() {
+        let requests = Arc::new(RequestLog::new(10));
+        let proxy = start_proxy(
+            vec![backend("a").await, backend("b").await],
+            Shutdown::new(),
+            Arc::clone(&requests),
+        )
+        .await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+
+        let mut bodies = Vec::new();
+        for _ in 0..2 {
+            client
+                .write_all(b"GET /hello?x=1 HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await
+                .unwrap();
+            let response = read_response(&mut client).await;
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            bodies.push(response.split_once("\r\n\r\n").unwrap().1.to_string());
+        }
+
+        assert_eq!(bodies[0], "a /hello?x=1 127.0.0.1");
+        assert_eq!(bodies[1], "b /hello?x=1 127.0.0.1");
+
+        // Requests are recorded once their body has been streamed out.
+        while requests.recent(10, None).len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let recent = requests.recent(10, None);
+        assert_eq!(recent[0].get_status(), &Status::Completed);
+        assert_eq!(recent[0].get_target(), "GET /hello");
+        let record = serde_json::to_value(&recent[0]).unwrap();
+        assert_eq!(record["bytes"], bodies[1].len());
+    }
+
+    #[tokio::test]
+    async fn reuses_backend_connection_after_complete_response_test() {
+        let backend_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend_listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let accepted = Arc::clone(&accepted);
+            async move {
+                while let Ok((stream, _)) = backend_listener.accept().await {
+                    accepted.fetch_add(1, Ordering::Relaxed);
+                    tokio::spawn(async move {
+                        let service = service_fn(|_: Request<Incoming>| async {
+                            Ok::<_, Infallible>(Response::new(Full::new(Bytes::from("pong"))))
+                        });
+                        let _ = server_http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await;
+                    });
+                }
+            }
+        });
+        let proxy = start_proxy(
+            vec![backend_addr],
+            Shutdown::new(),
+            Arc::new(RequestLog::new(10)),
+        )
+        .await;
+
+        for _ in 0..3 {
+            let mut client = TcpStream::connect(proxy).await.unwrap();
+            client
+                .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await
+                .unwrap();
+            let response = read_response(&mut client).await;
+            assert!(response.ends_with("pong"));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(accepted.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn reuses_tls_backend_connection_test() {
+        let (cert, _) = certificate(&["backend.internal"], &[]);
+        let tls = TlsTerminator::new(&tls_config(vec![cert.clone()]), ListenerMode::Http).unwrap();
+        let backend_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend_listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let accepted = Arc::clone(&accepted);
+            async move {
+                while let Ok((stream, _)) = backend_listener.accept().await {
+                    accepted.fetch_add(1, Ordering::Relaxed);
+                    let tls = tls.clone();
+                    tokio::spawn(async move {
+                        let stream = tls.accept(stream).await.unwrap();
+                        let service = service_fn(|request: Request<Incoming>| async move {
+                            let body = format!("tls {}", request.uri());
+                            Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(body))))
+                        });
+                        let _ = server_http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await;
+                    });
+                }
+            }
+        });
+        let mut pool = pool(vec![backend_addr]);
+        pool.tls(TlsOriginator::new(&backend_tls(&cert.cert, Some("backend.internal"))).unwrap());
+        let proxy = start_proxy_with(
+            pool,
+            Shutdown::new(),
+            Arc::new(RequestLog::new(10)),
+            None,
+            None,
+        )
+        .await;
+
+        for _ in 0..3 {
+            let mut client = TcpStream::connect(proxy).await.unwrap();
+            client
+                .write_all(b"GET /secure HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await
+                .unwrap();
+            let response = read_response(&mut client).await;
+            assert!(response.ends_with("tls /secure"));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(accepted.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn accepts_h2c_prior_knowledge_test() {
+        let proxy = start_proxy(
+            vec![backend("a").await],
+            Shutdown::new(),
+            Arc::new(RequestLog::new(10)),
+        )
+        .await;
+        let stream = TcpStream::connect(proxy).await.unwrap();
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+        let request = Request::get("http://localhost/h2c")
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+        let response = sender.send_request(request).await.unwrap();
+        assert_eq!(response.version(), Version::HTTP_2);
+        assert_eq!(response.headers()["x-forwarded-proto"], "http");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "a /h2c 127.0.0.1");
+    }
+
+    // Speaks HTTP/2 only and echoes the version and URI it received.
+    async fn h2_backend(tls: Option<TlsTerminator>) -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let accepted = Arc::clone(&accepted);
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    accepted.fetch_add(1, Ordering::Relaxed);
+                    let tls = tls.clone();
+                    tokio::spawn(async move {
+                        let stream = match tls {
+                            Some(tls) => tls.accept(stream).await.unwrap(),
+                            None => ClientStream::Plain(stream),
+                        };
+                        let service = service_fn(|request: Request<Incoming>| async move {
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            let body = format!("{:?} {}", request.version(), request.uri());
+                            Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(body))))
+                        });
+                        let _ = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await;
+                    });
+                }
+            }
+        });
+        (addr, accepted)
+    }
+
+    #[tokio::test]
+    async fn multiplexes_requests_on_http2_backend_test() {
+        let (cert, _) = certificate(&["backend.internal"], &[]);
+        let tls = TlsTerminator::new(&tls_config(vec![cert.clone()]), ListenerMode::Http).unwrap();
+        for (tls, scheme) in [(None, "http"), (Some(tls), "https")] {
+            let originated = tls.is_some();
+            let (backend_addr, accepted) = h2_backend(tls).await;
+            let mut pool = pool(vec![backend_addr]);
+            pool.protocol(BackendProtocol::Http2);
+            if originated {
+                let config = backend_tls(&cert.cert, Some("backend.internal"));
+                pool.tls(TlsOriginator::new(&config).unwrap());
+            }
+            let metrics = Arc::clone(&pool.metrics[0]);
+            let proxy = start_proxy_with(
+                pool,
+                Shutdown::new(),
+                Arc::new(RequestLog::new(10)),
+                None,
+                None,
+            )
+            .await;
+
+            // Requests overlapping on a cold pool wait for a single connection.
+            let requests: Vec<_> = (0..4)
+                .map(|idx| {
+                    tokio::spawn(async move {
+                        let mut client = TcpStream::connect(proxy).await.unwrap();
+                        let request =
+                            format!("GET /r{} HTTP/1.1\r\nHost: example.com\r\n\r\n", idx);
+                        client.write_all(request.as_bytes()).await.unwrap();
+                        read_response(&mut client).await
+                    })
+                })
+                .collect();
+            for (idx, request) in requests.into_iter().enumerate() {
+                let response = request.await.unwrap();
+                assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+                let expected = format!("HTTP/2.0 {}://example.com/r{}", scheme, idx);
+                assert!(response.ends_with(&expected), "{}", response);
+            }
+            assert_eq!(accepted.load(Ordering::Relaxed), 1);
+            assert_eq!(metrics.connections.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    // Answers every call with grpc-status 0 in the trailers, echoing the deadline and TE it got.
+    async fn grpc_backend() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let service = service_fn(|request: Request<Incoming>| async move {
+                    if request.uri().path().ends_with("/Slow") {
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                    }
+                    // Streams the first message right away and finishes much later.
+                    let streaming = request.uri().path().ends_with("/Watch");
+                    let echo = |name: &str| request.headers().get(name).cloned();
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                    let mut response = Response::new(
+                        Full::new(Bytes::from_static(&[0, 0, 0, 0, 0])).with_trailers(async move {
+                            if streaming {
+                                tokio::time::sleep(Duration::from_millis(500)).await;
+                            }
+                            Some(Ok(trailers))
+                        }),
+                    );
+                    for (name, value) in [
+                        ("x-grpc-timeout", echo("grpc-timeout")),
+                        ("x-te", echo("te")),
+                    ] {
+                        if let Some(value) = value {
+                            response.headers_mut().insert(name, value);
+                        }
+                    }
+                    Ok::<_, Infallible>(response)
+                });
+                tokio::spawn(
+                    hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service),
+                );
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn proxies_grpc_calls_test() {
+        let mut pool = pool(vec![grpc_backend().await]);
+        pool.protocol(BackendProtocol::Http2);
+        let proxy = start_proxy_with(
+            pool,
+            Shutdown::new(),
+            Arc::new(RequestLog::new(10)),
+            None,
+            None,
+        )
+        .await;
+        let stream = TcpStream::connect(proxy).await.unwrap();
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+        let call = |path: &str, timeout: &'static str| {
+            Request::post(format!("http://localhost{}", path))
+                .header(header::CONTENT_TYPE, "application/grpc")
+                .header(header::TE, "trailers")
+                .header("grpc-timeout", timeout)
+                .body(Full::new(Bytes::from_static(&[0, 0, 0, 0, 0])))
+                .unwrap()
+        };
+
+        let response = sender
+            .send_request(call("/shop.v1.Orders/Get", "10S"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-te"], "trailers");
+        let deadline: u64 = response.headers()["x-grpc-timeout"]
+            .to_str()
+            .unwrap()
+            .trim_end_matches('m')
+            .parse()
+            .unwrap();
+        assert!(deadline > 9000 && deadline <= 10000);
+        let body = response.into_body().collect().await.unwrap();
+        assert_eq!(body.trailers().unwrap()["grpc-status"], "0");
+
+        // The client's deadline is shorter than the proxy's request timeout.
+        let response = sender
+            .send_request(call("/shop.v1.Orders/Slow", "100m"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["grpc-status"], "4");
+        assert_eq!(response.headers()["grpc-message"], "backend timed out");
+
+        let response = sender
+            .send_request(call("/shop.v1.Orders/Watch", "100m"))
+            .await
+            .unwrap();
+        assert!(!response.headers().contains_key("grpc-status"));
+        let body = response.into_body().collect().await.unwrap();
+        assert_eq!(body.trailers().unwrap()["grpc-status"], "4");
+
+        let response = sender
+            .send_request(call("/missing/Get", "1S"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["grpc-status"], "12");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/grpc");
+    }
+
+    #[tokio::test]
+    async fn returns_502_when_backends_fail_test() {
+        let dead = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_addr = dead.local_addr().unwrap();
+        drop(dead);
+
+        let requests = Arc::new(RequestLog::new(10));
+        let proxy = start_proxy(vec![dead_addr], Shutdown::new(), Arc::clone(&requests)).await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await
+            .unwrap();
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 502 Bad Gateway\r\n"));
+        let recent = requests.recent(10, None);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].get_status(), &Status::Failed);
+        assert_eq!(recent[0].get_target(), "GET /");
+    }
+
+    async fn start_rate_limited_proxy(config: &str) -> SocketAddr {
+        start_proxy_with(
+            pool(vec![backend("a").await]),
+            Shutdown::new(),
+            Arc::new(RequestLog::new(10)),
+            Some(Arc::new(RateLimiter::new(toml::from_str(config).unwrap()))),
+            None,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_get_429_test() {
+        let proxy = start_rate_limited_proxy(
+            "requests_per_sec = 0.5\nburst = 1\nkey = { header = \"X-Api-Key\" }",
+        )
+        .await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        for (key, expected) in [
+            ("one", "HTTP/1.1 200 OK\r\n"),
+            ("one", "HTTP/1.1 429 Too Many Requests\r\n"),
+            ("two", "HTTP/1.1 200 OK\r\n"),
+        ] {
+            let request = format!(
+                "GET / HTTP/1.1\r\nHost: example.com\r\nX-Api-Key: {}\r\n\r\n",
+                key
+            );
+            client.write_all(request.as_bytes()).await.unwrap();
+            let response = read_response(&mut client).await;
+            assert!(response.starts_with(expected), "{}", response);
+            if expected.contains("429") {
+                assert!(response.to_lowercase().contains("retry-after: 2\r\n"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limit_drop_closes_connection_test() {
+        let proxy = start_rate_limited_proxy("requests_per_sec = 1\naction = \"drop\"").await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        let request = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        client.write_all(request).await.unwrap();
+        assert!(
+            read_response(&mut client)
+                .await
+                .starts_with("HTTP/1.1 200 OK\r\n")
+        );
+
+        client.write_all(request).await.unwrap();
+        let mut rest = Vec::new();
+        let _ = client.read_to_end(&mut rest).await;
+        assert!(rest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn returns_404_without_route_test() {
+        let proxy = start_proxy(
+            vec![backend("a").await],
+            Shutdown::new(),
+            Arc::new(RequestLog::new(10)),
+        )
+        .await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        client
+            .write_all(b"GET /missing HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await
+            .unwrap();
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[tokio::test]
+    async fn closes_keep_alive_connection_on_shutdown_test() {
+        let shutdown = Shutdown::new();
+        let proxy = start_proxy(
+            vec![backend("a").await],
+            shutdown.clone(),
+            Arc::new(RequestLog::new(10)),
+        )
+        .await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(
+            read_response(&mut client)
+                .await
+                .starts_with("HTTP/1.1 200 OK\r\n")
+        );
+
+        shutdown.trigger();
+        let mut buf = [0u8; 16];
+        let read = tokio::time::timeout(Duration::from_secs(1), client.read(&mut buf)).await;
+        assert_eq!(read.unwrap().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn terminates_tls_for_h2_and_http1_clients_test() {
+        let (cert, der) = certificate(&["localhost"], &[]);
+        let tls = TlsTerminator::new(&tls_config(vec![cert]), ListenerMode::Http).unwrap();
+        let proxy = start_proxy_with(
+            pool(vec![backend("a").await]),
+            Shutdown::new(),
+            Arc::new(RequestLog::new(10)),
+            None,
+            Some(tls),
+        )
+        .await;
+
+        let stream = connect(&connector(&[&der], &[ALPN_H2]), proxy, "localhost")
+            .await
+            .unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(ALPN_H2));
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+        for _ in 0..2 {
+            let request = Request::get("https://localhost/h2")
+                .body(Full::new(Bytes::new()))
+                .unwrap();
+            let response = sender.send_request(request).await.unwrap();
+            assert_eq!(response.version(), Version::HTTP_2);
+            assert_eq!(response.headers()["x-forwarded-proto"], "https");
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, "a /h2 127.0.0.1");
+        }
+
+        let mut stream = connect(&connector(&[&der], &[ALPN_HTTP11]), proxy, "localhost")
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET /h1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("x-forwarded-proto: https\r\n"));
+        assert!(response.ends_with("a /h1 127.0.0.1"));
+    }
+}
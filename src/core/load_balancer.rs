@@ -1,51 +1,477 @@
-use log::{error, info};
+use arc_swap::ArcSwap;
+use log::{debug, error, info, warn};
 
+use std::net::SocketAddr;
 use std::sync::{
     Arc,
     atomic::{AtomicU64, Ordering},
 };
-use tokio::{io, time::Duration};
-use tokio::time::timeout;
+use std::time::Instant;
 use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinError, JoinSet};
+use tokio::time::timeout;
+use tokio::{io, time::Duration};
 
-use crate::{config::app::AppConfig, domain::tcp_conn_pool::FastTcpPool};
-use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+use crate::config::app::AppConfig;
+use crate::config::limits::OverflowAction;
+use crate::config::listener::{ListenerConfig, ListenerMode};
+use crate::config::rate_limit::RateLimitAction;
+use crate::config::retry::RetryConfig;
+use crate::config::router_map::RouteRequest;
+use crate::core::admin::{AdminApi, serve_admin};
+use crate::core::failover::{FailoverError, connect_with_failover, reject, reject_client};
+use crate::core::http_proxy::{HttpProxy, serve_http};
+use crate::core::reload::{ConfigReloader, reload_on_sighup};
+use crate::core::router::{BackendGroup, Router, SharedRouter};
+use crate::core::session_key::session_key;
+use crate::core::shutdown::{Shutdown, wait_for_signal};
+use crate::core::sni::peek_server_name;
+use crate::domain::request::{Request, TerminationReason};
+use crate::infrastructure::access_log::AccessLog;
+use crate::infrastructure::connection_limit::{ConnectionLimiter, ConnectionSlot};
+use crate::infrastructure::metrics::{ListenerMetrics, MetricsRegistry};
+use crate::infrastructure::rate_limit::{Admission, ClientConnection, RateLimiter};
+use crate::infrastructure::request_log::RequestLog;
+use crate::infrastructure::smart_tcp_pool::SmartTcpConnPool;
+use crate::infrastructure::tls::{ClientStream, TlsTerminator};
+
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
 
 pub async fn run_load_balancer(
+    config_path: &str,
     app_config: AppConfig,
-    pool: ConnectionPool,
+    router: Router,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let router = Arc::new(router);
+    let request_counter = Arc::new(AtomicU64::new(0));
+    let shutdown = Shutdown::new();
+    let mut requests = RequestLog::new(app_config.request_log_size);
+    if let Some(access_log) = &app_config.access_log {
+        requests.access_log(AccessLog::new(access_log)?);
+    }
+    let metrics = Arc::new(MetricsRegistry::new(Arc::new(requests)));
+
+    let mut listeners = JoinSet::new();
+    let mut listener_routers = Vec::new();
+    for listener_config in app_config.all_listeners() {
+        let listener = TcpListener::bind(&listener_config.address).await?;
+        info!(
+            "Load balancer listening on {} ({:?} mode)",
+            listener_config.address, listener_config.mode
+        );
+        let listener_router = match &listener_config.router_map {
+            Some(router_map) => Arc::new(router.with_map(router_map.clone())),
+            None => Arc::clone(&router),
+        };
+        let listener_router: SharedRouter = Arc::new(ArcSwap::new(listener_router));
+        listener_routers.push((
+            listener_config.address.clone(),
+            Arc::clone(&listener_router),
+        ));
+        let listener_metrics = metrics.listener(&listener_config.address);
+        listeners.spawn(serve_listener(
+            listener,
+            listener_config,
+            listener_router,
+            app_config.clone(),
+            Arc::clone(&request_counter),
+            listener_metrics,
+            shutdown.clone(),
+        ));
+    }
 
-    let listen_addr = &app_config.listen_addr.unwrap();
+    let reloader = Arc::new(ConfigReloader::new(
+        config_path,
+        app_config.clone(),
+        router,
+        listener_routers,
+    ));
+    tokio::spawn({
+        let reloader = Arc::clone(&reloader);
+        async move {
+            if let Err(e) = reload_on_sighup(reloader).await {
+                error!("Failed to install SIGHUP handler: {}", e);
+            }
+        }
+    });
+    if let Some(admin_addr) = &app_config.admin_addr {
+        let admin_listener = TcpListener::bind(admin_addr).await?;
+        info!("Admin API listening on {}", admin_addr);
+        let admin = Arc::new(AdminApi {
+            reloader: Arc::clone(&reloader),
+            metrics: Arc::clone(&metrics),
+        });
+        tokio::spawn(serve_admin(admin_listener, admin, shutdown.clone()));
+    }
 
-    let listener = TcpListener::bind(listen_addr).await?;
-    let pool = Arc::new(pool);
-    let request_counter = Arc::new(AtomicU64::new(0));
+    // An error still drains the other listeners before it is returned.
+    let failure = tokio::select! {
+        Some(result) = listeners.join_next() => listener_failure(result),
+        signal = wait_for_signal() => match signal {
+            Ok(signal) => {
+                info!("Received {}", signal);
+                None
+            }
+            Err(e) => Some(e),
+        },
+    };
+    match &failure {
+        Some(e) => error!(
+            "Shutting down on error, draining connections for up to {}s: {}",
+            app_config.drain_timeout_sec, e
+        ),
+        None => info!(
+            "Draining connections for up to {}s",
+            app_config.drain_timeout_sec
+        ),
+    }
+    let failure = stop_listeners(&mut listeners, &shutdown, failure).await;
+
+    let cut = shutdown
+        .drain(Duration::from_secs(app_config.drain_timeout_sec))
+        .await;
+    if cut > 0 {
+        warn!("Drain deadline reached, cutting {} connections", cut);
+    } else {
+        info!("All connections drained");
+    }
+    // Only now, so streams that drained connections handed back are closed too.
+    let closed = reloader.router().await.close_idle().await;
+    info!("Closed {} idle pooled connections", closed);
+    match failure {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+// Stops the listeners still running and waits for them, keeping the first error.
+async fn stop_listeners(
+    listeners: &mut JoinSet<io::Result<()>>,
+    shutdown: &Shutdown,
+    mut failure: Option<io::Error>,
+) -> Option<io::Error> {
+    shutdown.trigger();
+    while let Some(result) = listeners.join_next().await {
+        if let Some(e) = listener_failure(result) {
+            error!("Listener failed while shutting down: {}", e);
+            failure.get_or_insert(e);
+        }
+    }
+    failure
+}
 
-    info!("Load balancer listening on {}", listen_addr);
+fn listener_failure(result: Result<io::Result<()>, JoinError>) -> Option<io::Error> {
+    result
+        .map_err(io::Error::from)
+        .and_then(|served| served)
+        .err()
+}
+
+async fn serve_listener(
+    listener: TcpListener,
+    listener_config: ListenerConfig,
+    router: SharedRouter,
+    app_config: AppConfig,
+    request_counter: Arc<AtomicU64>,
+    metrics: Arc<ListenerMetrics>,
+    shutdown: Shutdown,
+) -> io::Result<()> {
+    let listen_addr = listener_config.address;
+    let rate_limiter = listener_config.rate_limit.map(|rate_limit| {
+        let rate_limiter = Arc::new(RateLimiter::new(rate_limit));
+        rate_limiter.start_sweeper();
+        rate_limiter
+    });
+    let http_proxy = Arc::new(HttpProxy {
+        router: Arc::clone(&router),
+        listener: listen_addr.clone(),
+        retry: app_config.retry.clone(),
+        request_timeout: Duration::from_secs(app_config.request_timout_sec),
+        request_counter: Arc::clone(&request_counter),
+        metrics: Arc::clone(&metrics),
+        rate_limiter: rate_limiter.clone(),
+        shutdown: shutdown.clone(),
+    });
+    let limiter = listener_config
+        .limits
+        .map(|limits| Arc::new(ConnectionLimiter::new(limits)));
+    let tls = match &listener_config.tls {
+        Some(tls) => Some(TlsTerminator::new(tls, listener_config.mode).map_err(io::Error::other)?),
+        None => None,
+    };
 
     loop {
-        let (mut incoming_stream, addr) = listener.accept().await?;
-        let pool = Arc::clone(&pool);
+        let (incoming_stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                // Usually out of file descriptors; the listener itself is still fine.
+                Err(e) => {
+                    warn!("Listener {} failed to accept: {}", listen_addr, e);
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            },
+            _ = shutdown.triggered() => {
+                info!("Listener {} stopped accepting connections", listen_addr);
+                return Ok(());
+            }
+        };
+        let connection = (shutdown.track(), metrics.track());
+        let limiters = (rate_limiter.clone(), limiter.clone());
+        let mode = listener_config.mode;
+        let tls = tls.clone();
+        if listener_config.mode == ListenerMode::Http {
+            let http_proxy = Arc::clone(&http_proxy);
+            let metrics = Arc::clone(&metrics);
+            tokio::spawn(async move {
+                let _connection = connection;
+                let Some((incoming_stream, _admitted)) =
+                    admit(limiters, incoming_stream, addr, mode).await
+                else {
+                    return;
+                };
+                let Some(incoming_stream) = terminate(tls, incoming_stream, addr, &metrics).await
+                else {
+                    return;
+                };
+                if let Err(e) = serve_http(http_proxy, incoming_stream, addr).await {
+                    error!("Error serving HTTP connection from {}: {}", addr, e);
+                }
+            });
+            continue;
+        }
+
         let request_id = request_counter.fetch_add(1, Ordering::Relaxed);
+        // Passthrough connections are routed once their ClientHello has arrived.
+        let group = if mode == ListenerMode::TlsPassthrough {
+            None
+        } else {
+            let route = RouteRequest {
+                listener: &listen_addr,
+                ..RouteRequest::default()
+            };
+            let Some(group) = router.load().route(&route) else {
+                warn!(
+                    "Request {}: no route for connection from {} on {}",
+                    request_id, addr, listen_addr
+                );
+                continue;
+            };
+            Some(group)
+        };
+        let router = Arc::clone(&router);
+        let listen_addr = listen_addr.clone();
+        let retry = app_config.retry.clone();
+        let timeout_sec = app_config.request_timout_sec;
+        let metrics = Arc::clone(&metrics);
 
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(pool, incoming_stream, request_id, app_config.request_timout_sec).await {
+            let _connection = connection;
+            let Some((incoming_stream, _admitted)) =
+                admit(limiters, incoming_stream, addr, mode).await
+            else {
+                return;
+            };
+            let group = match group {
+                Some(group) => group,
+                None => {
+                    let route =
+                        route_by_sni(&router, &listen_addr, &incoming_stream, addr, request_id);
+                    let Some(group) = route.await else {
+                        return;
+                    };
+                    group
+                }
+            };
+            let Some(incoming_stream) = terminate(tls, incoming_stream, addr, &metrics).await
+            else {
+                return;
+            };
+            if let Err(e) = handle_connection(
+                group,
+                incoming_stream,
+                addr,
+                request_id,
+                timeout_sec,
+                retry,
+                metrics,
+            )
+            .await
+            {
                 error!("Error handling connection: {}", e);
             }
         });
     }
 }
 
+// Per-client limits first, then a slot under the listener's limit; None once rejected.
+async fn admit(
+    (rate_limiter, limiter): (Option<Arc<RateLimiter>>, Option<Arc<ConnectionLimiter>>),
+    incoming_stream: TcpStream,
+    client_addr: SocketAddr,
+    mode: ListenerMode,
+) -> Option<(
+    TcpStream,
+    (Option<ClientConnection>, Option<ConnectionSlot>),
+)> {
+    let client = match rate_limiter {
+        Some(rate_limiter) => match throttle(&rate_limiter, client_addr, mode).await {
+            Some(client) => Some(client),
+            None => {
+                let overflow = match rate_limiter.config().action {
+                    RateLimitAction::Drop => OverflowAction::Reset,
+                    _ => OverflowAction::Reject,
+                };
+                reject(incoming_stream, &FailoverError::RateLimited, overflow).await;
+                return None;
+            }
+        },
+        None => None,
+    };
+    let Some(limiter) = limiter else {
+        return Some((incoming_stream, (client, None)));
+    };
+    match limiter.acquire().await {
+        Ok(slot) => Some((incoming_stream, (client, Some(slot)))),
+        Err(e) => {
+            warn!("Rejecting connection from {}: {}", client_addr, e);
+            let error = FailoverError::Overloaded(e);
+            reject(incoming_stream, &error, limiter.config().overflow).await;
+            None
+        }
+    }
+}
+
+// HTTP listeners rate limit each request instead; see `HttpProxy::throttle`.
+async fn throttle(
+    rate_limiter: &Arc<RateLimiter>,
+    client_addr: SocketAddr,
+    mode: ListenerMode,
+) -> Option<ClientConnection> {
+    let Some(client) = rate_limiter.connect(client_addr.ip()) else {
+        debug!(
+            "Rejecting {}: too many connections from its IP",
+            client_addr
+        );
+        return None;
+    };
+    if mode == ListenerMode::Http {
+        return Some(client);
+    }
+    match rate_limiter.check(&client_addr.ip().to_string()) {
+        Admission::Allow => Some(client),
+        Admission::Delay(wait) => {
+            tokio::time::sleep(wait).await;
+            Some(client)
+        }
+        Admission::Deny(_) => {
+            debug!("Rejecting {}: connection rate limit exceeded", client_addr);
+            None
+        }
+    }
+}
+
+// The ClientHello is only peeked at, so the backend receives the whole TLS session.
+async fn route_by_sni(
+    router: &SharedRouter,
+    listener: &str,
+    incoming_stream: &TcpStream,
+    client_addr: SocketAddr,
+    request_id: u64,
+) -> Option<Arc<BackendGroup>> {
+    let server_name = match peek_server_name(incoming_stream).await {
+        Ok(server_name) => server_name,
+        Err(e) => {
+            debug!("Request {}: dropping {}: {}", request_id, client_addr, e);
+            return None;
+        }
+    };
+    let route = RouteRequest {
+        listener,
+        host: server_name.as_deref(),
+        ..RouteRequest::default()
+    };
+    let group = router.load().route(&route);
+    if group.is_none() {
+        warn!(
+            "Request {}: no route for server name {} from {} on {}",
+            request_id,
+            server_name.as_deref().unwrap_or("(none)"),
+            client_addr,
+            listener
+        );
+    }
+    group
+}
+
+// Plaintext listeners pass the stream through; TLS ones return it once the handshake is done.
+async fn terminate(
+    tls: Option<TlsTerminator>,
+    incoming_stream: TcpStream,
+    client_addr: SocketAddr,
+    metrics: &ListenerMetrics,
+) -> Option<ClientStream> {
+    let Some(tls) = tls else {
+        return Some(ClientStream::Plain(incoming_stream));
+    };
+    match tls.accept(incoming_stream).await {
+        Ok(incoming_stream) => Some(incoming_stream),
+        Err(e) => {
+            debug!("TLS handshake with {} failed: {}", client_addr, e);
+            metrics
+                .tls_handshake_failures
+                .fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+}
+
 async fn handle_connection(
-    pool: Arc<ConnectionPool>,
-    mut incoming_stream: TcpStream,
+    group: Arc<BackendGroup>,
+    mut incoming_stream: ClientStream,
+    client_addr: SocketAddr,
     request_id: u64,
     timeout_ms: u64,
+    retry: RetryConfig,
+    listener_metrics: Arc<ListenerMetrics>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let start = std::time::Instant::now();
-
-    let mut backend = pool.get_connection(request_id).await.unwrap();
+    let start = Instant::now();
+    let pool = &group.pool;
+    let sticky = &group.sticky;
+
+    let key = session_key(
+        &pool.hash_key,
+        incoming_stream.peekable(),
+        client_addr,
+        request_id,
+    )
+    .await;
+    let user_id = SmartTcpConnPool::user_id(key);
+    let mut request = Request::new(request_id, group.name.clone(), user_id);
+    request.set_client(client_addr);
+    let preferred = sticky
+        .as_ref()
+        .and_then(|sticky| sticky.get_or_assign_backend(user_id));
+
+    let (backend_idx, mut backend, _active) =
+        match connect_with_failover(pool, key, preferred, &retry).await {
+            Ok((backend_idx, pooled, slot)) => (backend_idx, pooled.stream, slot),
+            Err(e) => {
+                reject_client(incoming_stream, &e, pool.overflow()).await;
+                request.finish(TerminationReason::NoBackend);
+                request.set_time_taken(start.elapsed().as_secs_f64());
+                listener_metrics.record(request);
+                return Err(e.into());
+            }
+        };
+    request.set_backend(pool.backends[backend_idx].address());
+    if let Some(sticky) = sticky
+        && preferred != Some(backend_idx)
+    {
+        sticky.pin(user_id, backend_idx);
+    }
+    let backend_metrics = &pool.metrics[backend_idx];
+    let session_start = Instant::now();
 
     match timeout(
         Duration::from_secs(timeout_ms),
@@ -61,14 +487,41 @@ async fn handle_connection(
                 received,
                 start.elapsed()
             );
+            listener_metrics
+                .bytes_received
+                .fetch_add(sent, Ordering::Relaxed);
+            listener_metrics
+                .bytes_sent
+                .fetch_add(received, Ordering::Relaxed);
+            backend_metrics
+                .bytes_sent
+                .fetch_add(sent, Ordering::Relaxed);
+            backend_metrics
+                .bytes_received
+                .fetch_add(received, Ordering::Relaxed);
+            pool.report_success(backend_idx);
+            request.set_bytes_received(sent as usize);
+            request.set_bytes(received as usize);
+            request.finish(TerminationReason::Done);
         }
         Ok(Err(e)) => {
             error!("Copy error {}: {}", request_id, e);
+            pool.report_failure(backend_idx);
+            request.finish(TerminationReason::IoError);
         }
         Err(_) => {
             error!("Timeout for {}", request_id);
+            backend_metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+            pool.report_failure(backend_idx);
+            request.finish(TerminationReason::Timeout);
         }
     }
+    request.set_time_taken(start.elapsed().as_secs_f64());
+    listener_metrics.record(request);
+    listener_metrics.session_duration.observe(start.elapsed());
+    backend_metrics
+        .session_duration
+        .observe(session_start.elapsed());
 
     Ok(())
 }
@@ -76,9 +529,323 @@ async fn handle_connection(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::limits::{ConnectionLimitConfig, OverflowAction};
+    use crate::config::router_map::{Route, RouterMap};
+    use crate::core::router::BackendGroup;
     use crate::domain::backend_conn::ConnString;
+    use crate::domain::request::Status;
+    use crate::domain::tcp_conn_pool::FastTcpPool;
+    use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+    use crate::infrastructure::tls::tests::{
+        certificate, connect, connector, echo_server, tls_config,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::net::TcpListener;
 
+    async fn greeting_backend(greeting: &'static [u8]) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.write_all(greeting).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn listeners_route_to_their_own_groups_test() {
+        let mut router = Router::new(RouterMap::new());
+        for (name, greeting) in [("a", b"from a"), ("b", b"from b")] {
+            let addr = greeting_backend(greeting).await;
+            let backends = vec![ConnString::new(addr.ip().to_string(), addr.port())];
+            router.add_group(BackendGroup::new(name, ConnectionPool::new(backends, 10)));
+        }
+        let mut app_config = AppConfig::new();
+        app_config.request_timeout(5);
+        let request_counter = Arc::new(AtomicU64::new(0));
+
+        let mut addrs = Vec::new();
+        for name in ["a", "b"] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let mut router_map = RouterMap::new();
+            router_map.default_route(name);
+            let listener_config = ListenerConfig::new(&addr.to_string(), ListenerMode::Tcp);
+            tokio::spawn(serve_listener(
+                listener,
+                listener_config,
+                Arc::new(ArcSwap::from_pointee(router.with_map(router_map))),
+                app_config.clone(),
+                Arc::clone(&request_counter),
+                Arc::new(ListenerMetrics::new(Arc::new(RequestLog::new(10)))),
+                Shutdown::new(),
+            ));
+            addrs.push(addr);
+        }
+
+        for (addr, expected) in addrs.iter().zip(["from a", "from b"]) {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            let mut greeting = String::new();
+            client.read_to_string(&mut greeting).await.unwrap();
+            assert_eq!(greeting, expected);
+        }
+        assert_eq!(request_counter.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_accepting_and_drains_test() {
+        let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = backend.accept().await {
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    let _ = stream.write_all(b"late").await;
+                });
+            }
+        });
+        let backends = vec![ConnString::new(
+            backend_addr.ip().to_string(),
+            backend_addr.port(),
+        )];
+        let mut router_map = RouterMap::new();
+        router_map.default_route("web");
+        let mut router = Router::new(router_map);
+        router.add_group(BackendGroup::new("web", ConnectionPool::new(backends, 10)));
+        let mut app_config = AppConfig::new();
+        app_config.request_timeout(5);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = Shutdown::new();
+        let requests = Arc::new(RequestLog::new(10));
+        let serving = tokio::spawn(serve_listener(
+            listener,
+            ListenerConfig::new(&addr.to_string(), ListenerMode::Tcp),
+            Arc::new(ArcSwap::from_pointee(router)),
+            app_config,
+            Arc::new(AtomicU64::new(0)),
+            Arc::new(ListenerMetrics::new(Arc::clone(&requests))),
+            shutdown.clone(),
+        ));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        while shutdown.active() == 0 {
+            tokio::task::yield_now().await;
+        }
+        shutdown.trigger();
+        serving.await.unwrap().unwrap();
+        assert!(TcpStream::connect(addr).await.is_err());
+
+        let reader = tokio::spawn(async move {
+            let mut received = String::new();
+            client.read_to_string(&mut received).await.unwrap();
+            received
+        });
+        assert_eq!(shutdown.drain(Duration::from_secs(5)).await, 0);
+        assert_eq!(reader.await.unwrap(), "late");
+
+        let recent = requests.recent(10, None);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].get_status(), &Status::Completed);
+        assert_eq!(recent[0].get_target(), "web");
+    }
+
+    #[tokio::test]
+    async fn listener_failure_stops_other_listeners_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = Shutdown::new();
+        let mut listeners = JoinSet::new();
+        listeners.spawn(serve_listener(
+            listener,
+            ListenerConfig::new(&addr.to_string(), ListenerMode::Tcp),
+            Arc::new(ArcSwap::from_pointee(Router::new(RouterMap::new()))),
+            AppConfig::new(),
+            Arc::new(AtomicU64::new(0)),
+            Arc::new(ListenerMetrics::new(Arc::new(RequestLog::new(10)))),
+            shutdown.clone(),
+        ));
+        listeners.spawn(async { Err(io::Error::other("second")) });
+
+        let failure = listener_failure(Ok(Err(io::Error::other("first"))));
+        let failure = stop_listeners(&mut listeners, &shutdown, failure).await;
+        assert_eq!(failure.unwrap().to_string(), "first");
+        assert!(listeners.is_empty());
+        assert!(TcpStream::connect(addr).await.is_err());
+
+        let mut listeners = JoinSet::new();
+        listeners.spawn(async { Err(io::Error::other("second")) });
+        let failure = stop_listeners(&mut listeners, &shutdown, None).await;
+        assert_eq!(failure.unwrap().to_string(), "second");
+    }
+
+    // A TCP listener in front of a backend that holds every connection open; the receiver
+    // yields the backend side of each proxied connection.
+    async fn holding_listener(
+        configure: impl FnOnce(&mut ListenerConfig),
+    ) -> (SocketAddr, tokio::sync::mpsc::UnboundedReceiver<TcpStream>) {
+        let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+        let (accepted, accepted_rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = backend.accept().await {
+                let _ = accepted.send(stream);
+            }
+        });
+        let backends = vec![ConnString::new(
+            backend_addr.ip().to_string(),
+            backend_addr.port(),
+        )];
+        let mut router_map = RouterMap::new();
+        router_map.default_route("web");
+        let mut router = Router::new(router_map);
+        router.add_group(BackendGroup::new("web", ConnectionPool::new(backends, 10)));
+        let mut app_config = AppConfig::new();
+        app_config.request_timeout(5);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut listener_config = ListenerConfig::new(&addr.to_string(), ListenerMode::Tcp);
+        configure(&mut listener_config);
+        tokio::spawn(serve_listener(
+            listener,
+            listener_config,
+            Arc::new(ArcSwap::from_pointee(router)),
+            app_config,
+            Arc::new(AtomicU64::new(0)),
+            Arc::new(ListenerMetrics::new(Arc::new(RequestLog::new(10)))),
+            Shutdown::new(),
+        ));
+        (addr, accepted_rx)
+    }
+
+    async fn assert_reset(addr: SocketAddr) {
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let mut received = Vec::new();
+        let error = client.read_to_end(&mut received).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+    }
+
+    #[tokio::test]
+    async fn listener_limit_resets_overflow_test() {
+        let (addr, mut accepted) = holding_listener(|listener| {
+            listener.limits = Some(ConnectionLimitConfig {
+                max_connections: 1,
+                queue_size: 0,
+                queue_timeout_ms: 100,
+                overflow: OverflowAction::Reset,
+            });
+        })
+        .await;
+
+        let _first = TcpStream::connect(addr).await.unwrap();
+        let _backend_side = accepted.recv().await.unwrap();
+        assert_reset(addr).await;
+    }
+
+    #[tokio::test]
+    async fn connections_per_ip_are_capped_test() {
+        let (addr, mut accepted) = holding_listener(|listener| {
+            listener.rate_limit =
+                Some(toml::from_str("max_connections_per_ip = 1\naction = \"drop\"").unwrap());
+        })
+        .await;
+
+        let first = TcpStream::connect(addr).await.unwrap();
+        let backend_side = accepted.recv().await.unwrap();
+        assert_reset(addr).await;
+
+        drop((first, backend_side));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let _third = TcpStream::connect(addr).await.unwrap();
+        assert!(accepted.recv().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn tls_listener_forwards_plaintext_test() {
+        let (cert, der) = certificate(&["localhost"], &[]);
+        let (addr, mut accepted) = holding_listener(|listener| {
+            listener.tls = Some(tls_config(vec![cert]));
+        })
+        .await;
+
+        let mut client = connect(&connector(&[&der], &[]), addr, "localhost")
+            .await
+            .unwrap();
+        client.write_all(b"hello").await.unwrap();
+        let mut backend_side = accepted.recv().await.unwrap();
+        let mut received = [0u8; 5];
+        backend_side.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"hello");
+        backend_side.write_all(b"world").await.unwrap();
+        drop(backend_side);
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, b"world");
+
+        // A plaintext client fails the handshake and never reaches the backend.
+        let mut plain = TcpStream::connect(addr).await.unwrap();
+        plain.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let _ = plain.read_to_end(&mut Vec::new()).await;
+        assert!(accepted.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn passthrough_routes_by_server_name_test() {
+        let mut router_map = RouterMap::new();
+        router_map.map_route(Route {
+            host: Some("*.a.example.com".to_string()),
+            group: "a".to_string(),
+            ..Route::default()
+        });
+        router_map.default_route("b");
+        let mut router = Router::new(router_map);
+        let mut trusted = Vec::new();
+        for name in ["a", "b"] {
+            let (cert, der) = certificate(&[&format!("www.{}.example.com", name)], &[]);
+            let terminator = TlsTerminator::new(&tls_config(vec![cert]), ListenerMode::Tcp);
+            let addr = echo_server(terminator.unwrap()).await;
+            let backends = vec![ConnString::new(addr.ip().to_string(), addr.port())];
+            router.add_group(BackendGroup::new(name, ConnectionPool::new(backends, 10)));
+            trusted.push(der);
+        }
+        let mut app_config = AppConfig::new();
+        app_config.request_timeout(5);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_listener(
+            listener,
+            ListenerConfig::new(&addr.to_string(), ListenerMode::TlsPassthrough),
+            Arc::new(ArcSwap::from_pointee(router)),
+            app_config,
+            Arc::new(AtomicU64::new(0)),
+            Arc::new(ListenerMetrics::new(Arc::new(RequestLog::new(10)))),
+            Shutdown::new(),
+        ));
+
+        // Each backend's own certificate verifies, so the session reached it untouched.
+        for (server_name, der) in [
+            ("www.a.example.com", &trusted[0]),
+            ("www.b.example.com", &trusted[1]),
+        ] {
+            let mut client = connect(&connector(&[der], &[]), addr, server_name)
+                .await
+                .unwrap();
+            let mut reply = Vec::new();
+            client.read_to_end(&mut reply).await.unwrap();
+            assert_eq!(reply, b"none");
+        }
+
+        let mut plain = TcpStream::connect(addr).await.unwrap();
+        plain.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        // Closed without a reply; the unread request may turn that into a reset.
+        let mut reply = Vec::new();
+        let _ = plain.read_to_end(&mut reply).await;
+        assert!(reply.is_empty());
+    }
+
     #[test]
     fn constructor_test() {
         let backends = vec![
@@ -89,7 +856,7 @@ mod tests {
 
         assert_eq!(pool.backends.len(), 2);
         assert_eq!(pool.max_pool_size, 10);
-        assert_eq!(pool.current.load(Ordering::Relaxed), 0);
+        assert_eq!(pool.strategy.name(), "round_robin");
         assert_eq!(pool.pools.len(), 2);
     }
 
@@ -126,9 +893,9 @@ mod tests {
         let pool = ConnectionPool::new(backends, 10);
 
         let stream = TcpStream::connect(addr).await.unwrap();
-        pool.return_connection(0, stream).await;
+        pool.return_connection(0, stream);
 
-        let pool_guard = pool.pools[0].lock().await;
+        let pool_guard = &pool.pools[0];
         assert_eq!(pool_guard.len(), 1);
     }
 
@@ -142,10 +909,10 @@ mod tests {
 
         for _ in 0..3 {
             let stream = TcpStream::connect(addr).await.unwrap();
-            pool.return_connection(0, stream).await;
+            pool.return_connection(0, stream);
         }
 
-        let pool_guard = pool.pools[0].lock().await;
+        let pool_guard = &pool.pools[0];
         assert_eq!(pool_guard.len(), 2);
     }
 
@@ -159,7 +926,7 @@ mod tests {
 
         let stream = TcpStream::connect(addr).await.unwrap();
         let local_addr = stream.local_addr().unwrap();
-        pool.return_connection(0, stream).await;
+        pool.return_connection(0, stream);
 
         let reused_stream = pool.get_connection(1).await.unwrap();
         assert_eq!(reused_stream.local_addr().unwrap(), local_addr);
@@ -176,7 +943,7 @@ mod tests {
         let conn = pool.get_connection(1).await.unwrap();
         assert_eq!(conn.peer_addr().unwrap(), addr);
 
-        let pool_guard = pool.pools[0].lock().await;
+        let pool_guard = &pool.pools[0];
         assert_eq!(pool_guard.len(), 0);
     }
 
@@ -1 +1,11 @@
+pub mod admin;
+pub mod failover;
+pub mod grpc;
+pub mod http_proxy;
 pub mod load_balancer;
+pub mod peek;
+pub mod reload;
+pub mod router;
+pub mod session_key;
+pub mod shutdown;
+pub mod sni;
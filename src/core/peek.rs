@@ -0,0 +1,111 @@
+use tokio::net::TcpStream;
+use tokio::time::{Duration, Instant, sleep};
+
+const PEEK_RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+// Peeks at the start of the stream without consuming it, until `complete` accepts the
+// bytes, `limit` bytes are buffered, the peer stops sending or `wait` elapses.
+pub async fn peek_until(
+    stream: &TcpStream,
+    limit: usize,
+    wait: Duration,
+    complete: impl Fn(&[u8]) -> bool,
+) -> Vec<u8> {
+    let deadline = Instant::now() + wait;
+    let mut buf = vec![0u8; limit];
+    let mut peeked = 0;
+
+    loop {
+        match tokio::time::timeout_at(deadline, stream.peek(&mut buf)).await {
+            Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+            Ok(Ok(n)) => peeked = n,
+        }
+        if peeked == limit || complete(&buf[..peeked]) || Instant::now() >= deadline {
+            break;
+        }
+        sleep(PEEK_RETRY_INTERVAL).await;
+    }
+
+    buf.truncate(peeked);
+    buf
+}
+
+pub fn find_headers_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|position| position + 4)
+}
+
+pub fn header_value<'a>(head: &'a [u8], name: &str) -> Option<&'a str> {
+    let head = std::str::from_utf8(head).ok()?;
+    head.split("\r\n")
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(header, _)| header.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+pub fn cookie_value<'a>(cookies: &'a str, name: &str) -> Option<&'a str> {
+    cookies
+        .split(';')
+        .filter_map(|cookie| cookie.split_once('='))
+        .find(|(cookie, _)| cookie.trim() == name)
+        .map(|(_, value)| value.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    const HEAD: &[u8] =
+        b"GET / HTTP/1.1\r\nHost: example.com\r\nX-User:  alice \r\nCookie: a=1; sid=xyz\r\n\r\nbody";
+
+    #[test]
+    fn header_value_test() {
+        assert_eq!(header_value(HEAD, "x-user"), Some("alice"));
+        assert_eq!(header_value(HEAD, "Host"), Some("example.com"));
+        assert_eq!(header_value(HEAD, "missing"), None);
+    }
+
+    #[test]
+    fn cookie_value_test() {
+        let cookies = header_value(HEAD, "cookie").unwrap();
+        assert_eq!(cookie_value(cookies, "sid"), Some("xyz"));
+        assert_eq!(cookie_value(cookies, "b"), None);
+    }
+
+    #[test]
+    fn find_headers_end_test() {
+        assert_eq!(find_headers_end(HEAD), Some(HEAD.len() - 4));
+        assert_eq!(find_headers_end(b"GET / HTTP/1.1\r\n"), None);
+    }
+
+    #[tokio::test]
+    async fn peek_until_does_not_consume_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (incoming, _) = listener.accept().await.unwrap();
+
+        client.write_all(&HEAD[..20]).await.unwrap();
+        let writer = tokio::spawn(async move {
+            sleep(Duration::from_millis(20)).await;
+            client.write_all(&HEAD[20..]).await.unwrap();
+            client
+        });
+
+        let peeked = peek_until(&incoming, 4096, Duration::from_secs(1), |buf| {
+            find_headers_end(buf).is_some()
+        })
+        .await;
+        assert!(find_headers_end(&peeked).is_some());
+
+        let again = peek_until(&incoming, 4096, Duration::from_millis(50), |_| true).await;
+        assert_eq!(&again[..20], &HEAD[..20]);
+        drop(writer.await.unwrap());
+    }
+}
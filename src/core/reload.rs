@@ -0,0 +1,267 @@
+use log::{error, info, warn};
+use std::sync::Arc;
+use tokio::io;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::Mutex;
+
+use crate::config::app::AppConfig;
+use crate::config::error::ConfigError;
+use crate::core::router::{ReloadSummary, Router, SharedRouter};
+
+pub struct ConfigReloader {
+    config_path: String,
+    live: Mutex<LiveConfig>,
+    listeners: Vec<(String, SharedRouter)>,
+}
+
+struct LiveConfig {
+    app_config: AppConfig,
+    router: Arc<Router>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config_path: &str,
+        app_config: AppConfig,
+        router: Arc<Router>,
+        listeners: Vec<(String, SharedRouter)>,
+    ) -> ConfigReloader {
+        ConfigReloader {
+            config_path: config_path.to_string(),
+            live: Mutex::new(LiveConfig { app_config, router }),
+            listeners,
+        }
+    }
+
+    pub async fn router(&self) -> Arc<Router> {
+        Arc::clone(&self.live.lock().await.router)
+    }
+
+    pub async fn reload(&self) -> Result<ReloadSummary, ConfigError> {
+        let app_config = AppConfig::from_file(&self.config_path)?;
+        let mut live = self.live.lock().await;
+        self.apply(&mut live, app_config).await
+    }
+
+    // Runtime changes edit the live config, so they are lost on the next reload from file.
+    pub async fn update(
+        &self,
+        change: impl FnOnce(&mut AppConfig) -> Result<(), ConfigError>,
+    ) -> Result<ReloadSummary, ConfigError> {
+        let mut live = self.live.lock().await;
+        let mut app_config = live.app_config.clone();
+        change(&mut app_config)?;
+        app_config.build()?;
+        self.apply(&mut live, app_config).await
+    }
+
+    // Builds the new routing next to the live one and swaps it in per listener; connections
+    // already holding a group keep using it until they finish.
+    async fn apply(
+        &self,
+        live: &mut LiveConfig,
+        app_config: AppConfig,
+    ) -> Result<ReloadSummary, ConfigError> {
+        let mut summary = ReloadSummary::default();
+        let next = Arc::new(live.router.reload(&app_config, &mut summary)?);
+
+        let listeners = app_config.all_listeners();
+        for (address, shared) in &self.listeners {
+            let listener = listeners
+                .iter()
+                .find(|listener| &listener.address == address);
+            if listener.is_none() {
+                warn!(
+                    "Listener {} was removed from the configuration, it keeps running until restart",
+                    address
+                );
+            }
+            let listener_router = match listener.and_then(|listener| listener.router_map.clone()) {
+                Some(router_map) => Arc::new(next.with_map(router_map)),
+                None => Arc::clone(&next),
+            };
+            shared.store(listener_router);
+        }
+        for listener in &listeners {
+            if !self
+                .listeners
+                .iter()
+                .any(|(address, _)| address == &listener.address)
+            {
+                warn!(
+                    "Listener {} is new, it starts accepting after a restart",
+                    listener.address
+                );
+            }
+        }
+
+        live.router.retire(&next, &mut summary).await;
+        live.router = next;
+        live.app_config = app_config;
+        summary.groups_removed.sort();
+        info!(
+            "Configuration reloaded: {} groups added, {} removed, {} backends added, {} removed",
+            summary.groups_added.len(),
+            summary.groups_removed.len(),
+            summary.backends_added.len(),
+            summary.backends_removed.len()
+        );
+        Ok(summary)
+    }
+}
+
+pub async fn reload_on_sighup(reloader: Arc<ConfigReloader>) -> io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        info!("Received SIGHUP, reloading {}", reloader.config_path);
+        if let Err(e) = reloader.reload().await {
+            error!("Reload failed, keeping the current configuration: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::router_map::RouteRequest;
+    use arc_swap::ArcSwap;
+
+    const CONFIG: &str = r#"
+listen_addr = "127.0.0.1:8080"
+
+[router_map]
+default_group = "web"
+
+[groups.web]
+backends = ["127.0.0.1:3000", "127.0.0.1:3001"]
+"#;
+
+    const RELOADED: &str = r#"
+listen_addr = "127.0.0.1:8080"
+
+[router_map]
+default_group = "web"
+
+[[router_map.routes]]
+path_prefix = "/api"
+group = "api"
+
+[groups.web]
+backends = ["127.0.0.1:3001", "127.0.0.1:3002"]
+
+[groups.api]
+backends = ["127.0.0.1:4000"]
+"#;
+
+    fn write_config(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn reloader(path: &str) -> (ConfigReloader, SharedRouter) {
+        let app_config = AppConfig::from_file(path).unwrap();
+        let router = Arc::new(Router::from_config(&app_config).unwrap());
+        let shared: SharedRouter = Arc::new(ArcSwap::new(Arc::clone(&router)));
+        let listeners = vec![("127.0.0.1:8080".to_string(), Arc::clone(&shared))];
+        (
+            ConfigReloader::new(path, app_config, router, listeners),
+            shared,
+        )
+    }
+
+    #[tokio::test]
+    async fn reload_swaps_routing_test() {
+        let path = write_config("reload-swap", CONFIG);
+        let (reloader, shared) = reloader(&path);
+        let web = shared.load().route(&RouteRequest::default()).unwrap();
+        let kept_uuid = web.pool.backends[1].get_uuid();
+
+        std::fs::write(&path, RELOADED).unwrap();
+        let summary = reloader.reload().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            summary,
+            ReloadSummary {
+                groups_added: vec!["api".to_string()],
+                groups_removed: vec![],
+                backends_added: vec!["127.0.0.1:4000".to_string(), "127.0.0.1:3002".to_string()],
+                backends_removed: vec!["127.0.0.1:3000".to_string()],
+            }
+        );
+        let api = RouteRequest {
+            path: Some("/api/users"),
+            ..RouteRequest::default()
+        };
+        assert_eq!(shared.load().route(&api).unwrap().name, "api");
+        let reloaded = shared.load().route(&RouteRequest::default()).unwrap();
+        assert_eq!(reloaded.pool.backends[0].get_uuid(), kept_uuid);
+        // Sessions holding the old group still see the full old backend list.
+        assert_eq!(web.pool.backends.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn update_applies_runtime_change_test() {
+        let path = write_config("reload-update", CONFIG);
+        let (reloader, shared) = reloader(&path);
+
+        let summary = reloader
+            .update(|app_config| {
+                let web = app_config.groups.get_mut("web").unwrap();
+                web.backends.retain(|backend| backend.get_port() != 3000);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(summary.backends_removed, vec!["127.0.0.1:3000".to_string()]);
+        let web = shared.load().route(&RouteRequest::default()).unwrap();
+        assert_eq!(web.pool.backends.len(), 1);
+
+        // Invalid changes are rejected and leave the live config alone.
+        let error = reloader
+            .update(|app_config| {
+                app_config.groups.get_mut("web").unwrap().backends.clear();
+                Ok(())
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidValue { .. }));
+        assert_eq!(
+            reloader
+                .router()
+                .await
+                .route(&RouteRequest::default())
+                .unwrap()
+                .pool
+                .backends
+                .len(),
+            1
+        );
+
+        // Reloading from file brings the file's backends back.
+        reloader.reload().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let web = shared.load().route(&RouteRequest::default()).unwrap();
+        assert_eq!(web.pool.backends.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reload_keeps_config_on_error_test() {
+        let path = write_config("reload-error", CONFIG);
+        let (reloader, shared) = reloader(&path);
+
+        std::fs::write(
+            &path,
+            CONFIG.replace("web\"\n\n[groups", "missing\"\n\n[groups"),
+        )
+        .unwrap();
+        let error = reloader.reload().await.unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(error, ConfigError::InvalidValue { .. }));
+        let web = shared.load().route(&RouteRequest::default()).unwrap();
+        assert_eq!(web.pool.backends.len(), 2);
+    }
+}
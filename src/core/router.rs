@@ -0,0 +1,298 @@
+use arc_swap::ArcSwap;
+use log::info;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::app::AppConfig;
+use crate::config::error::ConfigError;
+use crate::config::pool::PoolConfig;
+use crate::config::router_map::{RouteRequest, RouterMap};
+use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+use crate::infrastructure::health_check::HealthChecker;
+use crate::infrastructure::smart_tcp_pool::SmartTcpConnPool;
+use crate::infrastructure::tls::TlsOriginator;
+
+pub struct BackendGroup {
+    pub name: String,
+    pub pool: ConnectionPool,
+    pub sticky: Option<SmartTcpConnPool>,
+    health_checker: Option<HealthChecker>,
+}
+
+pub struct Router {
+    map: RouterMap,
+    groups: HashMap<String, Arc<BackendGroup>>,
+}
+
+pub type SharedRouter = Arc<ArcSwap<Router>>;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ReloadSummary {
+    pub groups_added: Vec<String>,
+    pub groups_removed: Vec<String>,
+    pub backends_added: Vec<String>,
+    pub backends_removed: Vec<String>,
+}
+
+impl BackendGroup {
+    pub fn new(name: &str, pool: ConnectionPool) -> BackendGroup {
+        BackendGroup {
+            name: name.to_string(),
+            pool,
+            sticky: None,
+            health_checker: None,
+        }
+    }
+
+    pub fn from_config(name: &str, config: &PoolConfig) -> Result<BackendGroup, ConfigError> {
+        let pool = ConnectionPool::new(config.backends.clone(), config.max_pool_size);
+        BackendGroup::configure(name, pool, config, None)
+    }
+
+    // Backends present in both configs keep their state; see `ConnectionPool::rebuild`.
+    pub fn reload(&self, config: &PoolConfig) -> Result<BackendGroup, ConfigError> {
+        let pool = self
+            .pool
+            .rebuild(config.backends.clone(), config.max_pool_size);
+        BackendGroup::configure(&self.name, pool, config, self.sticky.as_ref())
+    }
+
+    fn configure(
+        name: &str,
+        mut pool: ConnectionPool,
+        config: &PoolConfig,
+        previous_sticky: Option<&SmartTcpConnPool>,
+    ) -> Result<BackendGroup, ConfigError> {
+        pool.strategy(config.strategy, &config.hashing);
+        pool.protocol(config.protocol);
+        pool.keep_alive(config.keep_alive.clone());
+        if let Some(limits) = config.limits.clone() {
+            pool.limits(limits);
+        }
+        if let Some(outlier_detection) = config.outlier_detection.clone() {
+            pool.outlier_detection(outlier_detection);
+        }
+        // Certificates are read again on every reload, so rotated files get picked up.
+        if let Some(tls) = &config.tls {
+            pool.tls(TlsOriginator::new(tls)?);
+        }
+        info!(
+            "Group {}: balancing {} backends with {} strategy",
+            name,
+            pool.backends.len(),
+            pool.strategy.name()
+        );
+
+        let health_checker = config
+            .health_check
+            .clone()
+            .map(|health_check| HealthChecker::start(pool.clone(), health_check));
+        let sticky = config.sticky.clone().map(|sticky| match previous_sticky {
+            Some(previous) => previous.with_pool(pool.clone(), sticky),
+            None => {
+                let sticky = SmartTcpConnPool::new(pool.clone(), sticky);
+                sticky.start_sweeper();
+                sticky
+            }
+        });
+
+        let mut group = BackendGroup::new(name, pool);
+        group.sticky = sticky;
+        group.health_checker = health_checker;
+        Ok(group)
+    }
+
+    // Called once a replacement is live: sessions already on this group keep flowing, but
+    // its probes stop and backends that are gone lose their idle streams.
+    async fn retire(&self, replacement: Option<&BackendGroup>, summary: &mut ReloadSummary) {
+        if let Some(health_checker) = &self.health_checker {
+            health_checker.stop();
+        }
+        for (backend_idx, backend) in self.pool.backends.iter().enumerate() {
+            let address = backend.address();
+            if replacement.is_some_and(|group| group.pool.position(&address).is_some()) {
+                continue;
+            }
+            self.pool.clear_idle(backend_idx);
+            info!(
+                "Group {}: draining backend {} ({} active connections)",
+                self.name,
+                address,
+                self.pool.status[backend_idx].active()
+            );
+            summary.backends_removed.push(address);
+        }
+    }
+}
+
+impl Router {
+    pub fn new(map: RouterMap) -> Router {
+        Router {
+            map,
+            groups: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(app_config: &AppConfig) -> Result<Router, ConfigError> {
+        let mut router = Router::new(app_config.router_map.clone().unwrap_or_default());
+        for (name, config) in &app_config.groups {
+            router.add_group(BackendGroup::from_config(name, config)?);
+        }
+        Ok(router)
+    }
+
+    // Listeners with their own routing table still share the same backend groups.
+    pub fn with_map(&self, map: RouterMap) -> Router {
+        Router {
+            map,
+            groups: self.groups.clone(),
+        }
+    }
+
+    pub fn add_group(&mut self, group: BackendGroup) {
+        self.groups.insert(group.name.clone(), Arc::new(group));
+    }
+
+    pub fn reload(
+        &self,
+        app_config: &AppConfig,
+        summary: &mut ReloadSummary,
+    ) -> Result<Router, ConfigError> {
+        let mut router = Router::new(app_config.router_map.clone().unwrap_or_default());
+        for (name, config) in &app_config.groups {
+            let group = match self.groups.get(name) {
+                Some(group) => group.reload(config)?,
+                None => {
+                    summary.groups_added.push(name.clone());
+                    BackendGroup::from_config(name, config)?
+                }
+            };
+            for backend in group.pool.backends.iter() {
+                let known = self
+                    .groups
+                    .get(name)
+                    .is_some_and(|previous| previous.pool.position(&backend.address()).is_some());
+                if !known {
+                    summary.backends_added.push(backend.address());
+                }
+            }
+            router.add_group(group);
+        }
+        Ok(router)
+    }
+
+    pub async fn retire(&self, replacement: &Router, summary: &mut ReloadSummary) {
+        for (name, group) in &self.groups {
+            let next = replacement.groups.get(name);
+            if next.is_none() {
+                summary.groups_removed.push(name.clone());
+            }
+            group.retire(next.map(Arc::as_ref), summary).await;
+        }
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Arc<BackendGroup>> {
+        self.groups.get(name)
+    }
+
+    pub fn groups(&self) -> Vec<&Arc<BackendGroup>> {
+        let mut groups: Vec<_> = self.groups.values().collect();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        groups
+    }
+
+    pub async fn close_idle(&self) -> usize {
+        let mut closed = 0;
+        for group in self.groups.values() {
+            for backend_idx in 0..group.pool.backends.len() {
+                closed += group.pool.clear_idle(backend_idx);
+            }
+        }
+        closed
+    }
+
+    pub fn route(&self, request: &RouteRequest<'_>) -> Option<Arc<BackendGroup>> {
+        self.map
+            .resolve(request)
+            .and_then(|name| self.groups.get(name))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::router_map::Route;
+    use crate::domain::backend_conn::ConnString;
+
+    fn group(name: &str) -> BackendGroup {
+        let backends = vec![ConnString::new("127.0.0.1".to_string(), 3000)];
+        BackendGroup::new(name, ConnectionPool::new(backends, 10))
+    }
+
+    #[test]
+    fn route_to_group_test() {
+        let mut map = RouterMap::new();
+        map.map_route(Route {
+            path_prefix: Some("/api".to_string()),
+            group: "api".to_string(),
+            ..Route::default()
+        });
+        map.default_route("web");
+        let mut router = Router::new(map);
+        router.add_group(group("api"));
+        router.add_group(group("web"));
+
+        let api = RouteRequest {
+            path: Some("/api/users"),
+            ..RouteRequest::default()
+        };
+        assert_eq!(router.route(&api).unwrap().name, "api");
+        assert_eq!(router.route(&RouteRequest::default()).unwrap().name, "web");
+    }
+
+    #[test]
+    fn with_map_shares_groups_test() {
+        let mut router = Router::new(RouterMap::new());
+        router.add_group(group("web"));
+        let mut map = RouterMap::new();
+        map.default_route("web");
+        let listener_router = router.with_map(map);
+
+        let group = listener_router.route(&RouteRequest::default()).unwrap();
+        assert!(Arc::ptr_eq(&group, &router.groups["web"]));
+        assert!(router.route(&RouteRequest::default()).is_none());
+    }
+
+    #[tokio::test]
+    async fn close_idle_test() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let backends = vec![ConnString::new(addr.ip().to_string(), addr.port())];
+        let mut router = Router::new(RouterMap::new());
+        router.add_group(BackendGroup::new("web", ConnectionPool::new(backends, 10)));
+
+        let pool = &router.groups["web"].pool;
+        for _ in 0..2 {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            pool.return_connection(0, stream);
+        }
+        assert_eq!(router.close_idle().await, 2);
+        assert_eq!(router.close_idle().await, 0);
+    }
+
+    #[test]
+    fn route_without_match_test() {
+        let mut map = RouterMap::new();
+        map.map_route(Route {
+            host: Some("api.example.com".to_string()),
+            group: "api".to_string(),
+            ..Route::default()
+        });
+        let mut router = Router::new(map);
+        router.add_group(group("api"));
+
+        assert!(router.route(&RouteRequest::default()).is_none());
+    }
+}
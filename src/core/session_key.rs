@@ -0,0 +1,155 @@
+use hyper::HeaderMap;
+use hyper::header::COOKIE;
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio::time::Duration;
+
+use crate::config::hashing::HashKey;
+use crate::core::peek::{cookie_value, find_headers_end, header_value, peek_until};
+use crate::infrastructure::consistent_hash::hash_bytes;
+
+const MAX_HEAD_SIZE: usize = 8192;
+const HEAD_WAIT: Duration = Duration::from_millis(200);
+
+// Header and cookie keys fall back to the client IP when the value is missing, or when
+// there is no plaintext stream to peek at.
+pub async fn session_key(
+    hash_key: &HashKey,
+    stream: Option<&TcpStream>,
+    client_addr: SocketAddr,
+    request_id: u64,
+) -> u64 {
+    let client_ip_key = || hash_bytes(client_addr.ip().to_string().as_bytes());
+    let name = match hash_key {
+        HashKey::SessionId => return request_id,
+        HashKey::ClientIp => return client_ip_key(),
+        HashKey::Header(name) | HashKey::Cookie(name) => name,
+    };
+    let Some(stream) = stream else {
+        return client_ip_key();
+    };
+
+    let head = peek_until(stream, MAX_HEAD_SIZE, HEAD_WAIT, |buf| {
+        find_headers_end(buf).is_some()
+    })
+    .await;
+    let value = match hash_key {
+        HashKey::Cookie(_) => {
+            header_value(&head, "cookie").and_then(|cookies| cookie_value(cookies, name))
+        }
+        _ => header_value(&head, name),
+    };
+    value
+        .map(|value| hash_bytes(value.as_bytes()))
+        .unwrap_or_else(client_ip_key)
+}
+
+pub fn request_key(
+    hash_key: &HashKey,
+    headers: &HeaderMap,
+    client_addr: SocketAddr,
+    request_id: u64,
+) -> u64 {
+    let value = match hash_key {
+        HashKey::SessionId => return request_id,
+        HashKey::ClientIp => None,
+        HashKey::Header(name) => headers.get(name).and_then(|value| value.to_str().ok()),
+        HashKey::Cookie(name) => headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|cookies| cookie_value(cookies, name)),
+    };
+    match value {
+        Some(value) => hash_bytes(value.as_bytes()),
+        None => hash_bytes(client_addr.ip().to_string().as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    async fn connected_pair(request: &[u8]) -> (TcpStream, TcpStream, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        client.write_all(request).await.unwrap();
+        let (incoming, addr) = listener.accept().await.unwrap();
+        (client, incoming, addr)
+    }
+
+    #[tokio::test]
+    async fn session_id_key_test() {
+        let (_client, incoming, addr) = connected_pair(b"").await;
+        assert_eq!(
+            session_key(&HashKey::SessionId, Some(&incoming), addr, 42).await,
+            42
+        );
+    }
+
+    #[tokio::test]
+    async fn client_ip_key_test() {
+        let (_client, incoming, addr) = connected_pair(b"").await;
+        let first = session_key(&HashKey::ClientIp, Some(&incoming), addr, 1).await;
+        let second = session_key(&HashKey::ClientIp, Some(&incoming), addr, 2).await;
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn header_and_cookie_key_test() {
+        let request = b"GET / HTTP/1.1\r\nX-User: alice\r\nCookie: sid=abc\r\n\r\n";
+        let (_client, incoming, addr) = connected_pair(request).await;
+
+        let header = HashKey::Header("x-user".to_string());
+        assert_eq!(
+            session_key(&header, Some(&incoming), addr, 1).await,
+            hash_bytes(b"alice")
+        );
+        let cookie = HashKey::Cookie("sid".to_string());
+        assert_eq!(
+            session_key(&cookie, Some(&incoming), addr, 1).await,
+            hash_bytes(b"abc")
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_header_falls_back_to_client_ip_test() {
+        let (_client, incoming, addr) = connected_pair(b"GET / HTTP/1.1\r\n\r\n").await;
+        let header = HashKey::Header("x-user".to_string());
+        assert_eq!(
+            session_key(&header, Some(&incoming), addr, 1).await,
+            session_key(&HashKey::ClientIp, Some(&incoming), addr, 1).await
+        );
+        // Terminated TLS streams can't be peeked at.
+        assert_eq!(
+            session_key(&header, None, addr, 1).await,
+            session_key(&HashKey::ClientIp, None, addr, 1).await
+        );
+    }
+
+    #[test]
+    fn request_key_test() {
+        let addr: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-user", "alice".parse().unwrap());
+        headers.append(COOKIE, "a=1".parse().unwrap());
+        headers.append(COOKIE, "sid=abc".parse().unwrap());
+
+        let header = HashKey::Header("X-User".to_string());
+        assert_eq!(
+            request_key(&header, &headers, addr, 1),
+            hash_bytes(b"alice")
+        );
+        let cookie = HashKey::Cookie("sid".to_string());
+        assert_eq!(request_key(&cookie, &headers, addr, 1), hash_bytes(b"abc"));
+        let missing = HashKey::Cookie("other".to_string());
+        assert_eq!(
+            request_key(&missing, &headers, addr, 1),
+            request_key(&HashKey::ClientIp, &headers, addr, 7)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::{Notify, watch};
+use tokio::time::{Duration, Instant, timeout_at};
+
+#[derive(Clone)]
+pub struct Shutdown {
+    state: Arc<ShutdownState>,
+}
+
+struct ShutdownState {
+    triggered: watch::Sender<bool>,
+    active: AtomicUsize,
+    drained: Notify,
+}
+
+pub struct ConnectionGuard {
+    state: Arc<ShutdownState>,
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown {
+            state: Arc::new(ShutdownState {
+                triggered: watch::Sender::new(false),
+                active: AtomicUsize::new(0),
+                drained: Notify::new(),
+            }),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.state.triggered.send_replace(true);
+    }
+
+    pub async fn triggered(&self) {
+        let mut receiver = self.state.triggered.subscribe();
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+
+    pub fn track(&self) -> ConnectionGuard {
+        self.state.active.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard {
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        self.state.active.load(Ordering::Relaxed)
+    }
+
+    // Waits for tracked connections to finish and returns how many were still open at the deadline.
+    pub async fn drain(&self, deadline: Duration) -> usize {
+        let deadline = Instant::now() + deadline;
+        loop {
+            let drained = self.state.drained.notified();
+            tokio::pin!(drained);
+            drained.as_mut().enable();
+            if self.active() == 0 {
+                return 0;
+            }
+            if timeout_at(deadline, drained).await.is_err() {
+                return self.active();
+            }
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.state.active.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.state.drained.notify_waiters();
+        }
+    }
+}
+
+pub async fn wait_for_signal() -> io::Result<&'static str> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = terminate.recv() => Ok("SIGTERM"),
+        _ = interrupt.recv() => Ok("SIGINT"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn triggered_test() {
+        let shutdown = Shutdown::new();
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.triggered().await }
+        });
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        // Late subscribers see the shutdown as well.
+        shutdown.triggered().await;
+    }
+
+    #[tokio::test]
+    async fn drain_waits_for_connections_test() {
+        let shutdown = Shutdown::new();
+        let first = shutdown.track();
+        let second = shutdown.track();
+        assert_eq!(shutdown.active(), 2);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(first);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(second);
+        });
+        assert_eq!(shutdown.drain(Duration::from_secs(5)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn drain_reports_connections_past_deadline_test() {
+        let shutdown = Shutdown::new();
+        let _stuck = shutdown.track();
+        let finished = shutdown.track();
+        drop(finished);
+
+        assert_eq!(shutdown.drain(Duration::from_millis(50)).await, 1);
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt;
+use tokio::net::TcpStream;
+use tokio::time::Duration;
+
+use crate::core::peek::peek_until;
+
+// Large post-quantum key shares still fit; anything bigger is not worth waiting for.
+const MAX_HELLO_SIZE: usize = 16 * 1024;
+const HELLO_WAIT: Duration = Duration::from_secs(5);
+
+const CONTENT_HANDSHAKE: u8 = 22;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const EXTENSION_SERVER_NAME: u16 = 0;
+const NAME_TYPE_HOST_NAME: u8 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelloError {
+    Incomplete,
+    Invalid,
+}
+
+impl fmt::Display for HelloError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HelloError::Incomplete => write!(f, "incomplete TLS ClientHello"),
+            HelloError::Invalid => write!(f, "not a TLS ClientHello"),
+        }
+    }
+}
+
+// Waits for the ClientHello without consuming it, so it can be forwarded untouched.
+// Ok(None) means the client sent no SNI.
+pub async fn peek_server_name(stream: &TcpStream) -> Result<Option<String>, HelloError> {
+    let hello = peek_until(stream, MAX_HELLO_SIZE, HELLO_WAIT, |buf| {
+        server_name(buf) != Err(HelloError::Incomplete)
+    })
+    .await;
+    server_name(&hello)
+}
+
+pub fn server_name(buf: &[u8]) -> Result<Option<String>, HelloError> {
+    let message = client_hello(buf)?;
+    parse_server_name(&message).ok_or(HelloError::Invalid)
+}
+
+// Reassembles the ClientHello, which may be split over several handshake records.
+fn client_hello(buf: &[u8]) -> Result<Vec<u8>, HelloError> {
+    let mut message = Vec::new();
+    let mut records = buf;
+    loop {
+        let Some(header) = records.get(..5) else {
+            return Err(HelloError::Incomplete);
+        };
+        if header[0] != CONTENT_HANDSHAKE || header[1] != 3 {
+            return Err(HelloError::Invalid);
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let Some(fragment) = records.get(5..5 + len) else {
+            return Err(HelloError::Incomplete);
+        };
+        message.extend_from_slice(fragment);
+        records = &records[5 + len..];
+
+        if message.len() >= 4 {
+            if message[0] != HANDSHAKE_CLIENT_HELLO {
+                return Err(HelloError::Invalid);
+            }
+            let len = u32::from_be_bytes([0, message[1], message[2], message[3]]) as usize;
+            if message.len() >= 4 + len {
+                message.truncate(4 + len);
+                return Ok(message);
+            }
+        }
+    }
+}
+
+fn parse_server_name(message: &[u8]) -> Option<Option<String>> {
+    let mut hello = Reader(&message[4..]);
+    hello.take(2 + 32)?;
+    hello.vec8()?;
+    hello.vec16()?;
+    hello.vec8()?;
+    if hello.0.is_empty() {
+        return Some(None);
+    }
+    let mut extensions = Reader(hello.vec16()?);
+    while !extensions.0.is_empty() {
+        let kind = extensions.u16()?;
+        let data = extensions.vec16()?;
+        if kind != EXTENSION_SERVER_NAME {
+            continue;
+        }
+        let mut names = Reader(Reader(data).vec16()?);
+        while !names.0.is_empty() {
+            let name_type = names.take(1)?[0];
+            let name = names.vec16()?;
+            if name_type == NAME_TYPE_HOST_NAME {
+                let name = std::str::from_utf8(name).ok()?;
+                return Some(Some(name.to_ascii_lowercase()));
+            }
+        }
+        return Some(None);
+    }
+    Some(None)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.take(1)?[0] as usize;
+        self.take(len)
+    }
+
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::crypto::ring::default_provider;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore};
+    use std::sync::Arc;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    fn hello(server_name: &str) -> Vec<u8> {
+        let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        let mut connection = ClientConnection::new(
+            Arc::new(config),
+            server_name.to_string().try_into().unwrap(),
+        )
+        .unwrap();
+        let mut hello = Vec::new();
+        connection.write_tls(&mut hello).unwrap();
+        hello
+    }
+
+    #[test]
+    fn server_name_test() {
+        let api = hello("API.example.com");
+        assert_eq!(server_name(&api), Ok(Some("api.example.com".to_string())));
+        assert_eq!(
+            server_name(&api[..api.len() - 1]),
+            Err(HelloError::Incomplete)
+        );
+        // IP addresses are never sent as SNI.
+        assert_eq!(server_name(&hello("127.0.0.1")), Ok(None));
+        assert_eq!(
+            server_name(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"),
+            Err(HelloError::Invalid)
+        );
+    }
+
+    #[test]
+    fn reassembles_fragmented_hello_test() {
+        let hello = hello("www.example.com");
+        let (header, message) = hello.split_at(5);
+        let mut fragmented = Vec::new();
+        for fragment in message.chunks(100) {
+            fragmented.extend_from_slice(&header[..3]);
+            fragmented.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            fragmented.extend_from_slice(fragment);
+        }
+        assert_eq!(
+            server_name(&fragmented),
+            Ok(Some("www.example.com".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn peek_leaves_hello_unread_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let hello = hello("db.internal");
+        let (first, second) = hello.split_at(10);
+        client.write_all(first).await.unwrap();
+        tokio::spawn({
+            let second = second.to_vec();
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                client.write_all(&second).await.unwrap();
+                client
+            }
+        });
+
+        assert_eq!(
+            peek_server_name(&stream).await,
+            Ok(Some("db.internal".to_string()))
+        );
+        let mut buf = vec![0u8; hello.len()];
+        assert_eq!(stream.peek(&mut buf).await.unwrap(), hello.len());
+        assert_eq!(buf, hello);
+    }
+}
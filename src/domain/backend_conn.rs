@@ -1,10 +1,20 @@
+use serde::Deserialize;
 use uuid::Uuid;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "BackendEntry")]
 pub struct ConnString {
     uuid: Uuid,
     host: String,
     port: u16,
+    weight: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackendEntry {
+    Address(String),
+    Weighted { address: String, weight: u32 },
 }
 
 impl ConnString {
@@ -13,9 +23,15 @@ impl ConnString {
             uuid: Uuid::new_v4(),
             host,
             port,
+            weight: 1,
         }
     }
 
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+
     pub fn new_from_address(address: &str) -> Result<Self, String> {
         let parts: Vec<&str> = address.split(':').collect();
         if parts.len() != 2 {
@@ -43,6 +59,31 @@ impl ConnString {
     pub fn get_uuid(&self) -> Uuid {
         self.uuid
     }
+
+    pub fn get_weight(&self) -> u32 {
+        self.weight
+    }
+}
+
+impl TryFrom<String> for ConnString {
+    type Error = String;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        ConnString::new_from_address(&address)
+    }
+}
+
+impl TryFrom<BackendEntry> for ConnString {
+    type Error = String;
+
+    fn try_from(entry: BackendEntry) -> Result<Self, Self::Error> {
+        match entry {
+            BackendEntry::Address(address) => ConnString::try_from(address),
+            BackendEntry::Weighted { address, weight } => {
+                Ok(ConnString::new_from_address(&address)?.with_weight(weight))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -53,6 +94,7 @@ mod tests {
     fn constructor_test() {
         let result = ConnString::new("ip".to_string(), 80);
         assert_eq!(result.address(), "ip:80");
+        assert_eq!(result.get_weight(), 1);
     }
 
     #[test]
@@ -61,4 +103,21 @@ mod tests {
         let result = ConnString::new_from_address(conn_string).expect("Test faild");
         assert_eq!(result.address(), conn_string);
     }
+
+    #[test]
+    fn try_from_weighted_entry_test() {
+        let entry = BackendEntry::Weighted {
+            address: "ip:80".to_string(),
+            weight: 3,
+        };
+        let result = ConnString::try_from(entry).expect("Test faild");
+        assert_eq!(result.address(), "ip:80");
+        assert_eq!(result.get_weight(), 3);
+    }
+
+    #[test]
+    fn try_from_invalid_string_test() {
+        let result = ConnString::try_from("ip".to_string());
+        assert!(result.is_err());
+    }
 }
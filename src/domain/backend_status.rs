@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthState {
+    Healthy,
+    Unhealthy,
+}
+
+// Set by operators: draining backends only serve clients already pinned to them,
+// backends in maintenance serve nobody.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminState {
+    #[default]
+    Active,
+    Draining,
+    Maintenance,
+}
+
+#[derive(Debug)]
+pub struct BackendStatus {
+    healthy: AtomicBool,
+    admin_state: AtomicU8,
+    consecutive_successes: AtomicU32,
+    consecutive_failures: AtomicU32,
+    consecutive_errors: AtomicU32,
+    ejections: AtomicU32,
+    ejected_until_ms: AtomicU64,
+    active: AtomicUsize,
+    created_at: Instant,
+}
+
+// Keeps a backend's active connection count raised for as long as it is alive.
+#[derive(Debug)]
+pub struct ActiveConnection {
+    status: Arc<BackendStatus>,
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.status.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl BackendStatus {
+    pub fn new() -> BackendStatus {
+        BackendStatus {
+            healthy: AtomicBool::new(true),
+            admin_state: AtomicU8::new(AdminState::Active as u8),
+            consecutive_successes: AtomicU32::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            consecutive_errors: AtomicU32::new(0),
+            ejections: AtomicU32::new(0),
+            ejected_until_ms: AtomicU64::new(0),
+            active: AtomicUsize::new(0),
+            created_at: Instant::now(),
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Acquire)
+    }
+
+    pub fn admin_state(&self) -> AdminState {
+        match self.admin_state.load(Ordering::Acquire) {
+            1 => AdminState::Draining,
+            2 => AdminState::Maintenance,
+            _ => AdminState::Active,
+        }
+    }
+
+    pub fn set_admin_state(&self, state: AdminState) {
+        self.admin_state.store(state as u8, Ordering::Release);
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    pub fn track(self: &Arc<Self>) -> ActiveConnection {
+        self.active.fetch_add(1, Ordering::AcqRel);
+        ActiveConnection {
+            status: Arc::clone(self),
+        }
+    }
+
+    // Like `track`, but only while fewer than `limit` connections are active.
+    pub fn try_track(self: &Arc<Self>, limit: usize) -> Option<ActiveConnection> {
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < limit).then_some(active + 1)
+            })
+            .ok()?;
+        Some(ActiveConnection {
+            status: Arc::clone(self),
+        })
+    }
+
+    pub fn is_ejected(&self) -> bool {
+        self.elapsed_ms() < self.ejected_until_ms.load(Ordering::Acquire)
+    }
+
+    pub fn ejections(&self) -> u32 {
+        self.ejections.load(Ordering::Relaxed)
+    }
+
+    // Milliseconds since the last ejection ended, None if never ejected.
+    pub fn since_last_ejection_ms(&self) -> Option<u64> {
+        let until = self.ejected_until_ms.load(Ordering::Acquire);
+        (until > 0).then(|| self.elapsed_ms().saturating_sub(until))
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_errors.store(0, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) -> u32 {
+        self.consecutive_errors.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn eject(&self, duration: Duration, reset_ejections: bool) {
+        if reset_ejections {
+            self.ejections.store(0, Ordering::Relaxed);
+        }
+        self.ejections.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_errors.store(0, Ordering::Relaxed);
+        let until = self.elapsed_ms() + duration.as_millis() as u64;
+        self.ejected_until_ms.store(until, Ordering::Release);
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        // Offset by one so that a zero deadline always means "never ejected".
+        self.created_at.elapsed().as_millis() as u64 + 1
+    }
+
+    // Returns the new state only when this probe flipped it.
+    pub fn record_probe(&self, success: bool, rise: u32, fall: u32) -> Option<HealthState> {
+        if success {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            let successes = self.consecutive_successes.fetch_add(1, Ordering::Relaxed) + 1;
+            if successes >= rise && !self.healthy.swap(true, Ordering::AcqRel) {
+                return Some(HealthState::Healthy);
+            }
+        } else {
+            self.consecutive_successes.store(0, Ordering::Relaxed);
+            let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+            if failures >= fall && self.healthy.swap(false, Ordering::AcqRel) {
+                return Some(HealthState::Unhealthy);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constructor_test() {
+        let status = BackendStatus::new();
+        assert!(status.is_healthy());
+    }
+
+    #[test]
+    fn admin_state_test() {
+        let status = BackendStatus::new();
+        assert_eq!(status.admin_state(), AdminState::Active);
+        status.set_admin_state(AdminState::Maintenance);
+        assert_eq!(status.admin_state(), AdminState::Maintenance);
+        status.set_admin_state(AdminState::Draining);
+        assert_eq!(status.admin_state(), AdminState::Draining);
+    }
+
+    #[test]
+    fn fall_threshold_test() {
+        let status = BackendStatus::new();
+        assert_eq!(status.record_probe(false, 2, 3), None);
+        assert_eq!(status.record_probe(false, 2, 3), None);
+        assert_eq!(
+            status.record_probe(false, 2, 3),
+            Some(HealthState::Unhealthy)
+        );
+        assert_eq!(status.record_probe(false, 2, 3), None);
+        assert!(!status.is_healthy());
+    }
+
+    #[test]
+    fn rise_threshold_test() {
+        let status = BackendStatus::new();
+        status.record_probe(false, 2, 1);
+        assert_eq!(status.record_probe(true, 2, 1), None);
+        assert!(!status.is_healthy());
+        assert_eq!(status.record_probe(true, 2, 1), Some(HealthState::Healthy));
+        assert!(status.is_healthy());
+    }
+
+    #[test]
+    fn track_active_connections_test() {
+        let status = Arc::new(BackendStatus::new());
+        let first = status.track();
+        let second = status.track();
+        assert_eq!(status.active(), 2);
+
+        drop(first);
+        assert_eq!(status.active(), 1);
+        drop(second);
+        assert_eq!(status.active(), 0);
+    }
+
+    #[test]
+    fn try_track_respects_limit_test() {
+        let status = Arc::new(BackendStatus::new());
+        let first = status.try_track(1).unwrap();
+        assert!(status.try_track(1).is_none());
+        drop(first);
+        assert!(status.try_track(1).is_some());
+    }
+
+    #[test]
+    fn eject_test() {
+        let status = BackendStatus::new();
+        assert!(!status.is_ejected());
+        assert_eq!(status.since_last_ejection_ms(), None);
+
+        assert_eq!(status.record_error(), 1);
+        assert_eq!(status.record_error(), 2);
+        status.eject(Duration::from_secs(60), false);
+
+        assert!(status.is_ejected());
+        assert_eq!(status.ejections(), 1);
+        assert_eq!(status.record_error(), 1);
+        assert_eq!(status.since_last_ejection_ms(), Some(0));
+    }
+
+    #[test]
+    fn ejection_expires_test() {
+        let status = BackendStatus::new();
+        status.eject(Duration::from_millis(0), false);
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(!status.is_ejected());
+
+        status.eject(Duration::from_millis(0), true);
+        assert_eq!(status.ejections(), 1);
+    }
+
+    #[test]
+    fn success_resets_failures_test() {
+        let status = BackendStatus::new();
+        status.record_probe(false, 1, 2);
+        status.record_probe(true, 1, 2);
+        assert_eq!(status.record_probe(false, 1, 2), None);
+        assert!(status.is_healthy());
+    }
+}
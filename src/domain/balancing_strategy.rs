@@ -0,0 +1,19 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub backend_idx: usize,
+    pub weight: u32,
+    pub active: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SelectionContext<'a> {
+    pub candidates: &'a [Candidate],
+    pub key: u64,
+}
+
+pub trait BalancingStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Candidates are never empty; the returned index is a `Candidate::backend_idx`.
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize>;
+}
@@ -1,3 +1,5 @@
 pub mod backend_conn;
+pub mod backend_status;
+pub mod balancing_strategy;
 pub mod request;
 pub mod tcp_conn_pool;
@@ -1,6 +1,10 @@
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Status {
     Created,
     Processing,
@@ -8,25 +12,52 @@ pub enum Status {
     Failed,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationReason {
+    Done,
+    NoBackend,
+    IoError,
+    Timeout,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Request {
+    timestamp: f64,
     uuid: Uuid,
+    id: u64,
     target: String,
     status: Status,
     user_id: Uuid,
+    client: Option<SocketAddr>,
+    backend: Option<String>,
     time_taken: Option<f64>,
+    bytes_received: Option<usize>,
     bytes: Option<usize>,
+    http_status: Option<u16>,
+    reason: Option<TerminationReason>,
 }
 
 impl Request {
-    pub fn new(target: String, user_id: Uuid) -> Request {
+    pub fn new(id: u64, target: String, user_id: Uuid) -> Request {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs_f64())
+            .unwrap_or_default();
         Request {
+            timestamp,
             uuid: Uuid::new_v4(),
-            target: target,
+            id,
+            target,
             status: Status::Created,
-            user_id: user_id,
+            user_id,
+            client: None,
+            backend: None,
             time_taken: None,
+            bytes_received: None,
             bytes: None,
+            http_status: None,
+            reason: None,
         }
     }
 
@@ -42,6 +73,15 @@ impl Request {
         &self.status
     }
 
+    pub fn set_client(&mut self, client: SocketAddr) {
+        self.client = Some(client);
+    }
+
+    pub fn set_backend(&mut self, backend: String) {
+        self.backend = Some(backend);
+        self.status = Status::Processing;
+    }
+
     pub fn set_status(&mut self, status: Status) {
         self.status = status;
     }
@@ -50,9 +90,26 @@ impl Request {
         self.bytes = Some(bytes);
     }
 
+    pub fn set_bytes_received(&mut self, bytes: usize) {
+        self.bytes_received = Some(bytes);
+    }
+
     pub fn set_time_taken(&mut self, time_taken: f64) {
         self.time_taken = Some(time_taken);
     }
+
+    pub fn set_http_status(&mut self, http_status: u16) {
+        self.http_status = Some(http_status);
+    }
+
+    // Completed requests end with `Done`; everything else fails the request.
+    pub fn finish(&mut self, reason: TerminationReason) {
+        self.status = match reason {
+            TerminationReason::Done => Status::Completed,
+            _ => Status::Failed,
+        };
+        self.reason = Some(reason);
+    }
 }
 
 #[cfg(test)]
@@ -61,7 +118,7 @@ mod tests {
 
     #[test]
     fn constructor_test() {
-        let request: Request = Request::new("example.com".to_string(), Uuid::new_v4());
+        let request: Request = Request::new(1, "example.com".to_string(), Uuid::new_v4());
         assert_eq!(request.get_target(), "example.com");
         assert_eq!(request.get_status(), &Status::Created);
         assert_eq!(request.bytes, None);
@@ -70,21 +127,39 @@ mod tests {
 
     #[test]
     fn change_status_test() {
-        let mut request = Request::new("example.com".to_string(), Uuid::new_v4());
+        let mut request = Request::new(1, "example.com".to_string(), Uuid::new_v4());
         request.set_status(Status::Processing);
         assert_eq!(request.status, Status::Processing);
     }
 
+    #[test]
+    fn set_backend_test() {
+        let mut request = Request::new(1, "example.com".to_string(), Uuid::new_v4());
+        request.set_backend("127.0.0.1:3000".to_string());
+        assert_eq!(request.backend.as_deref(), Some("127.0.0.1:3000"));
+        assert_eq!(request.status, Status::Processing);
+    }
+
+    #[test]
+    fn finish_test() {
+        let mut request = Request::new(1, "example.com".to_string(), Uuid::new_v4());
+        request.finish(TerminationReason::Done);
+        assert_eq!(request.status, Status::Completed);
+        request.finish(TerminationReason::Timeout);
+        assert_eq!(request.status, Status::Failed);
+        assert_eq!(request.reason, Some(TerminationReason::Timeout));
+    }
+
     #[test]
     fn set_bytes_test() {
-        let mut request = Request::new("example.com".to_string(), Uuid::new_v4());
+        let mut request = Request::new(1, "example.com".to_string(), Uuid::new_v4());
         request.set_bytes(1024);
         assert_eq!(request.bytes, Some(1024));
     }
 
     #[test]
     fn set_time_taken_test() {
-        let mut request = Request::new("example.com".to_string(), Uuid::new_v4());
+        let mut request = Request::new(1, "example.com".to_string(), Uuid::new_v4());
         request.set_time_taken(0.5);
         assert_eq!(request.time_taken, Some(0.5));
     }
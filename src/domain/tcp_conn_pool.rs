@@ -8,7 +8,7 @@ pub trait TcpConnectionPool {
 
 pub trait SmartTcpConnectionPool {
     fn get_connection(
-        &mut self,
+        &self,
         user_id: Uuid,
     ) -> impl std::future::Future<Output = Option<TcpStream>> + Send;
 }
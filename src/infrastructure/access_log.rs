@@ -0,0 +1,323 @@
+use log::{error, warn};
+use rand::RngExt;
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::UdpSocket;
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::thread;
+
+use crate::config::access_log::{AccessLogConfig, AccessLogFormat, AccessLogSink, FileSinkConfig};
+use crate::domain::request::{Request, Status};
+
+const QUEUE_SIZE: usize = 8192;
+// local0.info
+const SYSLOG_PRIORITY: u8 = 134;
+
+// Records are formatted on the proxy tasks and written by a dedicated thread, so a slow
+// sink never blocks the runtime; when the queue is full records are dropped and counted.
+#[derive(Debug)]
+pub struct AccessLog {
+    format: AccessLogFormat,
+    sample_rate: f64,
+    sender: SyncSender<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+enum Sink {
+    Stdout(io::Stdout),
+    File(RotatingFile),
+    UnixSyslog(UnixDatagram),
+    UdpSyslog(UdpSocket),
+}
+
+struct RotatingFile {
+    path: String,
+    max_size: u64,
+    max_files: usize,
+    size: u64,
+    writer: BufWriter<File>,
+}
+
+impl AccessLog {
+    pub fn new(config: &AccessLogConfig) -> io::Result<AccessLog> {
+        let sink = Sink::open(&config.sink)?;
+        let (sender, receiver) = sync_channel(QUEUE_SIZE);
+        let dropped = Arc::new(AtomicU64::new(0));
+        thread::Builder::new()
+            .name("access-log".to_string())
+            .spawn({
+                let dropped = Arc::clone(&dropped);
+                move || write_loop(receiver, sink, dropped)
+            })?;
+        Ok(AccessLog {
+            format: config.format.clone(),
+            sample_rate: config.sample_rate,
+            sender,
+            dropped,
+        })
+    }
+
+    pub fn write(&self, request: &Request) {
+        if !self.sampled(request) {
+            return;
+        }
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(self.format(request)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Failed requests are always logged; sampling only thins out the completed ones.
+    fn sampled(&self, request: &Request) -> bool {
+        request.get_status() != &Status::Completed
+            || self.sample_rate >= 1.0
+            || rand::rng().random::<f64>() < self.sample_rate
+    }
+
+    fn format(&self, request: &Request) -> String {
+        match &self.format {
+            AccessLogFormat::Json => serde_json::to_string(request).unwrap_or_default(),
+            AccessLogFormat::Template(template) => match serde_json::to_value(request) {
+                Ok(record) => render_template(template, &record),
+                Err(_) => String::new(),
+            },
+        }
+    }
+}
+
+// Missing values render as `-`, strings without quotes.
+fn render_template(template: &str, record: &Value) -> String {
+    let mut parts = template.split('{');
+    let mut line = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let Some((field, literal)) = part.split_once('}') else {
+            line.push('{');
+            line.push_str(part);
+            continue;
+        };
+        match record.get(field) {
+            Some(Value::String(value)) => line.push_str(value),
+            Some(Value::Null) | None => line.push('-'),
+            Some(value) => line.push_str(&value.to_string()),
+        }
+        line.push_str(literal);
+    }
+    line
+}
+
+fn write_loop(lines: Receiver<String>, mut sink: Sink, dropped: Arc<AtomicU64>) {
+    while let Ok(line) = lines.recv() {
+        let mut result = sink.write_line(&line);
+        // Bursts are written back to back and flushed once the queue is empty.
+        while let Ok(line) = lines.try_recv() {
+            result = result.and(sink.write_line(&line));
+        }
+        if let Err(e) = result.and(sink.flush()) {
+            error!("Failed to write access log: {}", e);
+        }
+        let dropped = dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("Access log queue is full, dropped {} records", dropped);
+        }
+    }
+}
+
+impl Sink {
+    fn open(config: &AccessLogSink) -> io::Result<Sink> {
+        match config {
+            AccessLogSink::Stdout => Ok(Sink::Stdout(io::stdout())),
+            AccessLogSink::File(file) => Ok(Sink::File(RotatingFile::open(file)?)),
+            AccessLogSink::Syslog(address) if address.starts_with('/') => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(address)?;
+                Ok(Sink::UnixSyslog(socket))
+            }
+            AccessLogSink::Syslog(address) => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.connect(address)?;
+                Ok(Sink::UdpSyslog(socket))
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => writeln!(stdout.lock(), "{}", line),
+            Sink::File(file) => file.write_line(line),
+            Sink::UnixSyslog(socket) => socket.send(syslog_message(line).as_bytes()).map(|_| ()),
+            Sink::UdpSyslog(socket) => socket.send(syslog_message(line).as_bytes()).map(|_| ()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::File(file) => file.writer.flush(),
+            Sink::UnixSyslog(_) | Sink::UdpSyslog(_) => Ok(()),
+        }
+    }
+}
+
+fn syslog_message(line: &str) -> String {
+    format!("<{}>load-balancer: {}", SYSLOG_PRIORITY, line)
+}
+
+impl RotatingFile {
+    fn open(config: &FileSinkConfig) -> io::Result<RotatingFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        Ok(RotatingFile {
+            path: config.path.clone(),
+            max_size: config.max_size_mb.saturating_mul(1024 * 1024),
+            max_files: config.max_files,
+            size: file.metadata()?.len(),
+            writer: BufWriter::new(file),
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.writer, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    // access.log becomes access.log.1, access.log.1 becomes access.log.2 and so on.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        for idx in (1..self.max_files).rev() {
+            let from = format!("{}.{}", self.path, idx);
+            match fs::rename(&from, format!("{}.{}", self.path, idx + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, format!("{}.1", self.path))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.writer = BufWriter::new(file);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::access_log::ACCESS_LOG_FIELDS;
+    use crate::domain::request::TerminationReason;
+    use uuid::Uuid;
+
+    fn finished(reason: TerminationReason) -> Request {
+        let mut request = Request::new(7, "web".to_string(), Uuid::nil());
+        request.set_client("127.0.0.1:50000".parse().unwrap());
+        request.set_backend("127.0.0.1:3000".to_string());
+        request.set_bytes(42);
+        request.finish(reason);
+        request
+    }
+
+    fn access_log(format: AccessLogFormat, sample_rate: f64) -> AccessLog {
+        AccessLog::new(&AccessLogConfig {
+            format,
+            sink: AccessLogSink::Stdout,
+            sample_rate,
+        })
+        .unwrap()
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.log", name, std::process::id()));
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn json_format_test() {
+        let line =
+            access_log(AccessLogFormat::Json, 1.0).format(&finished(TerminationReason::Done));
+        let record: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(record["id"], 7);
+        assert_eq!(record["client"], "127.0.0.1:50000");
+        assert_eq!(record["status"], "completed");
+        assert_eq!(record["reason"], "done");
+        assert_eq!(record["http_status"], Value::Null);
+        for field in ACCESS_LOG_FIELDS {
+            assert!(record.get(field).is_some(), "missing field {}", field);
+        }
+    }
+
+    #[test]
+    fn template_format_test() {
+        let template =
+            "{client} -> {backend} {status}/{reason} {bytes}B {http_status} {".to_string();
+        let line = access_log(AccessLogFormat::Template(template), 1.0)
+            .format(&finished(TerminationReason::Timeout));
+        assert_eq!(
+            line,
+            "127.0.0.1:50000 -> 127.0.0.1:3000 failed/timeout 42B - {"
+        );
+    }
+
+    #[test]
+    fn sampling_keeps_failures_test() {
+        let log = access_log(AccessLogFormat::Json, 0.0);
+        assert!(!log.sampled(&finished(TerminationReason::Done)));
+        assert!(log.sampled(&finished(TerminationReason::NoBackend)));
+    }
+
+    #[test]
+    fn rotating_file_test() {
+        let path = temp_path("access-rotate");
+        let _ = fs::remove_file(&path);
+        let mut file = RotatingFile::open(&FileSinkConfig {
+            path: path.clone(),
+            max_size_mb: 1,
+            max_files: 2,
+        })
+        .unwrap();
+        file.max_size = 10;
+        for line in ["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+        file.writer.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(format!("{}.1", path)).unwrap(),
+            "third\n"
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}.2", path)).unwrap(),
+            "second\n"
+        );
+        for file in [path.clone(), format!("{}.1", path), format!("{}.2", path)] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn udp_syslog_test() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap().to_string();
+        let mut sink = Sink::open(&AccessLogSink::Syslog(address)).unwrap();
+        sink.write_line("hello").unwrap();
+
+        let mut buf = [0u8; 128];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"<134>load-balancer: hello");
+    }
+}
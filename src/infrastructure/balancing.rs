@@ -0,0 +1,250 @@
+use rand::RngExt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config::hashing::HashingConfig;
+use crate::config::pool::StrategyKind;
+use crate::domain::backend_conn::ConnString;
+use crate::domain::balancing_strategy::{BalancingStrategy, Candidate, SelectionContext};
+use crate::infrastructure::consistent_hash::{HashRing, Maglev};
+
+pub fn build_strategy(
+    kind: StrategyKind,
+    backends: &[ConnString],
+    hashing: &HashingConfig,
+) -> Arc<dyn BalancingStrategy> {
+    match kind {
+        StrategyKind::RoundRobin => Arc::new(RoundRobin::new()),
+        StrategyKind::WeightedRoundRobin => Arc::new(SmoothWeightedRoundRobin::new()),
+        StrategyKind::LeastConnections => Arc::new(LeastConnections::new()),
+        StrategyKind::PowerOfTwoChoices => Arc::new(PowerOfTwoChoices),
+        StrategyKind::Random => Arc::new(Random),
+        StrategyKind::ConsistentHash => Arc::new(HashRing::new(backends, hashing.virtual_nodes)),
+        StrategyKind::Maglev => Arc::new(Maglev::new(backends, hashing.table_size)),
+    }
+}
+
+pub struct RoundRobin {
+    current: AtomicUsize,
+}
+
+impl RoundRobin {
+    pub fn new() -> RoundRobin {
+        RoundRobin {
+            current: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl BalancingStrategy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round_robin"
+    }
+
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize> {
+        let candidates = context.candidates;
+        let position = self.current.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        Some(candidates[position].backend_idx)
+    }
+}
+
+// nginx-style smooth weighted round-robin: spreads heavy backends out instead of bursting them.
+pub struct SmoothWeightedRoundRobin {
+    current_weights: Mutex<Vec<i64>>,
+}
+
+impl SmoothWeightedRoundRobin {
+    pub fn new() -> SmoothWeightedRoundRobin {
+        SmoothWeightedRoundRobin {
+            current_weights: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl BalancingStrategy for SmoothWeightedRoundRobin {
+    fn name(&self) -> &'static str {
+        "weighted_round_robin"
+    }
+
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize> {
+        let mut current_weights = self.current_weights.lock().unwrap();
+        let max_idx = context.candidates.iter().map(|c| c.backend_idx).max()?;
+        if current_weights.len() <= max_idx {
+            current_weights.resize(max_idx + 1, 0);
+        }
+
+        let mut total = 0i64;
+        let mut best: Option<usize> = None;
+        for candidate in context.candidates {
+            let weight = candidate.weight as i64;
+            current_weights[candidate.backend_idx] += weight;
+            total += weight;
+            if best.is_none_or(|idx| current_weights[candidate.backend_idx] > current_weights[idx])
+            {
+                best = Some(candidate.backend_idx);
+            }
+        }
+
+        let best = best?;
+        current_weights[best] -= total;
+        Some(best)
+    }
+}
+
+pub struct LeastConnections {
+    tie_breaker: AtomicUsize,
+}
+
+impl LeastConnections {
+    pub fn new() -> LeastConnections {
+        LeastConnections {
+            tie_breaker: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl BalancingStrategy for LeastConnections {
+    fn name(&self) -> &'static str {
+        "least_connections"
+    }
+
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize> {
+        let candidates = context.candidates;
+        let offset = self.tie_breaker.fetch_add(1, Ordering::Relaxed);
+        (0..candidates.len())
+            .map(|i| &candidates[(offset + i) % candidates.len()])
+            .min_by(|a, b| load(a).total_cmp(&load(b)))
+            .map(|candidate| candidate.backend_idx)
+    }
+}
+
+pub struct PowerOfTwoChoices;
+
+impl BalancingStrategy for PowerOfTwoChoices {
+    fn name(&self) -> &'static str {
+        "power_of_two_choices"
+    }
+
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize> {
+        let candidates = context.candidates;
+        if candidates.len() == 1 {
+            return Some(candidates[0].backend_idx);
+        }
+        let mut rng = rand::rng();
+        let first = rng.random_range(0..candidates.len());
+        let mut second = rng.random_range(0..candidates.len() - 1);
+        if second >= first {
+            second += 1;
+        }
+        let (a, b) = (&candidates[first], &candidates[second]);
+        Some(if load(b) < load(a) { b } else { a }.backend_idx)
+    }
+}
+
+pub struct Random;
+
+impl BalancingStrategy for Random {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize> {
+        let position = rand::rng().random_range(0..context.candidates.len());
+        Some(context.candidates[position].backend_idx)
+    }
+}
+
+fn load(candidate: &Candidate) -> f64 {
+    candidate.active as f64 / candidate.weight.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(backend_idx: usize, weight: u32, active: usize) -> Candidate {
+        Candidate {
+            backend_idx,
+            weight,
+            active,
+        }
+    }
+
+    fn pick(strategy: &dyn BalancingStrategy, candidates: &[Candidate]) -> usize {
+        strategy
+            .select(&SelectionContext { candidates, key: 0 })
+            .unwrap()
+    }
+
+    #[test]
+    fn round_robin_test() {
+        let strategy = RoundRobin::new();
+        let candidates = [candidate(0, 1, 0), candidate(3, 1, 0)];
+        let picks: Vec<usize> = (0..4).map(|_| pick(&strategy, &candidates)).collect();
+        assert_eq!(picks, vec![0, 3, 0, 3]);
+    }
+
+    #[test]
+    fn smooth_weighted_round_robin_test() {
+        let strategy = SmoothWeightedRoundRobin::new();
+        let candidates = [candidate(0, 5, 0), candidate(1, 1, 0), candidate(2, 1, 0)];
+        let picks: Vec<usize> = (0..7).map(|_| pick(&strategy, &candidates)).collect();
+        assert_eq!(picks, vec![0, 0, 1, 0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn smooth_weighted_round_robin_skips_missing_candidates_test() {
+        let strategy = SmoothWeightedRoundRobin::new();
+        let candidates = [candidate(0, 2, 0), candidate(2, 1, 0)];
+        let picks: Vec<usize> = (0..3).map(|_| pick(&strategy, &candidates)).collect();
+        assert_eq!(picks, vec![0, 2, 0]);
+    }
+
+    #[test]
+    fn least_connections_test() {
+        let strategy = LeastConnections::new();
+        let candidates = [candidate(0, 1, 4), candidate(1, 1, 1), candidate(2, 1, 3)];
+        for _ in 0..3 {
+            assert_eq!(pick(&strategy, &candidates), 1);
+        }
+    }
+
+    #[test]
+    fn least_connections_respects_weight_test() {
+        let strategy = LeastConnections::new();
+        let candidates = [candidate(0, 4, 4), candidate(1, 1, 2)];
+        assert_eq!(pick(&strategy, &candidates), 0);
+    }
+
+    #[test]
+    fn power_of_two_choices_test() {
+        let strategy = PowerOfTwoChoices;
+        let candidates = [candidate(0, 1, 10), candidate(1, 1, 0)];
+        for _ in 0..10 {
+            assert_eq!(pick(&strategy, &candidates), 1);
+        }
+    }
+
+    #[test]
+    fn random_test() {
+        let strategy = Random;
+        let candidates = [candidate(2, 1, 0), candidate(5, 1, 0)];
+        for _ in 0..10 {
+            assert!([2, 5].contains(&pick(&strategy, &candidates)));
+        }
+    }
+
+    #[test]
+    fn build_strategy_test() {
+        let hashing = HashingConfig::default();
+        assert_eq!(
+            build_strategy(StrategyKind::LeastConnections, &[], &hashing).name(),
+            "least_connections"
+        );
+        assert_eq!(
+            build_strategy(StrategyKind::Maglev, &[], &hashing).name(),
+            "maglev"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+use tokio::time::{Duration, timeout};
+
+use crate::config::limits::ConnectionLimitConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueError {
+    Full,
+    Timeout,
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::Full => write!(f, "connection limit reached and wait queue is full"),
+            QueueError::Timeout => write!(f, "timed out waiting for a free connection slot"),
+        }
+    }
+}
+
+// Where connections wait for a slot once every candidate is at its limit. Whoever frees a
+// slot calls `release`, which wakes all waiters to race for it again.
+#[derive(Debug, Default)]
+pub struct WaitQueue {
+    waiting: AtomicUsize,
+    released: Notify,
+}
+
+// Counts a connection against its listener's limit for as long as it is alive.
+#[derive(Debug)]
+pub struct ConnectionSlot {
+    limiter: Arc<ConnectionLimiter>,
+}
+
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    config: ConnectionLimitConfig,
+    active: AtomicUsize,
+    queue: WaitQueue,
+}
+
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl WaitQueue {
+    pub fn new() -> WaitQueue {
+        WaitQueue::default()
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::Acquire)
+    }
+
+    pub fn release(&self) {
+        self.released.notify_waiters();
+    }
+
+    // Calls `take` until it hands out a slot, re-trying every time one is released.
+    pub async fn wait_for<T>(
+        &self,
+        config: &ConnectionLimitConfig,
+        mut take: impl FnMut() -> Option<T>,
+    ) -> Result<T, QueueError> {
+        if let Some(slot) = take() {
+            return Ok(slot);
+        }
+        if self.waiting.fetch_add(1, Ordering::AcqRel) >= config.queue_size {
+            self.waiting.fetch_sub(1, Ordering::AcqRel);
+            return Err(QueueError::Full);
+        }
+        let _waiting = Waiting(&self.waiting);
+        let wait = async {
+            loop {
+                let released = self.released.notified();
+                tokio::pin!(released);
+                // Registered before the retry, so a release in between is not missed.
+                released.as_mut().enable();
+                if let Some(slot) = take() {
+                    return slot;
+                }
+                released.await;
+            }
+        };
+        timeout(Duration::from_millis(config.queue_timeout_ms), wait)
+            .await
+            .map_err(|_| QueueError::Timeout)
+    }
+}
+
+impl ConnectionLimiter {
+    pub fn new(config: ConnectionLimitConfig) -> ConnectionLimiter {
+        ConnectionLimiter {
+            config,
+            active: AtomicUsize::new(0),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ConnectionLimitConfig {
+        &self.config
+    }
+
+    pub async fn acquire(self: &Arc<Self>) -> Result<ConnectionSlot, QueueError> {
+        self.queue
+            .wait_for(&self.config, || self.try_acquire())
+            .await
+    }
+
+    fn try_acquire(self: &Arc<Self>) -> Option<ConnectionSlot> {
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < self.config.max_connections).then_some(active + 1)
+            })
+            .ok()?;
+        Some(ConnectionSlot {
+            limiter: Arc::clone(self),
+        })
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.limiter.active.fetch_sub(1, Ordering::AcqRel);
+        self.limiter.queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::limits::OverflowAction;
+
+    fn limiter(max_connections: usize, queue_size: usize) -> Arc<ConnectionLimiter> {
+        Arc::new(ConnectionLimiter::new(ConnectionLimitConfig {
+            max_connections,
+            queue_size,
+            queue_timeout_ms: 100,
+            overflow: OverflowAction::Reject,
+        }))
+    }
+
+    #[tokio::test]
+    async fn queued_connection_gets_released_slot_test() {
+        let limiter = limiter(1, 1);
+        let first = limiter.acquire().await.unwrap();
+        let waiter = tokio::spawn({
+            let limiter = Arc::clone(&limiter);
+            async move { limiter.acquire().await.map(|_| ()) }
+        });
+        while limiter.queue.waiting() == 0 {
+            tokio::task::yield_now().await;
+        }
+        drop(first);
+        assert_eq!(waiter.await.unwrap(), Ok(()));
+        assert_eq!(limiter.active.load(Ordering::Acquire), 0);
+    }
+
+    #[tokio::test]
+    async fn overflow_test() {
+        let limiter = limiter(1, 1);
+        let _first = limiter.acquire().await.unwrap();
+        let queued = tokio::spawn({
+            let limiter = Arc::clone(&limiter);
+            async move { limiter.acquire().await.map(|_| ()) }
+        });
+        while limiter.queue.waiting() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(limiter.acquire().await.unwrap_err(), QueueError::Full);
+        assert_eq!(queued.await.unwrap(), Err(QueueError::Timeout));
+        assert_eq!(limiter.queue.waiting(), 0);
+        assert_eq!(limiter.active.load(Ordering::Acquire), 1);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::domain::backend_conn::ConnString;
+use crate::domain::balancing_strategy::{BalancingStrategy, SelectionContext};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let hash = bytes.iter().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    });
+    mix(hash)
+}
+
+// splitmix64 finalizer, spreads sequential keys such as session counters over the ring.
+pub fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn candidate_mask(context: &SelectionContext<'_>, backends: usize) -> Vec<bool> {
+    let mut mask = vec![false; backends];
+    for candidate in context.candidates {
+        if let Some(allowed) = mask.get_mut(candidate.backend_idx) {
+            *allowed = true;
+        }
+    }
+    mask
+}
+
+pub struct HashRing {
+    points: Vec<(u64, usize)>,
+    backends: usize,
+}
+
+impl HashRing {
+    pub fn new(backends: &[ConnString], virtual_nodes: usize) -> HashRing {
+        let mut points = Vec::new();
+        for (backend_idx, backend) in backends.iter().enumerate() {
+            let replicas = virtual_nodes * backend.get_weight() as usize;
+            for replica in 0..replicas {
+                let point = hash_bytes(format!("{}#{}", backend.address(), replica).as_bytes());
+                points.push((point, backend_idx));
+            }
+        }
+        points.sort_unstable();
+        HashRing {
+            points,
+            backends: backends.len(),
+        }
+    }
+}
+
+impl BalancingStrategy for HashRing {
+    fn name(&self) -> &'static str {
+        "consistent_hash"
+    }
+
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize> {
+        let allowed = candidate_mask(context, self.backends);
+        let start = self
+            .points
+            .partition_point(|(point, _)| *point < mix(context.key));
+        (0..self.points.len())
+            .map(|offset| self.points[(start + offset) % self.points.len()].1)
+            .find(|&backend_idx| allowed[backend_idx])
+    }
+}
+
+pub struct Maglev {
+    table: Vec<usize>,
+    backends: usize,
+}
+
+impl Maglev {
+    pub fn new(backends: &[ConnString], table_size: usize) -> Maglev {
+        let mut table = vec![usize::MAX; table_size];
+        if backends.is_empty() {
+            return Maglev { table, backends: 0 };
+        }
+
+        let permutations: Vec<(usize, usize)> = backends
+            .iter()
+            .map(|backend| {
+                let address = backend.address();
+                let offset = hash_bytes(address.as_bytes()) as usize % table_size;
+                let skip = hash_bytes(format!("{}#skip", address).as_bytes()) as usize
+                    % (table_size - 1)
+                    + 1;
+                (offset, skip)
+            })
+            .collect();
+        let mut next = vec![0usize; backends.len()];
+        let mut filled = 0;
+
+        'fill: loop {
+            for (backend_idx, backend) in backends.iter().enumerate() {
+                let (offset, skip) = permutations[backend_idx];
+                for _ in 0..backend.get_weight() {
+                    let mut slot = (offset + next[backend_idx] * skip) % table_size;
+                    while table[slot] != usize::MAX {
+                        next[backend_idx] += 1;
+                        slot = (offset + next[backend_idx] * skip) % table_size;
+                    }
+                    table[slot] = backend_idx;
+                    next[backend_idx] += 1;
+                    filled += 1;
+                    if filled == table_size {
+                        break 'fill;
+                    }
+                }
+            }
+        }
+
+        Maglev {
+            table,
+            backends: backends.len(),
+        }
+    }
+}
+
+impl BalancingStrategy for Maglev {
+    fn name(&self) -> &'static str {
+        "maglev"
+    }
+
+    fn select(&self, context: &SelectionContext<'_>) -> Option<usize> {
+        let allowed = candidate_mask(context, self.backends);
+        let size = self.table.len();
+        let start = mix(context.key) as usize % size;
+        (0..size)
+            .map(|offset| self.table[(start + offset) % size])
+            .find(|&backend_idx| backend_idx != usize::MAX && allowed[backend_idx])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::balancing_strategy::Candidate;
+
+    const KEYS: u64 = 10_000;
+
+    fn backends(count: u16) -> Vec<ConnString> {
+        (0..count)
+            .map(|i| ConnString::new("10.0.0.1".to_string(), 3000 + i))
+            .collect()
+    }
+
+    fn candidates(backends: usize) -> Vec<Candidate> {
+        (0..backends)
+            .map(|backend_idx| Candidate {
+                backend_idx,
+                weight: 1,
+                active: 0,
+            })
+            .collect()
+    }
+
+    fn assignments(strategy: &dyn BalancingStrategy, candidates: &[Candidate]) -> Vec<usize> {
+        (0..KEYS)
+            .map(|key| {
+                strategy
+                    .select(&SelectionContext { candidates, key })
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn moved(before: &[usize], after: &[usize]) -> f64 {
+        let moved = before.iter().zip(after).filter(|(a, b)| a != b).count();
+        moved as f64 / before.len() as f64
+    }
+
+    #[test]
+    fn hash_bytes_is_stable_test() {
+        assert_eq!(hash_bytes(b"10.0.0.1"), hash_bytes(b"10.0.0.1"));
+        assert_ne!(hash_bytes(b"10.0.0.1"), hash_bytes(b"10.0.0.2"));
+    }
+
+    #[test]
+    fn ring_is_sticky_test() {
+        let ring = HashRing::new(&backends(5), 100);
+        let candidates = candidates(5);
+        assert_eq!(
+            assignments(&ring, &candidates),
+            assignments(&ring, &candidates)
+        );
+    }
+
+    #[test]
+    fn ring_adding_backend_remaps_few_keys_test() {
+        let before = assignments(&HashRing::new(&backends(10), 160), &candidates(10));
+        let after = assignments(&HashRing::new(&backends(11), 160), &candidates(11));
+        assert!(moved(&before, &after) < 0.15);
+    }
+
+    #[test]
+    fn ring_unavailable_backend_only_moves_its_keys_test() {
+        let ring = HashRing::new(&backends(10), 160);
+        let before = assignments(&ring, &candidates(10));
+        let without_three: Vec<Candidate> = candidates(10)
+            .into_iter()
+            .filter(|candidate| candidate.backend_idx != 3)
+            .collect();
+        let after = assignments(&ring, &without_three);
+
+        for (before, after) in before.iter().zip(&after) {
+            if *before != 3 {
+                assert_eq!(before, after);
+            }
+            assert_ne!(*after, 3);
+        }
+    }
+
+    #[test]
+    fn maglev_table_is_balanced_test() {
+        let maglev = Maglev::new(&backends(5), 65537);
+        let mut counts = [0usize; 5];
+        for backend_idx in &maglev.table {
+            counts[*backend_idx] += 1;
+        }
+        let (min, max) = (counts.iter().min().unwrap(), counts.iter().max().unwrap());
+        assert!(max - min <= 1);
+    }
+
+    #[test]
+    fn maglev_respects_weights_test() {
+        let mut weighted = backends(2);
+        weighted[0] = weighted[0].clone().with_weight(3);
+        let maglev = Maglev::new(&weighted, 65537);
+        let heavy = maglev.table.iter().filter(|idx| **idx == 0).count();
+        assert!((heavy as f64 / 65537.0 - 0.75).abs() < 0.01);
+    }
+
+    #[test]
+    fn maglev_adding_backend_remaps_few_keys_test() {
+        let before = assignments(&Maglev::new(&backends(10), 65537), &candidates(10));
+        let after = assignments(&Maglev::new(&backends(11), 65537), &candidates(11));
+        assert!(moved(&before, &after) < 0.2);
+    }
+
+    #[test]
+    fn maglev_skips_unavailable_backend_test() {
+        let maglev = Maglev::new(&backends(3), 65537);
+        let only_one = [Candidate {
+            backend_idx: 1,
+            weight: 1,
+            active: 0,
+        }];
+        for key in 0..100 {
+            let selected = maglev.select(&SelectionContext {
+                candidates: &only_one,
+                key,
+            });
+            assert_eq!(selected, Some(1));
+        }
+    }
+}
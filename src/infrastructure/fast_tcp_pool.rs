@@ -1,54 +1,549 @@
-use std::collections::VecDeque;
-use std::sync::Arc;
+use arc_swap::ArcSwapOption;
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use hyper::client::conn::http2;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use log::{debug, warn};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io;
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
-
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::time::timeout;
+
+use crate::config::hashing::{HashKey, HashingConfig};
+use crate::config::keep_alive::KeepAliveConfig;
+use crate::config::limits::{ConnectionLimitConfig, OverflowAction};
+use crate::config::outlier_detection::OutlierDetectionConfig;
+use crate::config::pool::{BackendProtocol, StrategyKind};
 use crate::domain::backend_conn::ConnString;
+use crate::domain::backend_status::{ActiveConnection, AdminState, BackendStatus};
+use crate::domain::balancing_strategy::{BalancingStrategy, Candidate, SelectionContext};
 use crate::domain::tcp_conn_pool::FastTcpPool;
+use crate::infrastructure::balancing::build_strategy;
+use crate::infrastructure::connection_limit::{QueueError, WaitQueue};
+use crate::infrastructure::metrics::BackendMetrics;
+use crate::infrastructure::outlier_detection::OutlierDetector;
+use crate::infrastructure::tls::{ALPN_H2, BackendStream, TlsOriginator};
+
+pub type Http2Sender = http2::SendRequest<BoxBody<Bytes, hyper::Error>>;
 
+// New connections are always dialed outside of any lock; the idle stacks are only locked
+// for a single push or pop.
 #[derive(Clone)]
 pub struct ConnectionPool {
     pub backends: Arc<Vec<ConnString>>,
-    pub current: Arc<AtomicUsize>,
-    pub pools: Arc<Vec<Mutex<VecDeque<TcpStream>>>>,
+    pub strategy: Arc<dyn BalancingStrategy>,
+    pub hash_key: HashKey,
+    pub pools: Arc<Vec<Arc<IdleStreams>>>,
+    pub multiplexed: Arc<Vec<Arc<SharedConnection>>>,
+    pub status: Arc<Vec<Arc<BackendStatus>>>,
+    pub metrics: Arc<Vec<Arc<BackendMetrics>>>,
+    pub outlier_detector: Option<Arc<OutlierDetector>>,
+    pub keep_alive: KeepAliveConfig,
+    pub limits: Option<ConnectionLimitConfig>,
+    pub queue: Arc<WaitQueue>,
+    pub tls: Option<Arc<TlsOriginator>>,
+    pub protocol: BackendProtocol,
     pub max_pool_size: usize,
 }
 
+// A reserved place under a backend's connection limit; freeing it wakes queued requests.
+// HTTP/2 groups take one per request, as requests share the backend's single connection.
+#[derive(Debug)]
+pub struct BackendSlot {
+    _active: ActiveConnection,
+    queue: Arc<WaitQueue>,
+}
+
+impl Drop for BackendSlot {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+// A backend connection together with what keep-alive reuse needs to know about it.
+#[derive(Debug)]
+pub struct PooledStream {
+    pub stream: BackendStream,
+    pub created: Instant,
+    pub idle_since: Instant,
+    pub requests: u32,
+}
+
+// One HTTP/2 connection per backend, shared by all requests while it stays open.
+#[derive(Default)]
+pub struct SharedConnection {
+    sender: ArcSwapOption<Http2Sender>,
+    // Held while dialing, so requests arriving meanwhile wait for that connection instead
+    // of opening their own.
+    dialing: AsyncMutex<()>,
+}
+
+impl SharedConnection {
+    fn current(&self) -> Option<Http2Sender> {
+        self.sender
+            .load_full()
+            .filter(|sender| !sender.is_closed())
+            .map(|sender| (*sender).clone())
+    }
+}
+
+// The idle streams of one backend, newest on top: the stream reused next is the one least
+// likely to have run into the backend's own idle timeout.
+#[derive(Debug)]
+pub struct IdleStreams {
+    streams: Mutex<Vec<PooledStream>>,
+    capacity: AtomicUsize,
+}
+
+impl IdleStreams {
+    fn new(capacity: usize) -> IdleStreams {
+        IdleStreams {
+            streams: Mutex::new(Vec::new()),
+            capacity: AtomicUsize::new(capacity),
+        }
+    }
+
+    // A full stack hands the stream back, which closes it.
+    pub fn push(&self, pooled: PooledStream) -> Result<(), PooledStream> {
+        let mut streams = self.streams.lock().unwrap();
+        if streams.len() >= self.capacity.load(Ordering::Relaxed) {
+            return Err(pooled);
+        }
+        streams.push(pooled);
+        Ok(())
+    }
+
+    pub fn pop(&self) -> Option<PooledStream> {
+        self.streams.lock().unwrap().pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.streams.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Shrinking closes the oldest streams; everything else stays where both the old and
+    // the rebuilt pool find it.
+    fn resize(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+        let mut streams = self.streams.lock().unwrap();
+        let excess = streams.len().saturating_sub(capacity);
+        streams.drain(..excess);
+    }
+}
+
+impl PooledStream {
+    pub fn new(stream: BackendStream) -> PooledStream {
+        let now = Instant::now();
+        PooledStream {
+            stream,
+            created: now,
+            idle_since: now,
+            requests: 0,
+        }
+    }
+
+    // A socket the backend has closed (or written to unprompted) is readable while idle.
+    fn is_alive(&self) -> bool {
+        let mut buf = [0u8; 1];
+        matches!(
+            self.stream.tcp().try_read(&mut buf),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock
+        )
+    }
+}
+
 impl ConnectionPool {
     pub fn new(backends: Vec<ConnString>, max_pool_size: usize) -> ConnectionPool {
         ConnectionPool {
             backends: Arc::new(backends.clone()),
-            current: Arc::new(AtomicUsize::new(0)),
+            strategy: build_strategy(
+                StrategyKind::RoundRobin,
+                &backends,
+                &HashingConfig::default(),
+            ),
+            hash_key: HashKey::default(),
             pools: Arc::new(
                 backends
                     .iter()
-                    .map(|_| Mutex::new(VecDeque::new()))
+                    .map(|_| Arc::new(IdleStreams::new(max_pool_size)))
                     .collect(),
             ),
+            multiplexed: Arc::new(
+                backends
+                    .iter()
+                    .map(|_| Arc::new(SharedConnection::default()))
+                    .collect(),
+            ),
+            status: Arc::new(
+                backends
+                    .iter()
+                    .map(|_| Arc::new(BackendStatus::new()))
+                    .collect(),
+            ),
+            metrics: Arc::new(
+                backends
+                    .iter()
+                    .map(|_| Arc::new(BackendMetrics::new()))
+                    .collect(),
+            ),
+            outlier_detector: None,
+            keep_alive: KeepAliveConfig::default(),
+            limits: None,
+            queue: Arc::new(WaitQueue::new()),
+            tls: None,
+            protocol: BackendProtocol::default(),
             max_pool_size,
         }
     }
 
-    pub async fn return_connection(&self, backend_idx: usize, stream: TcpStream) {
-        let mut pool = self.pools[backend_idx].lock().await;
-        if pool.len() < self.max_pool_size {
-            pool.push_back(stream);
+    // Backends already in this pool (matched by address) keep their uuid, health state and
+    // idle streams, so sticky pins and ejections survive a reload.
+    pub fn rebuild(&self, backends: Vec<ConnString>, max_pool_size: usize) -> ConnectionPool {
+        let mut kept_backends = Vec::with_capacity(backends.len());
+        let mut pools = Vec::with_capacity(backends.len());
+        let mut multiplexed = Vec::with_capacity(backends.len());
+        let mut status = Vec::with_capacity(backends.len());
+        let mut metrics = Vec::with_capacity(backends.len());
+        for backend in backends {
+            match self.position(&backend.address()) {
+                Some(idx) => {
+                    kept_backends
+                        .push(self.backends[idx].clone().with_weight(backend.get_weight()));
+                    self.pools[idx].resize(max_pool_size);
+                    pools.push(Arc::clone(&self.pools[idx]));
+                    multiplexed.push(Arc::clone(&self.multiplexed[idx]));
+                    status.push(Arc::clone(&self.status[idx]));
+                    metrics.push(Arc::clone(&self.metrics[idx]));
+                }
+                None => {
+                    kept_backends.push(backend);
+                    pools.push(Arc::new(IdleStreams::new(max_pool_size)));
+                    multiplexed.push(Arc::new(SharedConnection::default()));
+                    status.push(Arc::new(BackendStatus::new()));
+                    metrics.push(Arc::new(BackendMetrics::new()));
+                }
+            }
+        }
+        ConnectionPool {
+            strategy: build_strategy(
+                StrategyKind::RoundRobin,
+                &kept_backends,
+                &HashingConfig::default(),
+            ),
+            backends: Arc::new(kept_backends),
+            hash_key: HashKey::default(),
+            pools: Arc::new(pools),
+            multiplexed: Arc::new(multiplexed),
+            status: Arc::new(status),
+            metrics: Arc::new(metrics),
+            outlier_detector: None,
+            keep_alive: KeepAliveConfig::default(),
+            limits: None,
+            // Shared so that connections still running on the old pool wake the new waiters.
+            queue: Arc::clone(&self.queue),
+            tls: None,
+            protocol: BackendProtocol::default(),
+            max_pool_size,
         }
     }
-}
 
-impl FastTcpPool for ConnectionPool {
-    async fn get_connection(&self, session_id: u64) -> Option<TcpStream> {
-        let backend_idx = self.current.fetch_add(1, Ordering::Relaxed) % self.backends.len();
+    pub fn position(&self, address: &str) -> Option<usize> {
+        self.backends
+            .iter()
+            .position(|backend| backend.address() == address)
+    }
 
-        let mut pool = self.pools[backend_idx].lock().await;
-        if let Some(stream) = pool.pop_front() {
-            return Some(stream);
+    pub fn strategy(&mut self, kind: StrategyKind, hashing: &HashingConfig) {
+        self.strategy = build_strategy(kind, &self.backends, hashing);
+        self.hash_key = hashing.key.clone();
+    }
+
+    pub fn keep_alive(&mut self, config: KeepAliveConfig) {
+        self.keep_alive = config;
+    }
+
+    pub fn limits(&mut self, config: ConnectionLimitConfig) {
+        self.limits = Some(config);
+    }
+
+    pub fn protocol(&mut self, protocol: BackendProtocol) {
+        self.protocol = protocol;
+    }
+
+    pub fn tls(&mut self, originator: TlsOriginator) {
+        self.tls = Some(Arc::new(originator));
+    }
+
+    pub fn outlier_detection(&mut self, config: OutlierDetectionConfig) {
+        self.outlier_detector = Some(Arc::new(OutlierDetector::new(config)));
+    }
+
+    pub fn is_available(&self, backend_idx: usize) -> bool {
+        self.accepts_pinned(backend_idx)
+            && self.status[backend_idx].admin_state() == AdminState::Active
+    }
+
+    // Draining backends still take clients that are already pinned to them.
+    pub fn accepts_pinned(&self, backend_idx: usize) -> bool {
+        let status = &self.status[backend_idx];
+        status.is_healthy()
+            && !status.is_ejected()
+            && status.admin_state() != AdminState::Maintenance
+    }
+
+    pub fn overflow(&self) -> OverflowAction {
+        self.limits
+            .as_ref()
+            .map(|limits| limits.overflow)
+            .unwrap_or_default()
+    }
+
+    pub fn has_capacity(&self, backend_idx: usize) -> bool {
+        self.limits
+            .as_ref()
+            .is_none_or(|limits| self.status[backend_idx].active() < limits.max_connections)
+    }
+
+    pub fn reserve(&self, backend_idx: usize) -> Option<BackendSlot> {
+        let status = &self.status[backend_idx];
+        let active = match &self.limits {
+            Some(limits) => status.try_track(limits.max_connections)?,
+            None => status.track(),
+        };
+        Some(BackendSlot {
+            _active: active,
+            queue: Arc::clone(&self.queue),
+        })
+    }
+
+    // Picks a backend with a free slot, `preferred` first. When every available backend is
+    // at its limit the caller queues for a slot; Ok(None) means no backend is available at all.
+    pub async fn reserve_any(
+        &self,
+        session_id: u64,
+        exclude: &[usize],
+        preferred: Option<usize>,
+    ) -> Result<Option<(usize, BackendSlot)>, QueueError> {
+        let take = || {
+            let backend_idx = preferred
+                .filter(|backend_idx| self.has_capacity(*backend_idx))
+                .or_else(|| self.select(session_id, exclude))?;
+            self.reserve(backend_idx).map(|slot| (backend_idx, slot))
+        };
+        let Some(limits) = &self.limits else {
+            return Ok(take());
+        };
+        let any_left = preferred.is_some()
+            || (0..self.backends.len())
+                .any(|idx| !exclude.contains(&idx) && self.is_available(idx));
+        if !any_left {
+            return Ok(None);
         }
+        self.queue.wait_for(limits, take).await.map(Some)
+    }
+
+    pub fn report_success(&self, backend_idx: usize) {
+        self.status[backend_idx].record_success();
+    }
+
+    pub fn report_failure(&self, backend_idx: usize) {
+        let Some(detector) = &self.outlier_detector else {
+            return;
+        };
+        if let Some(duration) = detector.record_failure(&self.status, backend_idx) {
+            warn!(
+                "Backend {} ejected for {:?} after consecutive failures",
+                self.backends[backend_idx].address(),
+                duration
+            );
+        }
+    }
+
+    pub async fn acquire(&self, session_id: u64) -> Option<(usize, BackendStream)> {
+        let backend_idx = self.select(session_id, &[])?;
+        let pooled = self.connect(backend_idx, None).await?;
+        Some((backend_idx, pooled.stream))
+    }
 
+    pub fn select(&self, session_id: u64, exclude: &[usize]) -> Option<usize> {
+        let candidates: Vec<Candidate> = (0..self.backends.len())
+            .filter(|idx| {
+                !exclude.contains(idx) && self.is_available(*idx) && self.has_capacity(*idx)
+            })
+            .map(|backend_idx| Candidate {
+                backend_idx,
+                weight: self.backends[backend_idx].get_weight(),
+                active: self.status[backend_idx].active(),
+            })
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        self.strategy.select(&SelectionContext {
+            candidates: &candidates,
+            key: session_id,
+        })
+    }
+
+    pub async fn connect(
+        &self,
+        backend_idx: usize,
+        connect_timeout: Option<Duration>,
+    ) -> Option<PooledStream> {
+        let metrics = &self.metrics[backend_idx];
+        // Newest idle stream first; expired or dead streams are closed on the way.
+        while let Some(pooled) = self.pools[backend_idx].pop() {
+            if self.is_reusable(&pooled, Instant::now()) && pooled.is_alive() {
+                metrics.pool_hits.fetch_add(1, Ordering::Relaxed);
+                metrics.connections.fetch_add(1, Ordering::Relaxed);
+                return Some(pooled);
+            }
+        }
+        metrics.pool_misses.fetch_add(1, Ordering::Relaxed);
+        self.open(backend_idx, connect_timeout, |stream| async move {
+            Ok(PooledStream::new(stream))
+        })
+        .await
+    }
+
+    // Requests on HTTP/2 groups share the backend's open connection; a new one is only
+    // dialed when there is none or the backend has closed it, and only by one request.
+    pub async fn connect_multiplexed(
+        &self,
+        backend_idx: usize,
+        connect_timeout: Duration,
+    ) -> Option<Http2Sender> {
+        let metrics = &self.metrics[backend_idx];
+        let shared = &self.multiplexed[backend_idx];
+        if let Some(sender) = shared.current() {
+            metrics.pool_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(sender);
+        }
+        let _dialing = timeout(connect_timeout, shared.dialing.lock()).await.ok()?;
+        if let Some(sender) = shared.current() {
+            metrics.pool_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(sender);
+        }
+        metrics.pool_misses.fetch_add(1, Ordering::Relaxed);
+
+        let address = self.backends[backend_idx].address();
+        let sender = self
+            .open(backend_idx, Some(connect_timeout), |stream| async move {
+                let (sender, connection) =
+                    http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                        .await
+                        .map_err(io::Error::other)?;
+                tokio::spawn(async move {
+                    if let Err(e) = connection.await {
+                        debug!("HTTP/2 connection to {} closed: {}", address, e);
+                    }
+                });
+                Ok(sender)
+            })
+            .await?;
+        shared.sender.store(Some(Arc::new(sender.clone())));
+        Some(sender)
+    }
+
+    // Dials the backend and runs `handshake` on the new stream, all within `connect_timeout`.
+    async fn open<T, F, Fut>(
+        &self,
+        backend_idx: usize,
+        connect_timeout: Option<Duration>,
+        handshake: F,
+    ) -> Option<T>
+    where
+        F: FnOnce(BackendStream) -> Fut,
+        Fut: Future<Output = io::Result<T>>,
+    {
+        let metrics = &self.metrics[backend_idx];
         let backend = &self.backends[backend_idx];
-        TcpStream::connect(&backend.address()).await.ok()
+        let started = Instant::now();
+        let connect = async { handshake(self.dial(backend).await?).await };
+        let result = match connect_timeout {
+            Some(connect_timeout) => timeout(connect_timeout, connect)
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+            None => connect.await,
+        };
+        match result {
+            Ok(connection) => {
+                metrics.connect_latency.observe(started.elapsed());
+                metrics.connections.fetch_add(1, Ordering::Relaxed);
+                Some(connection)
+            }
+            Err(e) => {
+                metrics.connect_failures.fetch_add(1, Ordering::Relaxed);
+                warn!("Failed to connect to backend {}: {}", backend.address(), e);
+                self.report_failure(backend_idx);
+                None
+            }
+        }
+    }
+
+    // Groups that originate TLS finish the handshake within the same connect timeout.
+    async fn dial(&self, backend: &ConnString) -> io::Result<BackendStream> {
+        let stream = TcpStream::connect(backend.address()).await?;
+        let alpn: &[&[u8]] = match self.protocol {
+            BackendProtocol::Http2 => &[ALPN_H2],
+            BackendProtocol::Http1 => &[],
+        };
+        match &self.tls {
+            Some(tls) => tls.connect(stream, backend.get_host(), alpn).await,
+            None => Ok(BackendStream::Plain(stream)),
+        }
+    }
+
+    // Also lets go of the shared HTTP/2 connection; requests still running on it finish.
+    pub fn clear_idle(&self, backend_idx: usize) -> usize {
+        let mut closed = 0;
+        while self.pools[backend_idx].pop().is_some() {
+            closed += 1;
+        }
+        if self.multiplexed[backend_idx].sender.swap(None).is_some() {
+            closed += 1;
+        }
+        closed
+    }
+
+    pub fn return_connection(&self, backend_idx: usize, stream: TcpStream) {
+        let _ = self.pools[backend_idx].push(PooledStream::new(BackendStream::Plain(stream)));
+    }
+
+    // Takes back a stream after a completed keep-alive request.
+    pub fn release(&self, backend_idx: usize, mut pooled: PooledStream) {
+        let now = Instant::now();
+        pooled.requests += 1;
+        pooled.idle_since = now;
+        if !self.is_reusable(&pooled, now) || !self.is_available(backend_idx) {
+            return;
+        }
+        let _ = self.pools[backend_idx].push(pooled);
+    }
+
+    fn is_reusable(&self, pooled: &PooledStream, now: Instant) -> bool {
+        now.duration_since(pooled.idle_since)
+            < Duration::from_secs(self.keep_alive.idle_timeout_sec)
+            && now.duration_since(pooled.created)
+                < Duration::from_secs(self.keep_alive.max_lifetime_sec)
+            && pooled.requests < self.keep_alive.max_requests
+    }
+}
+
+impl FastTcpPool for ConnectionPool {
+    // Only plain sockets can be handed out here; TLS groups go through `connect`.
+    async fn get_connection(&self, session_id: u64) -> Option<TcpStream> {
+        match self.acquire(session_id).await? {
+            (_, BackendStream::Plain(stream)) => Some(stream),
+            (_, BackendStream::Tls(_)) => None,
+        }
     }
 }
 
@@ -67,8 +562,9 @@ mod tests {
 
         assert_eq!(pool.backends.len(), 2);
         assert_eq!(pool.max_pool_size, 10);
-        assert_eq!(pool.current.load(Ordering::Relaxed), 0);
+        assert_eq!(pool.strategy.name(), "round_robin");
         assert_eq!(pool.pools.len(), 2);
+        assert!(pool.status.iter().all(|status| status.is_healthy()));
     }
 
     #[tokio::test]
@@ -104,9 +600,9 @@ mod tests {
         let pool = ConnectionPool::new(backends, 10);
 
         let stream = TcpStream::connect(addr).await.unwrap();
-        pool.return_connection(0, stream).await;
+        pool.return_connection(0, stream);
 
-        let pool_guard = pool.pools[0].lock().await;
+        let pool_guard = &pool.pools[0];
         assert_eq!(pool_guard.len(), 1);
     }
 
@@ -120,10 +616,10 @@ mod tests {
 
         for _ in 0..3 {
             let stream = TcpStream::connect(addr).await.unwrap();
-            pool.return_connection(0, stream).await;
+            pool.return_connection(0, stream);
         }
 
-        let pool_guard = pool.pools[0].lock().await;
+        let pool_guard = &pool.pools[0];
         assert_eq!(pool_guard.len(), 2);
     }
 
@@ -137,7 +633,7 @@ mod tests {
 
         let stream = TcpStream::connect(addr).await.unwrap();
         let local_addr = stream.local_addr().unwrap();
-        pool.return_connection(0, stream).await;
+        pool.return_connection(0, stream);
 
         let reused_stream = pool.get_connection(1).await.unwrap();
         assert_eq!(reused_stream.local_addr().unwrap(), local_addr);
@@ -154,7 +650,7 @@ mod tests {
         let conn = pool.get_connection(1).await.unwrap();
         assert_eq!(conn.peer_addr().unwrap(), addr);
 
-        let pool_guard = pool.pools[0].lock().await;
+        let pool_guard = &pool.pools[0];
         assert_eq!(pool_guard.len(), 0);
     }
 
@@ -191,4 +687,266 @@ mod tests {
         let result = pool.get_connection(1).await;
         assert!(result.is_none());
     }
+
+    #[tokio::test]
+    async fn get_connection_skips_unhealthy_backend_test() {
+        let listener1 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr1 = listener1.local_addr().unwrap();
+
+        let listener2 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr2 = listener2.local_addr().unwrap();
+
+        let backends = vec![
+            ConnString::new(addr1.ip().to_string(), addr1.port()),
+            ConnString::new(addr2.ip().to_string(), addr2.port()),
+        ];
+        let pool = ConnectionPool::new(backends, 10);
+        pool.status[0].record_probe(false, 1, 1);
+
+        for session_id in 0..3 {
+            let conn = pool.get_connection(session_id).await.unwrap();
+            assert_eq!(conn.peer_addr().unwrap(), addr2);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_connection_none_when_all_unhealthy_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let backends = vec![ConnString::new(addr.ip().to_string(), addr.port())];
+        let pool = ConnectionPool::new(backends, 10);
+        pool.status[0].record_probe(false, 1, 1);
+
+        assert!(pool.get_connection(1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn connect_failures_eject_backend_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let backends = vec![
+            ConnString::new("127.0.0.1".to_string(), 9999),
+            ConnString::new(addr.ip().to_string(), addr.port()),
+        ];
+        let mut pool = ConnectionPool::new(backends, 10);
+        pool.outlier_detection(OutlierDetectionConfig {
+            consecutive_failures: 1,
+            base_ejection_ms: 60_000,
+            max_ejection_ms: 60_000,
+            max_ejection_percent: 50,
+        });
+
+        assert!(pool.get_connection(1).await.is_none());
+        assert!(!pool.is_available(0));
+
+        for session_id in 2..5 {
+            let (backend_idx, _) = pool.acquire(session_id).await.unwrap();
+            assert_eq!(backend_idx, 1);
+        }
+    }
+
+    #[test]
+    fn select_excludes_backends_test() {
+        let backends = vec![
+            ConnString::new("127.0.0.1".to_string(), 8080),
+            ConnString::new("127.0.0.1".to_string(), 8081),
+            ConnString::new("127.0.0.1".to_string(), 8082),
+        ];
+        let pool = ConnectionPool::new(backends, 10);
+
+        for session_id in 0..3 {
+            assert_eq!(pool.select(session_id, &[0, 2]), Some(1));
+        }
+        assert_eq!(pool.select(4, &[0, 1, 2]), None);
+    }
+
+    #[test]
+    fn select_uses_configured_strategy_test() {
+        let backends = vec![
+            ConnString::new("127.0.0.1".to_string(), 8080),
+            ConnString::new("127.0.0.1".to_string(), 8081),
+        ];
+        let mut pool = ConnectionPool::new(backends, 10);
+        pool.strategy(StrategyKind::LeastConnections, &HashingConfig::default());
+
+        let _active = pool.status[0].track();
+        for session_id in 0..3 {
+            assert_eq!(pool.select(session_id, &[]), Some(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn get_connection_session_affinity_test() {
+        let listener1 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr1 = listener1.local_addr().unwrap();
+
+        let listener2 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr2 = listener2.local_addr().unwrap();
+
+        let backends = vec![
+            ConnString::new(addr1.ip().to_string(), addr1.port()),
+            ConnString::new(addr2.ip().to_string(), addr2.port()),
+        ];
+        let mut pool = ConnectionPool::new(backends, 10);
+        pool.strategy(StrategyKind::ConsistentHash, &HashingConfig::default());
+
+        for session_id in 0..10 {
+            let first = pool.get_connection(session_id).await.unwrap();
+            let second = pool.get_connection(session_id).await.unwrap();
+            assert_eq!(first.peer_addr().unwrap(), second.peer_addr().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn rebuild_keeps_existing_backends_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let kept = ConnString::new(addr.ip().to_string(), addr.port());
+        let removed = ConnString::new("127.0.0.1".to_string(), 3001);
+        let pool = ConnectionPool::new(vec![removed, kept.clone()], 10);
+        pool.status[1].eject(Duration::from_secs(60), false);
+        pool.return_connection(1, TcpStream::connect(addr).await.unwrap());
+
+        let added = ConnString::new("127.0.0.1".to_string(), 3002);
+        let rebuilt = pool.rebuild(
+            vec![
+                added.clone(),
+                ConnString::new_from_address(&kept.address())
+                    .unwrap()
+                    .with_weight(3),
+            ],
+            5,
+        );
+
+        assert_eq!(rebuilt.backends.len(), 2);
+        assert_eq!(rebuilt.backends[0].get_uuid(), added.get_uuid());
+        assert_eq!(rebuilt.backends[1].get_uuid(), kept.get_uuid());
+        assert_eq!(rebuilt.backends[1].get_weight(), 3);
+        assert!(Arc::ptr_eq(&rebuilt.status[1], &pool.status[1]));
+        assert!(!rebuilt.is_available(1));
+        assert!(Arc::ptr_eq(&rebuilt.pools[1], &pool.pools[1]));
+        assert_eq!(rebuilt.pools[1].len(), 1);
+        assert_eq!(rebuilt.max_pool_size, 5);
+        assert_eq!(rebuilt.position("127.0.0.1:3001"), None);
+    }
+
+    #[tokio::test]
+    async fn connect_reuses_newest_stream_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let pool = ConnectionPool::new(
+            vec![ConnString::new(addr.ip().to_string(), addr.port())],
+            10,
+        );
+        let mut local_addrs = Vec::new();
+        for _ in 0..3 {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            local_addrs.push(stream.local_addr().unwrap());
+            pool.return_connection(0, stream);
+        }
+
+        for expected in local_addrs.iter().rev() {
+            let pooled = pool.connect(0, None).await.unwrap();
+            assert_eq!(pooled.stream.tcp().local_addr().unwrap(), *expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn rebuild_shrinks_idle_streams_in_place_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let backend = ConnString::new(addr.ip().to_string(), addr.port());
+        let pool = ConnectionPool::new(vec![backend.clone()], 10);
+        let mut newest = None;
+        for _ in 0..3 {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            newest = Some(stream.local_addr().unwrap());
+            pool.return_connection(0, stream);
+        }
+
+        let rebuilt = pool.rebuild(vec![backend], 2);
+        assert_eq!(rebuilt.pools[0].len(), 2);
+        let pooled = rebuilt.connect(0, None).await.unwrap();
+        assert_eq!(pooled.stream.tcp().local_addr().unwrap(), newest.unwrap());
+
+        // Streams released through the old pool stay reachable from the rebuilt one.
+        pool.release(0, pooled);
+        assert_eq!(rebuilt.pools[0].len(), 2);
+        pool.release(
+            0,
+            PooledStream::new(BackendStream::Plain(
+                TcpStream::connect(addr).await.unwrap(),
+            )),
+        );
+        assert_eq!(rebuilt.pools[0].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn release_respects_max_requests_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut pool = ConnectionPool::new(
+            vec![ConnString::new(addr.ip().to_string(), addr.port())],
+            10,
+        );
+        pool.keep_alive(KeepAliveConfig {
+            max_requests: 2,
+            ..KeepAliveConfig::default()
+        });
+
+        let pooled = pool.connect(0, None).await.unwrap();
+        pool.release(0, pooled);
+        let pooled = pool.connect(0, None).await.unwrap();
+        assert_eq!(pooled.requests, 1);
+        pool.release(0, pooled);
+
+        assert!(pool.pools[0].is_empty());
+    }
+
+    #[tokio::test]
+    async fn connect_skips_closed_streams_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let pool = ConnectionPool::new(
+            vec![ConnString::new(addr.ip().to_string(), addr.port())],
+            10,
+        );
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        pool.return_connection(0, stream);
+        drop(accepted);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        pool.connect(0, None).await.unwrap();
+        assert_eq!(pool.metrics[0].pool_hits.load(Ordering::Relaxed), 0);
+        assert_eq!(pool.metrics[0].pool_misses.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn connect_records_metrics_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let dead = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_addr = dead.local_addr().unwrap();
+        drop(dead);
+
+        let backends = vec![
+            ConnString::new(addr.ip().to_string(), addr.port()),
+            ConnString::new(dead_addr.ip().to_string(), dead_addr.port()),
+        ];
+        let pool = ConnectionPool::new(backends, 10);
+        let pooled = pool.connect(0, None).await.unwrap();
+        pool.release(0, pooled);
+        pool.connect(0, None).await.unwrap();
+        assert!(pool.connect(1, None).await.is_none());
+
+        let metrics = &pool.metrics[0];
+        assert_eq!(metrics.pool_hits.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.pool_misses.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.connections.load(Ordering::Relaxed), 2);
+        assert_eq!(metrics.connect_latency.count(), 1);
+        assert_eq!(pool.metrics[1].connect_failures.load(Ordering::Relaxed), 1);
+    }
 }
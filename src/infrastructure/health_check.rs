@@ -0,0 +1,429 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use http_body_util::{BodyExt, Full};
+use hyper::client::conn::http2;
+use hyper::{Request, StatusCode, header};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use log::{info, warn};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, MissedTickBehavior, interval, timeout};
+
+use crate::config::health_check::{HealthCheckConfig, ProbeKind};
+use crate::domain::backend_conn::ConnString;
+use crate::domain::backend_status::HealthState;
+use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+use crate::infrastructure::tls::{ALPN_H2, BackendStream, TlsOriginator};
+
+const GRPC_HEALTH_CHECK: &str = "/grpc.health.v1.Health/Check";
+// HealthCheckResponse.ServingStatus.SERVING
+const GRPC_SERVING: u64 = 1;
+
+pub struct HealthChecker {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl HealthChecker {
+    pub fn start(pool: ConnectionPool, config: HealthCheckConfig) -> HealthChecker {
+        let tasks = (0..pool.backends.len())
+            .map(|backend_idx| {
+                let pool = pool.clone();
+                let config = config.clone();
+                tokio::spawn(async move { check_loop(pool, backend_idx, config).await })
+            })
+            .collect();
+        HealthChecker { tasks }
+    }
+
+    pub fn stop(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for HealthChecker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn check_loop(pool: ConnectionPool, backend_idx: usize, config: HealthCheckConfig) {
+    let backend = &pool.backends[backend_idx];
+    let status = &pool.status[backend_idx];
+    let mut ticker = interval(Duration::from_millis(config.interval_ms));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        let success = probe(backend, &config, pool.tls.as_deref()).await;
+        match status.record_probe(success, config.rise, config.fall) {
+            Some(HealthState::Healthy) => {
+                info!("Backend {} is healthy again", backend.address());
+            }
+            Some(HealthState::Unhealthy) => {
+                warn!("Backend {} marked unhealthy", backend.address());
+                pool.clear_idle(backend_idx);
+            }
+            None => {}
+        }
+    }
+}
+
+// On TLS groups a TCP probe also has to complete the handshake.
+pub async fn probe(
+    backend: &ConnString,
+    config: &HealthCheckConfig,
+    tls: Option<&TlsOriginator>,
+) -> bool {
+    let probe_timeout = Duration::from_millis(config.timeout_ms);
+    let result = match config.kind {
+        ProbeKind::Tcp => timeout(probe_timeout, connect(backend, tls))
+            .await
+            .map(|conn| conn.is_some()),
+        ProbeKind::Http => timeout(probe_timeout, http_probe(backend, config, tls))
+            .await
+            .map(|status| status == Some(config.expected_status)),
+        ProbeKind::Grpc => timeout(probe_timeout, grpc_probe(backend, config, tls))
+            .await
+            .map(|status| status == Some(GRPC_SERVING)),
+    };
+    result.unwrap_or(false)
+}
+
+async fn connect(backend: &ConnString, tls: Option<&TlsOriginator>) -> Option<BackendStream> {
+    dial(backend, tls, &[]).await
+}
+
+async fn dial(
+    backend: &ConnString,
+    tls: Option<&TlsOriginator>,
+    alpn: &[&[u8]],
+) -> Option<BackendStream> {
+    let stream = TcpStream::connect(backend.address()).await.ok()?;
+    match tls {
+        Some(tls) => tls.connect(stream, backend.get_host(), alpn).await.ok(),
+        None => Some(BackendStream::Plain(stream)),
+    }
+}
+
+// The serving status from the standard gRPC health service; None unless the call succeeded.
+async fn grpc_probe(
+    backend: &ConnString,
+    config: &HealthCheckConfig,
+    tls: Option<&TlsOriginator>,
+) -> Option<u64> {
+    let stream = dial(backend, tls, &[ALPN_H2]).await?;
+    let (mut sender, connection) = http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+        .await
+        .ok()?;
+    tokio::spawn(connection);
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let request = Request::post(format!(
+        "{}://{}{}",
+        scheme,
+        backend.address(),
+        GRPC_HEALTH_CHECK
+    ))
+    .header(header::CONTENT_TYPE, "application/grpc")
+    .header(header::TE, "trailers")
+    .header(header::USER_AGENT, "load-balancer-health-check")
+    .body(Full::new(health_check_request(&config.grpc_service)))
+    .ok()?;
+    let response = sender.send_request(request).await.ok()?;
+    if response.status() != StatusCode::OK {
+        return None;
+    }
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await.ok()?;
+    // A trailers-only answer carries grpc-status in the headers.
+    let grpc_status = body
+        .trailers()
+        .unwrap_or(&parts.headers)
+        .get("grpc-status")?
+        .clone();
+    if grpc_status != "0" {
+        return None;
+    }
+    serving_status(&body.to_bytes())
+}
+
+// A length-prefixed HealthCheckRequest { string service = 1; }.
+fn health_check_request(service: &str) -> Bytes {
+    let mut message = BytesMut::new();
+    if !service.is_empty() {
+        message.put_u8(0x0a);
+        put_varint(&mut message, service.len() as u64);
+        message.put_slice(service.as_bytes());
+    }
+    let mut frame = BytesMut::with_capacity(5 + message.len());
+    frame.put_u8(0);
+    frame.put_u32(message.len() as u32);
+    frame.put_slice(&message);
+    frame.freeze()
+}
+
+// Reads `status` (field 1) from a length-prefixed HealthCheckResponse.
+fn serving_status(frame: &[u8]) -> Option<u64> {
+    let ([compressed, len @ ..], message) = frame.split_first_chunk::<5>()?;
+    if *compressed != 0 {
+        return None;
+    }
+    let mut message = message.get(..u32::from_be_bytes(*len) as usize)?;
+    let mut status = 0;
+    while !message.is_empty() {
+        let key = read_varint(&mut message)?;
+        match (key >> 3, key & 0x7) {
+            (1, 0) => status = read_varint(&mut message)?,
+            (_, 0) => {
+                read_varint(&mut message)?;
+            }
+            (_, 2) => {
+                let len = read_varint(&mut message)? as usize;
+                message = message.get(len..)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(status)
+}
+
+fn put_varint(buf: &mut BytesMut, mut value: u64) {
+    while value >= 0x80 {
+        buf.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+async fn http_probe(
+    backend: &ConnString,
+    config: &HealthCheckConfig,
+    tls: Option<&TlsOriginator>,
+) -> Option<u16> {
+    let mut stream = connect(backend, tls).await?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: load-balancer-health-check\r\nConnection: close\r\n\r\n",
+        config.http_path,
+        backend.address()
+    );
+    stream.write_all(request.as_bytes()).await.ok()?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut status_line)
+        .await
+        .ok()?;
+    parse_status_code(&status_line)
+}
+
+fn parse_status_code(status_line: &str) -> Option<u16> {
+    let mut parts = status_line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::listener::ListenerMode;
+    use crate::infrastructure::tls::TlsTerminator;
+    use crate::infrastructure::tls::tests::{backend_tls, certificate, tls_config};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn config(kind: ProbeKind) -> HealthCheckConfig {
+        HealthCheckConfig {
+            kind,
+            interval_ms: 20,
+            timeout_ms: 20,
+            rise: 1,
+            fall: 1,
+            http_path: "/health".to_string(),
+            expected_status: 200,
+            grpc_service: String::new(),
+        }
+    }
+
+    async fn http_backend(status_line: &'static str) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream.write_all(status_line.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn parse_status_code_test() {
+        assert_eq!(parse_status_code("HTTP/1.1 204 No Content\r\n"), Some(204));
+        assert_eq!(parse_status_code("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn tcp_probe_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let backend = ConnString::new(addr.ip().to_string(), addr.port());
+        assert!(probe(&backend, &config(ProbeKind::Tcp), None).await);
+
+        drop(listener);
+        assert!(!probe(&backend, &config(ProbeKind::Tcp), None).await);
+    }
+
+    #[tokio::test]
+    async fn http_probe_expected_status_test() {
+        let ok = http_backend("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let failing = http_backend("HTTP/1.1 500 Internal Server Error\r\n\r\n").await;
+
+        let ok_backend = ConnString::new(ok.ip().to_string(), ok.port());
+        let failing_backend = ConnString::new(failing.ip().to_string(), failing.port());
+
+        assert!(probe(&ok_backend, &config(ProbeKind::Http), None).await);
+        assert!(!probe(&failing_backend, &config(ProbeKind::Http), None).await);
+    }
+
+    #[tokio::test]
+    async fn http_probe_over_tls_test() {
+        let (cert, _) = certificate(&["backend.internal"], &[]);
+        let tls = TlsTerminator::new(&tls_config(vec![cert.clone()]), ListenerMode::Http).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut stream) = tls.accept(stream).await else {
+                    continue;
+                };
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await;
+            }
+        });
+        let backend = ConnString::new(addr.ip().to_string(), addr.port());
+        let mut config = config(ProbeKind::Http);
+        config.timeout_ms = 1000;
+
+        let originator =
+            TlsOriginator::new(&backend_tls(&cert.cert, Some("backend.internal"))).unwrap();
+        assert!(probe(&backend, &config, Some(&originator)).await);
+        // A plaintext request never gets an HTTP status line back.
+        assert!(!probe(&backend, &config, None).await);
+        let untrusted = TlsOriginator::new(&backend_tls(&cert.cert, None)).unwrap();
+        assert!(!probe(&backend, &config, Some(&untrusted)).await);
+    }
+
+    #[test]
+    fn health_check_messages_test() {
+        assert_eq!(&health_check_request("")[..], [0, 0, 0, 0, 0]);
+        assert_eq!(
+            &health_check_request("db")[..],
+            [0, 0, 0, 0, 4, 0x0a, 2, b'd', b'b']
+        );
+        assert_eq!(
+            serving_status(&[0, 0, 0, 0, 2, 0x08, 1]),
+            Some(GRPC_SERVING)
+        );
+        // Unknown fields are skipped; an empty message means UNKNOWN.
+        assert_eq!(
+            serving_status(&[0, 0, 0, 0, 5, 0x12, 1, b'x', 0x08, 2]),
+            Some(2)
+        );
+        assert_eq!(serving_status(&[0, 0, 0, 0, 0]), Some(0));
+        assert_eq!(serving_status(&[0, 0, 0, 0, 2, 0x08]), None);
+    }
+
+    // Serves grpc.health.v1.Health: "" is serving, "down" is not, anything else is unknown.
+    async fn grpc_backend() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let service = hyper::service::service_fn(
+                    |request: Request<hyper::body::Incoming>| async move {
+                        assert_eq!(request.uri().path(), GRPC_HEALTH_CHECK);
+                        let request = request.into_body().collect().await?.to_bytes();
+                        let mut trailers = hyper::HeaderMap::new();
+                        let (status, grpc_status) = match &request[5..] {
+                            [] => (1, "0"),
+                            [0x0a, 4, b'd', b'o', b'w', b'n'] => (2, "0"),
+                            _ => (0, "5"),
+                        };
+                        trailers.insert("grpc-status", grpc_status.parse().unwrap());
+                        let frame = Bytes::from(vec![0, 0, 0, 0, 2, 0x08, status]);
+                        let body = Full::new(frame)
+                            .with_trailers(async move { Some(Ok(trailers)) })
+                            .boxed();
+                        let response = hyper::Response::builder()
+                            .header(header::CONTENT_TYPE, "application/grpc")
+                            .body(body)
+                            .unwrap();
+                        Ok::<_, hyper::Error>(response)
+                    },
+                );
+                tokio::spawn(
+                    hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service),
+                );
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn grpc_probe_test() {
+        let addr = grpc_backend().await;
+        let backend = ConnString::new(addr.ip().to_string(), addr.port());
+        let mut config = config(ProbeKind::Grpc);
+        config.timeout_ms = 1000;
+        assert!(probe(&backend, &config, None).await);
+
+        config.grpc_service = "down".to_string();
+        assert!(!probe(&backend, &config, None).await);
+        config.grpc_service = "missing".to_string();
+        assert!(!probe(&backend, &config, None).await);
+
+        // HTTP/1.1-only backends fail the HTTP/2 handshake.
+        let http = http_backend("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let backend = ConnString::new(http.ip().to_string(), http.port());
+        assert!(!probe(&backend, &config, None).await);
+    }
+
+    #[tokio::test]
+    async fn checker_marks_dead_backend_unhealthy_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let pool = ConnectionPool::new(
+            vec![ConnString::new(addr.ip().to_string(), addr.port())],
+            10,
+        );
+        let _checker = HealthChecker::start(pool.clone(), config(ProbeKind::Tcp));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!pool.is_available(0));
+
+        let _listener = TcpListener::bind(addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(pool.is_available(0));
+    }
+}
@@ -0,0 +1,489 @@
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::domain::request::{Request, Status};
+use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+use crate::infrastructure::request_log::RequestLog;
+
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.025, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 1800.0,
+];
+
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct BackendMetrics {
+    pub connections: AtomicU64,
+    pub connect_failures: AtomicU64,
+    pub timeouts: AtomicU64,
+    pub pool_hits: AtomicU64,
+    pub pool_misses: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub bytes_received: AtomicU64,
+    pub connect_latency: Histogram,
+    pub session_duration: Histogram,
+}
+
+#[derive(Debug)]
+pub struct ListenerMetrics {
+    pub accepted: AtomicU64,
+    active: AtomicUsize,
+    pub bytes_received: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub session_duration: Histogram,
+    requests_completed: AtomicU64,
+    requests_failed: AtomicU64,
+    pub tls_handshake_failures: AtomicU64,
+    requests: Arc<RequestLog>,
+}
+
+// Keeps a listener's active connection gauge raised for as long as it is alive.
+pub struct ListenerConnection {
+    metrics: Arc<ListenerMetrics>,
+}
+
+pub struct MetricsRegistry {
+    listeners: Mutex<Vec<(String, Arc<ListenerMetrics>)>>,
+    requests: Arc<RequestLog>,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = self.bounds.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, bound, cumulative
+            );
+        }
+        let count = self.count();
+        let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, count);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, count);
+    }
+}
+
+impl BackendMetrics {
+    pub fn new() -> BackendMetrics {
+        BackendMetrics {
+            connections: AtomicU64::new(0),
+            connect_failures: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            pool_hits: AtomicU64::new(0),
+            pool_misses: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            connect_latency: Histogram::new(&LATENCY_BUCKETS),
+            session_duration: Histogram::new(&DURATION_BUCKETS),
+        }
+    }
+}
+
+impl ListenerMetrics {
+    pub fn new(requests: Arc<RequestLog>) -> ListenerMetrics {
+        ListenerMetrics {
+            accepted: AtomicU64::new(0),
+            active: AtomicUsize::new(0),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            session_duration: Histogram::new(&DURATION_BUCKETS),
+            requests_completed: AtomicU64::new(0),
+            requests_failed: AtomicU64::new(0),
+            tls_handshake_failures: AtomicU64::new(0),
+            requests,
+        }
+    }
+
+    pub fn record(&self, request: Request) {
+        match request.get_status() {
+            Status::Completed => self.requests_completed.fetch_add(1, Ordering::Relaxed),
+            _ => self.requests_failed.fetch_add(1, Ordering::Relaxed),
+        };
+        self.requests.record(request);
+    }
+
+    pub fn track(self: &Arc<Self>) -> ListenerConnection {
+        self.accepted.fetch_add(1, Ordering::Relaxed);
+        self.active.fetch_add(1, Ordering::Relaxed);
+        ListenerConnection {
+            metrics: Arc::clone(self),
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for ListenerConnection {
+    fn drop(&mut self) {
+        self.metrics.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl MetricsRegistry {
+    pub fn new(requests: Arc<RequestLog>) -> MetricsRegistry {
+        MetricsRegistry {
+            listeners: Mutex::new(Vec::new()),
+            requests,
+        }
+    }
+
+    pub fn requests(&self) -> &RequestLog {
+        &self.requests
+    }
+
+    pub fn listener(&self, address: &str) -> Arc<ListenerMetrics> {
+        let mut listeners = self.listeners.lock().unwrap();
+        if let Some((_, metrics)) = listeners.iter().find(|(name, _)| name == address) {
+            return Arc::clone(metrics);
+        }
+        let metrics = Arc::new(ListenerMetrics::new(Arc::clone(&self.requests)));
+        listeners.push((address.to_string(), Arc::clone(&metrics)));
+        metrics
+    }
+
+    // Prometheus text format; every family is written in one block as the format requires.
+    pub fn render(&self, pools: &[(&str, &ConnectionPool)]) -> String {
+        let mut out = String::new();
+        let listeners = self.listeners.lock().unwrap().clone();
+        let listener_labels: Vec<(String, &ListenerMetrics)> = listeners
+            .iter()
+            .map(|(address, metrics)| (format!("listener=\"{}\"", escape(address)), &**metrics))
+            .collect();
+        let listener_counter =
+            |out: &mut String, name, help, value: fn(&ListenerMetrics) -> u64| {
+                family(out, name, "counter", help);
+                for (labels, metrics) in &listener_labels {
+                    sample(out, name, labels, value(metrics));
+                }
+            };
+        listener_counter(
+            &mut out,
+            "lb_listener_connections_accepted_total",
+            "Client connections accepted.",
+            |metrics| metrics.accepted.load(Ordering::Relaxed),
+        );
+        listener_counter(
+            &mut out,
+            "lb_listener_bytes_received_total",
+            "Bytes received from clients.",
+            |metrics| metrics.bytes_received.load(Ordering::Relaxed),
+        );
+        listener_counter(
+            &mut out,
+            "lb_listener_bytes_sent_total",
+            "Bytes sent to clients.",
+            |metrics| metrics.bytes_sent.load(Ordering::Relaxed),
+        );
+        listener_counter(
+            &mut out,
+            "lb_listener_tls_handshake_failures_total",
+            "TLS handshakes that failed or timed out.",
+            |metrics| metrics.tls_handshake_failures.load(Ordering::Relaxed),
+        );
+        family(
+            &mut out,
+            "lb_listener_requests_total",
+            "counter",
+            "Finished TCP sessions and HTTP requests by outcome.",
+        );
+        for (labels, metrics) in &listener_labels {
+            for (status, value) in [
+                ("completed", &metrics.requests_completed),
+                ("failed", &metrics.requests_failed),
+            ] {
+                sample(
+                    &mut out,
+                    "lb_listener_requests_total",
+                    &format!("{},status=\"{}\"", labels, status),
+                    value.load(Ordering::Relaxed),
+                );
+            }
+        }
+        family(
+            &mut out,
+            "lb_listener_connections_active",
+            "gauge",
+            "Client connections currently open.",
+        );
+        for (labels, metrics) in &listener_labels {
+            sample(
+                &mut out,
+                "lb_listener_connections_active",
+                labels,
+                metrics.active(),
+            );
+        }
+        family(
+            &mut out,
+            "lb_listener_session_duration_seconds",
+            "histogram",
+            "Duration of TCP sessions and HTTP requests.",
+        );
+        for (labels, metrics) in &listener_labels {
+            metrics.session_duration.render(
+                &mut out,
+                "lb_listener_session_duration_seconds",
+                labels,
+            );
+        }
+
+        let backends: Vec<(String, &ConnectionPool, usize)> = pools
+            .iter()
+            .flat_map(|(group, pool)| {
+                pool.backends.iter().enumerate().map(move |(idx, backend)| {
+                    let labels = format!(
+                        "group=\"{}\",backend=\"{}\"",
+                        escape(group),
+                        escape(&backend.address())
+                    );
+                    (labels, *pool, idx)
+                })
+            })
+            .collect();
+        let backend_counter = |out: &mut String, name, help, value: fn(&BackendMetrics) -> u64| {
+            family(out, name, "counter", help);
+            for (labels, pool, idx) in &backends {
+                sample(out, name, labels, value(&pool.metrics[*idx]));
+            }
+        };
+        backend_counter(
+            &mut out,
+            "lb_backend_connections_total",
+            "Connections handed to the backend.",
+            |metrics| metrics.connections.load(Ordering::Relaxed),
+        );
+        backend_counter(
+            &mut out,
+            "lb_backend_connect_failures_total",
+            "Failed connection attempts.",
+            |metrics| metrics.connect_failures.load(Ordering::Relaxed),
+        );
+        backend_counter(
+            &mut out,
+            "lb_backend_timeouts_total",
+            "Sessions or requests that hit the request timeout.",
+            |metrics| metrics.timeouts.load(Ordering::Relaxed),
+        );
+        backend_counter(
+            &mut out,
+            "lb_backend_pool_hits_total",
+            "Connections served from the idle pool.",
+            |metrics| metrics.pool_hits.load(Ordering::Relaxed),
+        );
+        backend_counter(
+            &mut out,
+            "lb_backend_pool_misses_total",
+            "Connections that had to be opened.",
+            |metrics| metrics.pool_misses.load(Ordering::Relaxed),
+        );
+        backend_counter(
+            &mut out,
+            "lb_backend_bytes_sent_total",
+            "Bytes sent to the backend.",
+            |metrics| metrics.bytes_sent.load(Ordering::Relaxed),
+        );
+        backend_counter(
+            &mut out,
+            "lb_backend_bytes_received_total",
+            "Bytes received from the backend.",
+            |metrics| metrics.bytes_received.load(Ordering::Relaxed),
+        );
+        let backend_gauge =
+            |out: &mut String, name, help, value: fn(&ConnectionPool, usize) -> usize| {
+                family(out, name, "gauge", help);
+                for (labels, pool, idx) in &backends {
+                    sample(out, name, labels, value(pool, *idx));
+                }
+            };
+        backend_gauge(
+            &mut out,
+            "lb_backend_connections_active",
+            "Connections currently open to the backend.",
+            |pool, idx| pool.status[idx].active(),
+        );
+        backend_gauge(
+            &mut out,
+            "lb_backend_healthy",
+            "1 when health checks pass.",
+            |pool, idx| pool.status[idx].is_healthy() as usize,
+        );
+        backend_gauge(
+            &mut out,
+            "lb_backend_ejected",
+            "1 while outlier detection has the backend ejected.",
+            |pool, idx| pool.status[idx].is_ejected() as usize,
+        );
+        backend_gauge(
+            &mut out,
+            "lb_backend_pool_idle",
+            "Idle connections waiting in the pool.",
+            |pool, idx| pool.pools[idx].len(),
+        );
+        family(
+            &mut out,
+            "lb_group_queued_requests",
+            "gauge",
+            "Connections or requests waiting for a backend below its connection limit.",
+        );
+        for (group, pool) in pools {
+            let labels = format!("group=\"{}\"", escape(group));
+            sample(
+                &mut out,
+                "lb_group_queued_requests",
+                &labels,
+                pool.queue.waiting(),
+            );
+        }
+        family(
+            &mut out,
+            "lb_backend_connect_duration_seconds",
+            "histogram",
+            "Time to open a new backend connection.",
+        );
+        for (labels, pool, idx) in &backends {
+            pool.metrics[*idx].connect_latency.render(
+                &mut out,
+                "lb_backend_connect_duration_seconds",
+                labels,
+            );
+        }
+        family(
+            &mut out,
+            "lb_backend_session_duration_seconds",
+            "histogram",
+            "Duration of TCP sessions and HTTP requests.",
+        );
+        for (labels, pool, idx) in &backends {
+            pool.metrics[*idx].session_duration.render(
+                &mut out,
+                "lb_backend_session_duration_seconds",
+                labels,
+            );
+        }
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: impl Display) {
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::backend_conn::ConnString;
+
+    #[test]
+    fn histogram_test() {
+        let histogram = Histogram::new(&LATENCY_BUCKETS);
+        histogram.observe(Duration::from_micros(400));
+        histogram.observe(Duration::from_millis(20));
+        histogram.observe(Duration::from_secs(10));
+
+        let mut out = String::new();
+        histogram.render(&mut out, "latency", "a=\"b\"");
+        assert!(out.contains("latency_bucket{a=\"b\",le=\"0.0005\"} 1\n"));
+        assert!(out.contains("latency_bucket{a=\"b\",le=\"0.025\"} 2\n"));
+        assert!(out.contains("latency_bucket{a=\"b\",le=\"2.5\"} 2\n"));
+        assert!(out.contains("latency_bucket{a=\"b\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("latency_sum{a=\"b\"} 10.0204\n"));
+        assert!(out.contains("latency_count{a=\"b\"} 3\n"));
+    }
+
+    #[test]
+    fn listener_connection_test() {
+        let registry = MetricsRegistry::new(Arc::new(RequestLog::new(10)));
+        let metrics = registry.listener("127.0.0.1:8080");
+        let connection = metrics.track();
+        assert_eq!(metrics.active(), 1);
+        drop(connection);
+        assert_eq!(metrics.active(), 0);
+        assert_eq!(metrics.accepted.load(Ordering::Relaxed), 1);
+        assert!(Arc::ptr_eq(&metrics, &registry.listener("127.0.0.1:8080")));
+    }
+
+    #[test]
+    fn render_test() {
+        let registry = MetricsRegistry::new(Arc::new(RequestLog::new(10)));
+        let listener = registry.listener("127.0.0.1:8080");
+        let _connection = listener.track();
+        let mut request = Request::new(0, "web".to_string(), uuid::Uuid::new_v4());
+        request.set_status(Status::Failed);
+        listener.record(request);
+        let pool = ConnectionPool::new(
+            vec![
+                ConnString::new("127.0.0.1".to_string(), 3000),
+                ConnString::new("127.0.0.1".to_string(), 3001),
+            ],
+            10,
+        );
+        pool.metrics[1].pool_hits.fetch_add(4, Ordering::Relaxed);
+        pool.status[0].record_probe(false, 1, 1);
+
+        let out = registry.render(&[("web", &pool)]);
+        assert!(out.contains("lb_listener_connections_active{listener=\"127.0.0.1:8080\"} 1\n"));
+        assert!(out.contains(
+            "lb_listener_requests_total{listener=\"127.0.0.1:8080\",status=\"failed\"} 1\n"
+        ));
+        assert_eq!(registry.requests().recent(10, None).len(), 1);
+        assert!(
+            out.contains(
+                "lb_backend_pool_hits_total{group=\"web\",backend=\"127.0.0.1:3001\"} 4\n"
+            )
+        );
+        assert!(out.contains("lb_backend_healthy{group=\"web\",backend=\"127.0.0.1:3000\"} 0\n"));
+        assert_eq!(out.matches("# TYPE lb_backend_healthy gauge").count(), 1);
+        assert!(out.contains("lb_group_queued_requests{group=\"web\"} 0\n"));
+    }
+}
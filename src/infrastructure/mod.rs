@@ -1,3 +1,13 @@
+pub mod access_log;
+pub mod balancing;
+pub mod connection_limit;
+pub mod consistent_hash;
 pub mod fast_tcp_pool;
+pub mod health_check;
+pub mod metrics;
+pub mod outlier_detection;
+pub mod rate_limit;
+pub mod request_log;
 pub mod smart_tcp_pool;
 pub mod tcp_round_pool;
+pub mod tls;
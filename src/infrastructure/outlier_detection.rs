@@ -0,0 +1,122 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::outlier_detection::OutlierDetectionConfig;
+use crate::domain::backend_status::BackendStatus;
+
+#[derive(Debug)]
+pub struct OutlierDetector {
+    config: OutlierDetectionConfig,
+}
+
+impl OutlierDetector {
+    pub fn new(config: OutlierDetectionConfig) -> OutlierDetector {
+        OutlierDetector { config }
+    }
+
+    // Returns the ejection period when this failure got the backend ejected.
+    pub fn record_failure(
+        &self,
+        statuses: &[Arc<BackendStatus>],
+        backend_idx: usize,
+    ) -> Option<Duration> {
+        let status = &statuses[backend_idx];
+        if status.is_ejected() {
+            return None;
+        }
+        if status.record_error() < self.config.consecutive_failures {
+            return None;
+        }
+
+        let ejected = statuses.iter().filter(|status| status.is_ejected()).count();
+        if (ejected + 1) * 100 > statuses.len() * self.config.max_ejection_percent as usize {
+            return None;
+        }
+
+        // A backend that stayed in rotation for a full max period starts over at the base period.
+        let reset = status
+            .since_last_ejection_ms()
+            .is_some_and(|ms| ms >= self.config.max_ejection_ms);
+        let previous = if reset { 0 } else { status.ejections() };
+        let duration = self.ejection_duration(previous);
+        status.eject(duration, reset);
+        Some(duration)
+    }
+
+    fn ejection_duration(&self, previous_ejections: u32) -> Duration {
+        let factor = 1u64.checked_shl(previous_ejections).unwrap_or(u64::MAX);
+        let ms = self
+            .config
+            .base_ejection_ms
+            .saturating_mul(factor)
+            .min(self.config.max_ejection_ms);
+        Duration::from_millis(ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_ejection_percent: u8) -> OutlierDetectionConfig {
+        OutlierDetectionConfig {
+            consecutive_failures: 2,
+            base_ejection_ms: 1000,
+            max_ejection_ms: 5000,
+            max_ejection_percent,
+        }
+    }
+
+    fn statuses(count: usize) -> Vec<Arc<BackendStatus>> {
+        (0..count).map(|_| Arc::new(BackendStatus::new())).collect()
+    }
+
+    #[test]
+    fn ejects_after_consecutive_failures_test() {
+        let detector = OutlierDetector::new(config(100));
+        let statuses = statuses(2);
+
+        assert_eq!(detector.record_failure(&statuses, 0), None);
+        assert_eq!(
+            detector.record_failure(&statuses, 0),
+            Some(Duration::from_millis(1000))
+        );
+        assert!(statuses[0].is_ejected());
+        assert!(!statuses[1].is_ejected());
+    }
+
+    #[test]
+    fn ejection_duration_grows_exponentially_test() {
+        let detector = OutlierDetector::new(config(100));
+        assert_eq!(detector.ejection_duration(0), Duration::from_millis(1000));
+        assert_eq!(detector.ejection_duration(1), Duration::from_millis(2000));
+        assert_eq!(detector.ejection_duration(2), Duration::from_millis(4000));
+        assert_eq!(detector.ejection_duration(3), Duration::from_millis(5000));
+        assert_eq!(detector.ejection_duration(64), Duration::from_millis(5000));
+    }
+
+    #[test]
+    fn max_ejection_percent_test() {
+        let detector = OutlierDetector::new(config(50));
+        let statuses = statuses(2);
+
+        detector.record_failure(&statuses, 0);
+        detector.record_failure(&statuses, 0);
+        detector.record_failure(&statuses, 1);
+        assert_eq!(detector.record_failure(&statuses, 1), None);
+
+        assert!(statuses[0].is_ejected());
+        assert!(!statuses[1].is_ejected());
+    }
+
+    #[test]
+    fn success_breaks_failure_streak_test() {
+        let detector = OutlierDetector::new(config(100));
+        let statuses = statuses(1);
+
+        detector.record_failure(&statuses, 0);
+        statuses[0].record_success();
+        assert_eq!(detector.record_failure(&statuses, 0), None);
+        assert!(!statuses[0].is_ejected());
+    }
+}
@@ -0,0 +1,208 @@
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use log::debug;
+use std::net::IpAddr;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::time::{MissedTickBehavior, interval};
+
+use crate::config::rate_limit::{RateLimitAction, RateLimitConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Admission {
+    Allow,
+    // A future token has been reserved; proceed once the delay has passed.
+    Delay(Duration),
+    // Carries how long until the next token, for `Retry-After`.
+    Deny(Duration),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// Per-client token buckets and connection counts, shared by every connection of a listener.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: DashMap<String, TokenBucket>,
+    connections: DashMap<IpAddr, usize>,
+}
+
+// Counts a connection against its client IP for as long as it is alive.
+#[derive(Debug)]
+pub struct ClientConnection {
+    limiter: Arc<RateLimiter>,
+    ip: IpAddr,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            config,
+            buckets: DashMap::new(),
+            connections: DashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    pub fn check(&self, key: &str) -> Admission {
+        let Some(rate) = self.config.requests_per_sec else {
+            return Admission::Allow;
+        };
+        let now = Instant::now();
+        let burst = self.config.burst();
+        let mut bucket = self.buckets.entry(key.to_string()).or_insert(TokenBucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Admission::Allow;
+        }
+        // Delayed clients borrow from the future, so the balance may go negative.
+        let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / rate);
+        if self.config.action == RateLimitAction::Delay
+            && wait <= Duration::from_millis(self.config.max_delay_ms)
+        {
+            bucket.tokens -= 1.0;
+            return Admission::Delay(wait);
+        }
+        Admission::Deny(wait)
+    }
+
+    pub fn connect(self: &Arc<Self>, ip: IpAddr) -> Option<ClientConnection> {
+        if let Some(max) = self.config.max_connections_per_ip {
+            let mut count = self.connections.entry(ip).or_insert(0);
+            if *count >= max {
+                return None;
+            }
+            *count += 1;
+        }
+        Some(ClientConnection {
+            limiter: Arc::clone(self),
+            ip,
+        })
+    }
+
+    // Buckets that refilled and stayed idle carry no state worth keeping.
+    pub fn sweep(&self) -> usize {
+        let now = Instant::now();
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_sec);
+        let rate = self.config.requests_per_sec.unwrap_or_default();
+        let burst = self.config.burst();
+        let before = self.buckets.len();
+        self.buckets.retain(|_, bucket| {
+            let idle = now.duration_since(bucket.updated);
+            idle < idle_timeout || bucket.tokens + idle.as_secs_f64() * rate < burst
+        });
+        before.saturating_sub(self.buckets.len())
+    }
+
+    pub fn start_sweeper(self: &Arc<Self>) {
+        tokio::spawn(sweep_loop(Arc::downgrade(self)));
+    }
+}
+
+impl Drop for ClientConnection {
+    fn drop(&mut self) {
+        if self.limiter.config.max_connections_per_ip.is_none() {
+            return;
+        }
+        if let Entry::Occupied(mut entry) = self.limiter.connections.entry(self.ip) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
+// Holds the limiter weakly so the sweeper stops once its listener is gone.
+async fn sweep_loop(limiter: Weak<RateLimiter>) {
+    let Some(idle_timeout_sec) = limiter
+        .upgrade()
+        .map(|limiter| limiter.config.idle_timeout_sec)
+    else {
+        return;
+    };
+    let mut ticker = interval(Duration::from_secs(idle_timeout_sec));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let Some(limiter) = limiter.upgrade() else {
+            return;
+        };
+        let swept = limiter.sweep();
+        if swept > 0 {
+            debug!("Expired {} idle rate limit buckets", swept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(contents: &str) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(toml::from_str(contents).unwrap()))
+    }
+
+    #[test]
+    fn token_bucket_test() {
+        let limiter = limiter("requests_per_sec = 1\nburst = 2");
+        assert_eq!(limiter.check("10.0.0.1"), Admission::Allow);
+        assert_eq!(limiter.check("10.0.0.1"), Admission::Allow);
+        assert!(
+            matches!(limiter.check("10.0.0.1"), Admission::Deny(wait) if wait > Duration::from_millis(900))
+        );
+        assert_eq!(limiter.check("10.0.0.2"), Admission::Allow);
+    }
+
+    #[test]
+    fn delay_reserves_tokens_test() {
+        let limiter =
+            limiter("requests_per_sec = 10\nburst = 1\naction = \"delay\"\nmax_delay_ms = 250");
+        assert_eq!(limiter.check("key"), Admission::Allow);
+        let Admission::Delay(first) = limiter.check("key") else {
+            panic!("expected a delay");
+        };
+        let Admission::Delay(second) = limiter.check("key") else {
+            panic!("expected a delay");
+        };
+        assert!(second > first);
+        assert!(matches!(limiter.check("key"), Admission::Deny(_)));
+    }
+
+    #[test]
+    fn connections_per_ip_test() {
+        let limiter = limiter("max_connections_per_ip = 2");
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let first = limiter.connect(ip).unwrap();
+        let _second = limiter.connect(ip).unwrap();
+        assert!(limiter.connect(ip).is_none());
+        assert!(limiter.connect("10.0.0.2".parse().unwrap()).is_some());
+        drop(first);
+        assert!(limiter.connect(ip).is_some());
+        assert_eq!(limiter.check("10.0.0.1"), Admission::Allow);
+    }
+
+    #[test]
+    fn sweep_test() {
+        let limiter = limiter("requests_per_sec = 1\nidle_timeout_sec = 1");
+        limiter.check("10.0.0.1");
+        assert_eq!(limiter.sweep(), 0);
+        limiter.buckets.get_mut("10.0.0.1").unwrap().updated -= Duration::from_secs(2);
+        assert_eq!(limiter.sweep(), 1);
+    }
+}
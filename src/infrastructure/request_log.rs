@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::domain::request::{Request, Status};
+use crate::infrastructure::access_log::AccessLog;
+
+// Keeps the most recent finished requests; the oldest entry is dropped once it is full.
+#[derive(Debug)]
+pub struct RequestLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<Request>>,
+    access_log: Option<AccessLog>,
+}
+
+impl RequestLog {
+    pub fn new(capacity: usize) -> RequestLog {
+        RequestLog {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            access_log: None,
+        }
+    }
+
+    pub fn access_log(&mut self, access_log: AccessLog) {
+        self.access_log = Some(access_log);
+    }
+
+    pub fn record(&self, request: Request) {
+        if let Some(access_log) = &self.access_log {
+            access_log.write(&request);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(request);
+    }
+
+    // Newest first.
+    pub fn recent(&self, limit: usize, status: Option<&Status>) -> Vec<Request> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|request| status.is_none_or(|status| request.get_status() == status))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn finished(id: u64, status: Status) -> Request {
+        let mut request = Request::new(id, format!("request-{}", id), Uuid::new_v4());
+        request.set_status(status);
+        request
+    }
+
+    #[test]
+    fn keeps_most_recent_test() {
+        let log = RequestLog::new(3);
+        for id in 0..5 {
+            log.record(finished(id, Status::Completed));
+        }
+        let targets: Vec<String> = log
+            .recent(10, None)
+            .iter()
+            .map(|request| request.get_target().to_string())
+            .collect();
+        assert_eq!(targets, vec!["request-4", "request-3", "request-2"]);
+        assert_eq!(log.recent(1, None)[0].get_target(), "request-4");
+    }
+
+    #[test]
+    fn filters_by_status_test() {
+        let log = RequestLog::new(10);
+        log.record(finished(0, Status::Completed));
+        log.record(finished(1, Status::Failed));
+        log.record(finished(2, Status::Completed));
+
+        let failed = log.recent(10, Some(&Status::Failed));
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].get_target(), "request-1");
+    }
+
+    #[test]
+    fn zero_capacity_records_nothing_test() {
+        let log = RequestLog::new(0);
+        log.record(finished(0, Status::Completed));
+        assert!(log.recent(10, None).is_empty());
+    }
+}
@@ -1,70 +1,224 @@
-use log::error;
-use std::collections::HashMap;
+use dashmap::DashMap;
+use log::{debug, error};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
+use tokio::time::{MissedTickBehavior, interval};
 use uuid::Uuid;
 
-use crate::domain::backend_conn::ConnString;
+use crate::config::sticky::StickyConfig;
 use crate::domain::tcp_conn_pool::SmartTcpConnectionPool;
+use crate::infrastructure::fast_tcp_pool::{ConnectionPool, PooledStream};
+use crate::infrastructure::tls::BackendStream;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
+struct StickyEntry {
+    backend_uuid: Uuid,
+    created_at: Instant,
+    last_seen: Instant,
+    // Set on every hit, cleared when the eviction hand passes over the pin.
+    referenced: bool,
+}
+
+// Pinned users in the order the eviction hand visits them.
+type Clock = Mutex<VecDeque<Uuid>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StickyAssignment {
+    pub user_id: String,
+    pub backend: Option<String>,
+    pub age_sec: u64,
+    pub idle_sec: u64,
+}
+
+#[derive(Clone)]
 pub struct SmartTcpConnPool {
-    available_backends: Vec<ConnString>,
-    user_session_map: HashMap<Uuid, Uuid>,
-    user_last_connection_time: HashMap<Uuid, std::time::Instant>,
-    backend_selector: usize,
+    pool: ConnectionPool,
+    user_session_map: Arc<DashMap<Uuid, StickyEntry>>,
+    clock: Arc<Clock>,
+    config: StickyConfig,
 }
 
 impl SmartTcpConnPool {
-    pub fn new(backends_hosts: Vec<ConnString>) -> Self {
+    pub fn new(pool: ConnectionPool, config: StickyConfig) -> Self {
         SmartTcpConnPool {
-            available_backends: backends_hosts,
-            user_session_map: std::collections::HashMap::new(),
-            user_last_connection_time: std::collections::HashMap::new(),
-            backend_selector: 0,
+            pool,
+            user_session_map: Arc::new(DashMap::new()),
+            clock: Arc::new(Mutex::new(VecDeque::new())),
+            config,
         }
     }
 
-    fn get_or_assign_backend(&mut self, user_id: Uuid) -> Uuid {
-        if let Some(&backend_uuid) = self.user_session_map.get(&user_id) {
-            backend_uuid
-        } else {
-            let backend_uuid = self.next_backend().get_uuid();
-            self.user_session_map.insert(user_id, backend_uuid);
-            self.user_last_connection_time
-                .insert(user_id, std::time::Instant::now());
-            backend_uuid
+    // Pins are stored by backend uuid, so they carry over to a rebuilt pool.
+    pub fn with_pool(&self, pool: ConnectionPool, config: StickyConfig) -> Self {
+        SmartTcpConnPool {
+            pool,
+            user_session_map: Arc::clone(&self.user_session_map),
+            clock: Arc::clone(&self.clock),
+            config,
         }
     }
 
-    fn next_backend(&mut self) -> &ConnString {
-        let current = self.backend_selector;
-        if current < self.available_backends.len() - 1 {
-            self.backend_selector = current + 1;
-            &self.available_backends[current]
-        } else {
-            self.backend_selector = 0;
-            &self.available_backends[0]
+    pub fn user_id(key: u64) -> Uuid {
+        Uuid::from_u64_pair(0, key)
+    }
+
+    // Returns the pinned backend, re-pinning when it expired, went unhealthy or was removed.
+    pub fn get_or_assign_backend(&self, user_id: Uuid) -> Option<usize> {
+        let now = Instant::now();
+        if let Some(mut entry) = self.user_session_map.get_mut(&user_id)
+            && !is_expired(&entry, &self.config, now)
+            && let Some(backend_idx) = self.backend_idx(entry.backend_uuid)
+            && self.pool.accepts_pinned(backend_idx)
+        {
+            entry.last_seen = now;
+            entry.referenced = true;
+            return Some(backend_idx);
         }
+
+        let backend_idx = self.pool.select(user_id.as_u64_pair().1, &[])?;
+        self.pin(user_id, backend_idx);
+        Some(backend_idx)
     }
-}
 
-impl SmartTcpConnectionPool for SmartTcpConnPool {
-    async fn get_connection(&mut self, user_id: Uuid) -> Option<TcpStream> {
-        let backend_uuid = self.get_or_assign_backend(user_id);
+    pub fn pin(&self, user_id: Uuid, backend_idx: usize) {
+        let now = Instant::now();
+        let entry = StickyEntry {
+            backend_uuid: self.pool.backends[backend_idx].get_uuid(),
+            created_at: now,
+            last_seen: now,
+            referenced: false,
+        };
+        if let Some(mut existing) = self.user_session_map.get_mut(&user_id) {
+            *existing = entry;
+            return;
+        }
+
+        // New users only go in under the clock lock, which keeps the table within max_entries.
+        let mut clock = self.clock.lock().unwrap();
+        if self.user_session_map.len() >= self.config.max_entries {
+            self.evict(&mut clock);
+        }
+        if self.user_session_map.insert(user_id, entry).is_none() {
+            clock.push_back(user_id);
+        }
+    }
+
+    pub fn assignments(&self) -> Vec<StickyAssignment> {
+        let now = Instant::now();
+        self.user_session_map
+            .iter()
+            .map(|entry| StickyAssignment {
+                user_id: entry.key().to_string(),
+                backend: self
+                    .backend_idx(entry.backend_uuid)
+                    .map(|backend_idx| self.pool.backends[backend_idx].address()),
+                age_sec: now.duration_since(entry.created_at).as_secs(),
+                idle_sec: now.duration_since(entry.last_seen).as_secs(),
+            })
+            .collect()
+    }
+
+    pub fn sweep(&self) -> usize {
+        sweep_expired(&self.user_session_map, &self.clock, &self.config)
+    }
 
-        let conn_string = self
-            .available_backends
+    pub fn start_sweeper(&self) {
+        tokio::spawn(sweep_loop(
+            Arc::downgrade(&self.user_session_map),
+            Arc::downgrade(&self.clock),
+            self.config.clone(),
+        ));
+    }
+
+    // Second chance: a pin hit since the hand last passed is spared once, anything else goes.
+    // The hand clears what it skips, so one eviction costs O(1) amortized.
+    fn evict(&self, clock: &mut VecDeque<Uuid>) {
+        let now = Instant::now();
+        while let Some(user_id) = clock.pop_front() {
+            let Some(mut entry) = self.user_session_map.get_mut(&user_id) else {
+                continue;
+            };
+            if entry.referenced && !is_expired(&entry, &self.config, now) {
+                entry.referenced = false;
+                clock.push_back(user_id);
+                continue;
+            }
+            drop(entry);
+            self.user_session_map.remove(&user_id);
+            return;
+        }
+    }
+
+    fn backend_idx(&self, backend_uuid: Uuid) -> Option<usize> {
+        self.pool
+            .backends
             .iter()
-            .find(|c| c.get_uuid() == backend_uuid)?
-            .clone();
+            .position(|backend| backend.get_uuid() == backend_uuid)
+    }
+}
+
+fn is_expired(entry: &StickyEntry, config: &StickyConfig, now: Instant) -> bool {
+    now.duration_since(entry.created_at) >= Duration::from_secs(config.ttl_sec)
+        || now.duration_since(entry.last_seen) >= Duration::from_secs(config.idle_timeout_sec)
+}
+
+fn sweep_expired(
+    sessions: &DashMap<Uuid, StickyEntry>,
+    clock: &Clock,
+    config: &StickyConfig,
+) -> usize {
+    let now = Instant::now();
+    let before = sessions.len();
+    sessions.retain(|_, entry| !is_expired(entry, config, now));
+    let swept = before.saturating_sub(sessions.len());
+    if swept > 0 {
+        clock
+            .lock()
+            .unwrap()
+            .retain(|user_id| sessions.contains_key(user_id));
+    }
+    swept
+}
+
+// Holds the table weakly so the sweeper stops once the pool it serves is dropped.
+async fn sweep_loop(
+    sessions: Weak<DashMap<Uuid, StickyEntry>>,
+    clock: Weak<Clock>,
+    config: StickyConfig,
+) {
+    let mut ticker = interval(Duration::from_secs(config.sweep_interval_sec));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
 
-        match TcpStream::connect(&conn_string.address()).await {
-            Ok(stream) => Some(stream),
-            Err(e) => {
+    loop {
+        ticker.tick().await;
+        let (Some(sessions), Some(clock)) = (sessions.upgrade(), clock.upgrade()) else {
+            return;
+        };
+        let swept = sweep_expired(&sessions, &clock, &config);
+        if swept > 0 {
+            debug!("Expired {} sticky sessions", swept);
+        }
+    }
+}
+
+impl SmartTcpConnectionPool for SmartTcpConnPool {
+    async fn get_connection(&self, user_id: Uuid) -> Option<TcpStream> {
+        let backend_idx = self.get_or_assign_backend(user_id)?;
+
+        match self.pool.connect(backend_idx, None).await {
+            Some(PooledStream {
+                stream: BackendStream::Plain(stream),
+                ..
+            }) => Some(stream),
+            Some(_) => None,
+            None => {
                 error!(
-                    "Failed to connect to backend {}: {}",
-                    conn_string.get_uuid(),
-                    e
+                    "Failed to connect to backend {}",
+                    self.pool.backends[backend_idx].get_uuid()
                 );
                 None
             }
@@ -75,25 +229,259 @@ impl SmartTcpConnectionPool for SmartTcpConnPool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::backend_conn::ConnString;
+    use crate::domain::backend_status::AdminState;
+    use tokio::net::TcpListener;
+
+    fn config() -> StickyConfig {
+        StickyConfig {
+            ttl_sec: 3600,
+            idle_timeout_sec: 300,
+            sweep_interval_sec: 30,
+            max_entries: 2,
+        }
+    }
+
+    fn pool(backends: usize) -> ConnectionPool {
+        let backends = (0..backends)
+            .map(|i| ConnString::new("127.0.0.1".to_string(), 8080 + i as u16))
+            .collect();
+        ConnectionPool::new(backends, 10)
+    }
 
     #[test]
     fn constructor_test() {
-        let pool = SmartTcpConnPool::new(vec![ConnString::new("127.0.0.1".to_string(), 8080)]);
-        assert_eq!(pool.available_backends.len(), 1);
-        assert_eq!(pool.backend_selector, 0);
+        let pool = SmartTcpConnPool::new(pool(1), config());
+        assert_eq!(pool.pool.backends.len(), 1);
         assert_eq!(pool.user_session_map.len(), 0);
-        assert_eq!(pool.user_last_connection_time.len(), 0);
+    }
+
+    #[test]
+    fn with_pool_keeps_pins_test() {
+        let original = pool(3);
+        let sticky = SmartTcpConnPool::new(original.clone(), config());
+        let user_id = Uuid::new_v4();
+        let pinned = sticky.get_or_assign_backend(user_id).unwrap();
+        let address = original.backends[pinned].address();
+
+        // Drop one of the other backends and put the pinned one last.
+        let mut backends: Vec<ConnString> = original
+            .backends
+            .iter()
+            .filter(|backend| backend.address() != address)
+            .skip(1)
+            .cloned()
+            .collect();
+        backends.push(original.backends[pinned].clone());
+        let reloaded = sticky.with_pool(original.rebuild(backends, 10), config());
+
+        assert_eq!(reloaded.get_or_assign_backend(user_id), Some(1));
+        assert_eq!(reloaded.user_session_map.len(), 1);
+    }
+
+    #[test]
+    fn sticks_to_pinned_backend_test() {
+        let pool = SmartTcpConnPool::new(pool(3), config());
+        let user_id = Uuid::new_v4();
+        let pinned = pool.get_or_assign_backend(user_id).unwrap();
+        for _ in 0..5 {
+            assert_eq!(pool.get_or_assign_backend(user_id), Some(pinned));
+        }
+        assert_eq!(pool.user_session_map.len(), 1);
+    }
+
+    #[test]
+    fn draining_backend_keeps_pinned_clients_test() {
+        let pool = SmartTcpConnPool::new(pool(2), config());
+        let user_id = Uuid::new_v4();
+        let pinned = pool.get_or_assign_backend(user_id).unwrap();
+
+        pool.pool.status[pinned].set_admin_state(AdminState::Draining);
+        assert_eq!(pool.get_or_assign_backend(user_id), Some(pinned));
+        assert_eq!(pool.get_or_assign_backend(Uuid::new_v4()), Some(1 - pinned));
+
+        pool.pool.status[pinned].set_admin_state(AdminState::Maintenance);
+        assert_eq!(pool.get_or_assign_backend(user_id), Some(1 - pinned));
+    }
+
+    #[test]
+    fn assignments_test() {
+        let pool = SmartTcpConnPool::new(pool(2), config());
+        let user_id = SmartTcpConnPool::user_id(7);
+        pool.pin(user_id, 1);
+
+        let assignments = pool.assignments();
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].user_id, user_id.to_string());
+        assert_eq!(assignments[0].backend.as_deref(), Some("127.0.0.1:8081"));
+    }
+
+    #[test]
+    fn repins_when_backend_unhealthy_test() {
+        let pool = SmartTcpConnPool::new(pool(2), config());
+        let user_id = Uuid::new_v4();
+        let pinned = pool.get_or_assign_backend(user_id).unwrap();
+
+        pool.pool.status[pinned].record_probe(false, 1, 1);
+        let repinned = pool.get_or_assign_backend(user_id).unwrap();
+        assert_ne!(repinned, pinned);
+
+        pool.pool.status[pinned].record_probe(true, 1, 1);
+        assert_eq!(pool.get_or_assign_backend(user_id), Some(repinned));
+    }
+
+    #[test]
+    fn repins_when_backend_removed_test() {
+        let pool = SmartTcpConnPool::new(pool(2), config());
+        let user_id = Uuid::new_v4();
+        pool.user_session_map.insert(
+            user_id,
+            StickyEntry {
+                backend_uuid: Uuid::new_v4(),
+                created_at: Instant::now(),
+                last_seen: Instant::now(),
+                referenced: false,
+            },
+        );
+
+        let backend_idx = pool.get_or_assign_backend(user_id).unwrap();
+        let entry = *pool.user_session_map.get(&user_id).unwrap();
+        assert_eq!(
+            entry.backend_uuid,
+            pool.pool.backends[backend_idx].get_uuid()
+        );
+    }
+
+    #[test]
+    fn sweep_removes_expired_entries_test() {
+        let mut config = config();
+        config.idle_timeout_sec = 1;
+        let pool = SmartTcpConnPool::new(pool(1), config);
+        let stale = Instant::now() - Duration::from_secs(2);
+        pool.user_session_map.insert(
+            Uuid::new_v4(),
+            StickyEntry {
+                backend_uuid: pool.pool.backends[0].get_uuid(),
+                created_at: stale,
+                last_seen: stale,
+                referenced: false,
+            },
+        );
+        pool.get_or_assign_backend(Uuid::new_v4());
+
+        assert_eq!(pool.sweep(), 1);
+        assert_eq!(pool.user_session_map.len(), 1);
+    }
+
+    #[test]
+    fn max_entries_evicts_oldest_pin_test() {
+        let pool = SmartTcpConnPool::new(pool(1), config());
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        pool.get_or_assign_backend(first);
+        pool.get_or_assign_backend(second);
+        pool.get_or_assign_backend(third);
+
+        assert_eq!(pool.user_session_map.len(), 2);
+        assert!(!pool.user_session_map.contains_key(&first));
+    }
+
+    #[test]
+    fn eviction_spares_recently_hit_pins_test() {
+        let pool = SmartTcpConnPool::new(pool(1), config());
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        pool.get_or_assign_backend(first);
+        pool.get_or_assign_backend(second);
+        pool.get_or_assign_backend(first);
+        pool.get_or_assign_backend(third);
+
+        assert!(pool.user_session_map.contains_key(&first));
+        assert!(!pool.user_session_map.contains_key(&second));
+
+        // The hand cleared the hit on its way past, so the first pin goes next.
+        pool.get_or_assign_backend(Uuid::new_v4());
+        assert!(!pool.user_session_map.contains_key(&first));
+        assert!(pool.user_session_map.contains_key(&third));
+    }
+
+    #[test]
+    fn eviction_stays_bounded_on_full_table_test() {
+        let mut config = config();
+        config.max_entries = 1000;
+        let pool = SmartTcpConnPool::new(pool(1), config);
+        for _ in 0..5000 {
+            let user_id = Uuid::new_v4();
+            pool.get_or_assign_backend(user_id);
+            pool.get_or_assign_backend(user_id);
+        }
+        assert_eq!(pool.user_session_map.len(), 1000);
+        assert_eq!(pool.clock.lock().unwrap().len(), 1000);
+
+        // Swept pins leave the clock as well.
+        let mut config = self::config();
+        config.idle_timeout_sec = 0;
+        let expiring = SmartTcpConnPool::new(pool.pool.clone(), config);
+        expiring.pin(Uuid::new_v4(), 0);
+        assert_eq!(expiring.sweep(), 1);
+        assert!(expiring.clock.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn concurrent_pins_respect_max_entries_test() {
+        let mut config = config();
+        config.max_entries = 64;
+        let pool = SmartTcpConnPool::new(pool(2), config);
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..500 {
+                        pool.get_or_assign_backend(Uuid::new_v4());
+                        // Evictions and inserts are done under this lock, so len() is exact here.
+                        let _clock = pool.clock.lock().unwrap();
+                        assert!(pool.user_session_map.len() <= 64);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(pool.user_session_map.len(), 64);
+    }
+
+    #[tokio::test]
+    async fn concurrent_assignment_test() {
+        let pool = SmartTcpConnPool::new(pool(4), config());
+        let user_id = Uuid::new_v4();
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move { pool.get_or_assign_backend(user_id) })
+            })
+            .collect();
+
+        let mut pinned = Vec::new();
+        for handle in handles {
+            pinned.push(handle.await.unwrap());
+        }
+        let current = pool.get_or_assign_backend(user_id);
+        assert!(pinned.iter().all(|backend_idx| backend_idx.is_some()));
+        assert!(pinned.contains(&current));
     }
 
     #[tokio::test]
     async fn get_connection_test() {
-        let mut pool = SmartTcpConnPool::new(vec![ConnString::new("127.0.0.1".to_string(), 8080)]);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let backends = vec![ConnString::new(addr.ip().to_string(), addr.port())];
+        let pool = SmartTcpConnPool::new(ConnectionPool::new(backends, 10), config());
+
         let user_id = Uuid::new_v4();
         let connection = pool.get_connection(user_id).await.unwrap();
         assert_eq!(
             connection.peer_addr().unwrap().ip(),
             std::net::IpAddr::from([127, 0, 0, 1])
         );
-        assert_eq!(connection.peer_addr().unwrap().port(), 8080);
+        assert_eq!(connection.peer_addr().unwrap().port(), addr.port());
     }
 }
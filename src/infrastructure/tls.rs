@@ -0,0 +1,629 @@
+use log::warn;
+use rustls::crypto::CryptoProvider;
+use rustls::crypto::ring::default_provider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::version::{TLS12, TLS13};
+use rustls::{ClientConfig, RootCertStore, ServerConfig, SupportedProtocolVersion};
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{Duration, timeout};
+use tokio_rustls::{TlsAcceptor, TlsConnector, client, server};
+
+use crate::config::error::ConfigError;
+use crate::config::listener::ListenerMode;
+use crate::config::tls::{BackendTlsConfig, CertificateConfig, TlsConfig, TlsVersion, suite_name};
+
+pub const ALPN_H2: &[u8] = b"h2";
+pub const ALPN_HTTP11: &[u8] = b"http/1.1";
+
+// Terminates TLS for one listener.
+#[derive(Clone)]
+pub struct TlsTerminator {
+    acceptor: TlsAcceptor,
+    handshake_timeout: Duration,
+}
+
+// Picks a certificate by SNI: exact names first, then `*.` wildcards, then the fallback.
+#[derive(Debug)]
+struct SniResolver {
+    names: HashMap<String, Arc<CertifiedKey>>,
+    wildcards: HashMap<String, Arc<CertifiedKey>>,
+    fallback: Arc<CertifiedKey>,
+}
+
+// Opens TLS to the backends of one group. Every connection shares the client config and
+// with it the session cache, so reconnects resume earlier sessions instead of a full handshake.
+pub struct TlsOriginator {
+    connector: TlsConnector,
+    server_name: Option<ServerName<'static>>,
+}
+
+#[derive(Debug)]
+pub enum MaybeTls<S> {
+    Plain(TcpStream),
+    Tls(Box<S>),
+}
+
+// A client connection, decrypted once its listener terminates TLS.
+pub type ClientStream = MaybeTls<server::TlsStream<TcpStream>>;
+
+// A backend connection, encrypted when its group originates TLS.
+pub type BackendStream = MaybeTls<client::TlsStream<TcpStream>>;
+
+impl TlsTerminator {
+    pub fn new(config: &TlsConfig, mode: ListenerMode) -> Result<TlsTerminator, ConfigError> {
+        let mut provider = default_provider();
+        if let Some(cipher_suites) = &config.cipher_suites {
+            provider.cipher_suites.retain(|suite| {
+                cipher_suites
+                    .iter()
+                    .any(|name| suite_name(suite) == Some(name))
+            });
+        }
+        let resolver = SniResolver::new(&config.certificates, &provider)?;
+        let mut server_config = ServerConfig::builder_with_provider(Arc::new(provider))
+            .with_protocol_versions(&protocol_versions(&config.versions))
+            .map_err(|e| ConfigError::invalid("tls", e.to_string()))?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(resolver));
+        server_config.alpn_protocols = match (&config.alpn, mode) {
+            (Some(alpn), _) => alpn.iter().map(|name| name.as_bytes().to_vec()).collect(),
+            (None, ListenerMode::Http) => vec![ALPN_H2.to_vec(), ALPN_HTTP11.to_vec()],
+            (None, _) => Vec::new(),
+        };
+        Ok(TlsTerminator {
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            handshake_timeout: Duration::from_millis(config.handshake_timeout_ms),
+        })
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> io::Result<ClientStream> {
+        match timeout(self.handshake_timeout, self.acceptor.accept(stream)).await {
+            Ok(Ok(stream)) => Ok(ClientStream::Tls(Box::new(stream))),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "TLS handshake timed out",
+            )),
+        }
+    }
+}
+
+impl TlsOriginator {
+    pub fn new(config: &BackendTlsConfig) -> Result<TlsOriginator, ConfigError> {
+        let mut roots = RootCertStore::empty();
+        match &config.ca_bundle {
+            Some(ca_bundle) => {
+                for cert in load_chain(ca_bundle)? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| read_error(ca_bundle, e.to_string()))?;
+                }
+            }
+            None => {
+                let (added, _) =
+                    roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+                if added == 0 {
+                    warn!("No system CA certificates found, backend TLS will fail verification");
+                }
+            }
+        }
+        let builder = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_protocol_versions(&protocol_versions(&config.versions))
+            .map_err(|e| ConfigError::invalid("tls", e.to_string()))?
+            .with_root_certificates(roots);
+        let client_config = match (&config.client_cert, &config.client_key) {
+            (Some(cert), Some(key)) => builder
+                .with_client_auth_cert(load_chain(cert)?, load_key(key)?)
+                .map_err(|e| read_error(key, e.to_string()))?,
+            _ => builder.with_no_client_auth(),
+        };
+        let server_name = config
+            .server_name
+            .as_ref()
+            .map(|name| ServerName::try_from(name.clone()))
+            .transpose()
+            .map_err(|e| ConfigError::invalid("tls.server_name", e.to_string()))?;
+        Ok(TlsOriginator {
+            connector: TlsConnector::from(Arc::new(client_config)),
+            server_name,
+        })
+    }
+
+    // The certificate is verified against the configured server name, or the backend's host.
+    pub async fn connect(
+        &self,
+        stream: TcpStream,
+        host: &str,
+        alpn: &[&[u8]],
+    ) -> io::Result<BackendStream> {
+        let server_name = match &self.server_name {
+            Some(server_name) => server_name.clone(),
+            None => ServerName::try_from(host.to_string())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        };
+        let stream = if alpn.is_empty() {
+            self.connector.connect(server_name, stream).await?
+        } else {
+            let alpn = alpn.iter().map(|name| name.to_vec()).collect();
+            self.connector
+                .with_alpn(alpn)
+                .connect(server_name, stream)
+                .await?
+        };
+        Ok(BackendStream::Tls(Box::new(stream)))
+    }
+}
+
+impl SniResolver {
+    fn new(
+        certificates: &[CertificateConfig],
+        provider: &CryptoProvider,
+    ) -> Result<SniResolver, ConfigError> {
+        let mut names = HashMap::new();
+        let mut wildcards = HashMap::new();
+        let mut first = None;
+        let mut fallback = None;
+        for certificate in certificates {
+            let key = Arc::new(load_certified_key(certificate, provider)?);
+            if certificate.server_names.is_empty() {
+                fallback.get_or_insert_with(|| Arc::clone(&key));
+            }
+            for name in &certificate.server_names {
+                let name = name.to_ascii_lowercase();
+                match name.strip_prefix("*.") {
+                    Some(suffix) => wildcards.insert(suffix.to_string(), Arc::clone(&key)),
+                    None => names.insert(name, Arc::clone(&key)),
+                };
+            }
+            first.get_or_insert(key);
+        }
+        let fallback = fallback.or(first).ok_or_else(|| {
+            ConfigError::invalid("tls.certificates", "at least one certificate is required")
+        })?;
+        Ok(SniResolver {
+            names,
+            wildcards,
+            fallback,
+        })
+    }
+
+    fn find(&self, server_name: Option<&str>) -> Arc<CertifiedKey> {
+        let Some(server_name) = server_name.map(str::to_ascii_lowercase) else {
+            return Arc::clone(&self.fallback);
+        };
+        self.names
+            .get(&server_name)
+            .or_else(|| {
+                let (_, parent) = server_name.split_once('.')?;
+                self.wildcards.get(parent)
+            })
+            .unwrap_or(&self.fallback)
+            .clone()
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.find(client_hello.server_name()))
+    }
+}
+
+fn protocol_versions(versions: &[TlsVersion]) -> Vec<&'static SupportedProtocolVersion> {
+    versions
+        .iter()
+        .map(|version| match version {
+            TlsVersion::Tls12 => &TLS12,
+            TlsVersion::Tls13 => &TLS13,
+        })
+        .collect()
+}
+
+fn load_certified_key(
+    certificate: &CertificateConfig,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, ConfigError> {
+    let chain = load_chain(&certificate.cert)?;
+    let key = load_key(&certificate.key)?;
+    CertifiedKey::from_der(chain, key, provider)
+        .map_err(|e| read_error(&certificate.key, e.to_string()))
+}
+
+fn load_chain(path: &str) -> Result<Vec<CertificateDer<'static>>, ConfigError> {
+    let chain = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| read_error(path, e.to_string()))?;
+    if chain.is_empty() {
+        return Err(read_error(path, "no certificates found".to_string()));
+    }
+    Ok(chain)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, ConfigError> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| read_error(path, e.to_string()))
+}
+
+fn read_error(path: &str, message: String) -> ConfigError {
+    ConfigError::Read {
+        path: path.to_string(),
+        message,
+    }
+}
+
+impl ClientStream {
+    // Only plaintext can be peeked at; TLS records have to be decrypted first.
+    pub fn peekable(&self) -> Option<&TcpStream> {
+        match self {
+            ClientStream::Plain(stream) => Some(stream),
+            ClientStream::Tls(_) => None,
+        }
+    }
+
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        match self {
+            ClientStream::Plain(_) => None,
+            ClientStream::Tls(stream) => stream.get_ref().1.alpn_protocol(),
+        }
+    }
+
+    pub fn is_tls(&self) -> bool {
+        matches!(self, ClientStream::Tls(_))
+    }
+}
+
+impl BackendStream {
+    pub fn tcp(&self) -> &TcpStream {
+        match self {
+            BackendStream::Plain(stream) => stream,
+            BackendStream::Tls(stream) => stream.get_ref().0,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for MaybeTls<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            MaybeTls::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for MaybeTls<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            MaybeTls::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            MaybeTls::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTls::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            MaybeTls::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rcgen::{CertifiedKey as GeneratedKey, generate_simple_self_signed};
+    use std::path::PathBuf;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::client::TlsStream as ClientTlsStream;
+
+    // Writes a self-signed certificate for `names` to the temp dir.
+    pub fn certificate(names: &[&str], server_names: &[&str]) -> (CertificateConfig, Vec<u8>) {
+        let GeneratedKey { cert, signing_key } = generate_simple_self_signed(
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!("lb-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, contents: String| -> String {
+            let path: PathBuf = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let config = CertificateConfig {
+            cert: write("cert.pem", cert.pem()),
+            key: write("key.pem", signing_key.serialize_pem()),
+            server_names: server_names.iter().map(|name| name.to_string()).collect(),
+        };
+        (config, cert.der().to_vec())
+    }
+
+    pub fn tls_config(certificates: Vec<CertificateConfig>) -> TlsConfig {
+        TlsConfig {
+            certificates,
+            versions: vec![TlsVersion::Tls12, TlsVersion::Tls13],
+            cipher_suites: None,
+            alpn: None,
+            handshake_timeout_ms: 1000,
+        }
+    }
+
+    // Trusts the given self-signed certificates and offers `alpn`.
+    pub fn connector(trusted: &[&[u8]], alpn: &[&[u8]]) -> TlsConnector {
+        let mut roots = rustls::RootCertStore::empty();
+        for der in trusted {
+            roots.add(CertificateDer::from(der.to_vec())).unwrap();
+        }
+        let mut config = rustls::ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|name| name.to_vec()).collect();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    pub async fn connect(
+        connector: &TlsConnector,
+        addr: std::net::SocketAddr,
+        server_name: &str,
+    ) -> io::Result<ClientTlsStream<TcpStream>> {
+        let stream = TcpStream::connect(addr).await?;
+        let server_name = server_name.to_string().try_into().unwrap();
+        connector.connect(server_name, stream).await
+    }
+
+    // Replies with the negotiated ALPN protocol, or `none`, and closes.
+    pub async fn echo_server(terminator: TlsTerminator) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let terminator = terminator.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = terminator.accept(stream).await else {
+                        return;
+                    };
+                    let alpn = stream.alpn_protocol().unwrap_or(b"none").to_vec();
+                    let _ = stream.write_all(&alpn).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn sni_selects_certificate_test() {
+        let (fallback, fallback_der) = certificate(&["default.test"], &[]);
+        let (api, api_der) = certificate(&["api.example.com"], &["api.example.com"]);
+        let (wildcard, wildcard_der) = certificate(&["*.example.com"], &["*.example.com"]);
+        let config = tls_config(vec![fallback, api, wildcard]);
+        let addr = echo_server(TlsTerminator::new(&config, ListenerMode::Tcp).unwrap()).await;
+
+        for (server_name, der) in [
+            ("api.example.com", &api_der),
+            ("www.example.com", &wildcard_der),
+            ("default.test", &fallback_der),
+        ] {
+            let connector = connector(&[der], &[]);
+            let mut stream = connect(&connector, addr, server_name).await.unwrap();
+            let mut alpn = Vec::new();
+            stream.read_to_end(&mut alpn).await.unwrap();
+            assert_eq!(alpn, b"none");
+        }
+        // The wildcard certificate does not cover the bare domain, so verification fails.
+        let connector = connector(&[&wildcard_der], &[]);
+        assert!(connect(&connector, addr, "example.com").await.is_err());
+    }
+
+    #[test]
+    fn fallback_prefers_unnamed_certificate_test() {
+        let (api, api_der) = certificate(&["api.example.com"], &["api.example.com"]);
+        let (fallback, fallback_der) = certificate(&["default.test"], &[]);
+        let resolver = SniResolver::new(&[api.clone(), fallback], &default_provider()).unwrap();
+        assert_eq!(resolver.find(None).cert[0].as_ref(), fallback_der);
+        assert_eq!(
+            resolver.find(Some("other.test")).cert[0].as_ref(),
+            fallback_der
+        );
+        assert_eq!(
+            resolver.find(Some("api.example.com")).cert[0].as_ref(),
+            api_der
+        );
+
+        // Without an unnamed certificate the first one serves everything else.
+        let resolver = SniResolver::new(&[api], &default_provider()).unwrap();
+        assert_eq!(resolver.find(None).cert[0].as_ref(), api_der);
+    }
+
+    #[tokio::test]
+    async fn alpn_and_versions_test() {
+        let (cert, der) = certificate(&["localhost"], &[]);
+        let mut config = tls_config(vec![cert]);
+        let addr = echo_server(TlsTerminator::new(&config, ListenerMode::Http).unwrap()).await;
+        for (offered, expected) in [
+            (&[ALPN_H2, ALPN_HTTP11][..], ALPN_H2),
+            (&[ALPN_HTTP11][..], ALPN_HTTP11),
+        ] {
+            let mut stream = connect(&connector(&[&der], offered), addr, "localhost")
+                .await
+                .unwrap();
+            let mut alpn = Vec::new();
+            stream.read_to_end(&mut alpn).await.unwrap();
+            assert_eq!(alpn, expected);
+        }
+
+        config.versions = vec![TlsVersion::Tls12];
+        config.cipher_suites = Some(vec!["TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256".to_string()]);
+        let addr = echo_server(TlsTerminator::new(&config, ListenerMode::Tcp).unwrap()).await;
+        let stream = connect(&connector(&[&der], &[]), addr, "localhost")
+            .await
+            .unwrap();
+        let connection = stream.get_ref().1;
+        assert_eq!(
+            connection.protocol_version(),
+            Some(rustls::ProtocolVersion::TLSv1_2)
+        );
+        assert_eq!(
+            connection
+                .negotiated_cipher_suite()
+                .and_then(|suite| suite_name(&suite)),
+            Some("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256")
+        );
+    }
+
+    pub fn backend_tls(ca_bundle: &str, server_name: Option<&str>) -> BackendTlsConfig {
+        BackendTlsConfig {
+            ca_bundle: Some(ca_bundle.to_string()),
+            server_name: server_name.map(str::to_string),
+            client_cert: None,
+            client_key: None,
+            versions: vec![TlsVersion::Tls12, TlsVersion::Tls13],
+        }
+    }
+
+    async fn read_backend(
+        originator: &TlsOriginator,
+        addr: std::net::SocketAddr,
+    ) -> io::Result<BackendStream> {
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = originator.connect(stream, "127.0.0.1", &[]).await?;
+        let mut alpn = Vec::new();
+        stream.read_to_end(&mut alpn).await?;
+        Ok(stream)
+    }
+
+    fn handshake_kind(stream: &BackendStream) -> Option<rustls::HandshakeKind> {
+        match stream {
+            MaybeTls::Tls(stream) => stream.get_ref().1.handshake_kind(),
+            MaybeTls::Plain(_) => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn originates_with_server_name_and_resumption_test() {
+        let (cert, _) = certificate(&["backend.internal"], &[]);
+        let addr = echo_server(
+            TlsTerminator::new(&tls_config(vec![cert.clone()]), ListenerMode::Tcp).unwrap(),
+        )
+        .await;
+
+        // The certificate does not cover the backend's address, only the configured name.
+        let originator = TlsOriginator::new(&backend_tls(&cert.cert, None)).unwrap();
+        assert!(read_backend(&originator, addr).await.is_err());
+
+        let originator =
+            TlsOriginator::new(&backend_tls(&cert.cert, Some("backend.internal"))).unwrap();
+        let first = read_backend(&originator, addr).await.unwrap();
+        assert_eq!(handshake_kind(&first), Some(rustls::HandshakeKind::Full));
+        let second = read_backend(&originator, addr).await.unwrap();
+        assert_eq!(
+            handshake_kind(&second),
+            Some(rustls::HandshakeKind::Resumed)
+        );
+
+        // A backend signed by an untrusted key fails verification.
+        let (other, _) = certificate(&["backend.internal"], &[]);
+        let originator =
+            TlsOriginator::new(&backend_tls(&other.cert, Some("backend.internal"))).unwrap();
+        assert!(read_backend(&originator, addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_test() {
+        let (server, _) = certificate(&["backend.internal"], &[]);
+        let (client, client_der) = certificate(&["lb.internal"], &[]);
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(client_der)).unwrap();
+        let provider = Arc::new(default_provider());
+        let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(
+            Arc::new(roots),
+            provider.clone(),
+        )
+        .build()
+        .unwrap();
+        let server_config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(
+                load_chain(&server.cert).unwrap(),
+                load_key(&server.key).unwrap(),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(mut stream) = acceptor.accept(stream).await {
+                        let _ = stream.write_all(b"ok").await;
+                        let _ = stream.shutdown().await;
+                    }
+                });
+            }
+        });
+
+        let mut config = backend_tls(&server.cert, Some("backend.internal"));
+        let originator = TlsOriginator::new(&config).unwrap();
+        assert!(read_backend(&originator, addr).await.is_err());
+
+        config.client_cert = Some(client.cert);
+        config.client_key = Some(client.key);
+        let originator = TlsOriginator::new(&config).unwrap();
+        assert!(read_backend(&originator, addr).await.is_ok());
+
+        config.ca_bundle = Some("/nonexistent/ca.pem".to_string());
+        assert!(matches!(
+            TlsOriginator::new(&config),
+            Err(ConfigError::Read { path, .. }) if path == "/nonexistent/ca.pem"
+        ));
+    }
+
+    #[test]
+    fn missing_files_test() {
+        let mut config = tls_config(vec![CertificateConfig {
+            cert: "/nonexistent/cert.pem".to_string(),
+            key: "/nonexistent/key.pem".to_string(),
+            server_names: Vec::new(),
+        }]);
+        assert!(matches!(
+            TlsTerminator::new(&config, ListenerMode::Tcp),
+            Err(ConfigError::Read { path, .. }) if path == "/nonexistent/cert.pem"
+        ));
+        let (cert, _) = certificate(&["localhost"], &[]);
+        let (other, _) = certificate(&["localhost"], &[]);
+        config.certificates = vec![CertificateConfig {
+            key: other.key,
+            ..cert
+        }];
+        assert!(TlsTerminator::new(&config, ListenerMode::Tcp).is_err());
+    }
+}
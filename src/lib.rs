@@ -0,0 +1,11 @@
+mod config;
+mod core;
+mod domain;
+mod infrastructure;
+
+// What the binary and the benchmarks need; everything else stays internal.
+pub use crate::config::app::AppConfig;
+pub use crate::core::load_balancer::run_load_balancer;
+pub use crate::core::router::Router;
+pub use crate::domain::backend_conn::ConnString;
+pub use crate::infrastructure::fast_tcp_pool::ConnectionPool;
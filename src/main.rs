@@ -1,42 +1,33 @@
-mod config;
-mod core;
-mod domain;
-mod infrastructure;
+use log::error;
 
-use crate::config::app::AppConfig;
-use crate::config::router_map::RouterMap;
-use crate::core::load_balancer::run_load_balancer;
-use crate::domain::backend_conn::ConnString;
-use crate::infrastructure::fast_tcp_pool::ConnectionPool;
+use load_balancer::{AppConfig, Router, run_load_balancer};
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
 
 #[tokio::main]
 async fn main() {
     colog::init();
-    let listen_addr = "127.0.0.1:8080";
-    let target_addr = "127.0.0.1";
-
-    let replicas_start = 3000;
-    let replicas_count = 20;
-
-    let mut app_config = AppConfig::new();
-    app_config.listener(listen_addr.to_string());
-    app_config.request_timeout(30);
-
-    let mut router_map = RouterMap::new();
-    router_map.map_route_range(listen_addr, target_addr, 3000, 3020);
-
-    app_config.router(router_map);
-    app_config.build();
-
-    let mut connections: Vec<ConnString> = Vec::new();
-
-    for i in replicas_start..(replicas_start + replicas_count) {
-        connections.push(ConnString::new(target_addr.to_string(), i));
-    }
-
-    let pool = ConnectionPool::new(connections, replicas_count as usize);
-
-    run_load_balancer(app_config, pool)
+    let config_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+
+    let app_config = match AppConfig::from_file(&config_path) {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let router = match Router::from_config(&app_config) {
+        Ok(router) => router,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    run_load_balancer(&config_path, app_config, router)
         .await
         .expect("Failed to run load balancer");
 }